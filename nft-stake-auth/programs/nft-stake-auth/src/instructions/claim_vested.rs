@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, TokenAccount, MintTo, mint_to},
+    associated_token::AssociatedToken
+};
+
+use crate::state::{Details, VestingEscrow};
+
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump = vesting_escrow.bump,
+        has_one = staker
+    )]
+    pub vesting_escrow: Account<'info, VestingEscrow>,
+
+    #[account(
+        mut,
+        mint::authority = token_authority,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker
+    )]
+    pub reward_receive_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref(),
+        ],
+        bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> ClaimVested<'info> {
+    pub fn mint_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_receive_account.to_account_info(),
+            authority: self.token_authority.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn claim_vested_handler(ctx: Context<ClaimVested>) -> Result<()> {
+    let token_auth_bump = ctx.accounts.stake_details.token_auth_bump;
+    let stake_details_key = ctx.accounts.stake_details.key();
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    // The escrow only keeps the amounts, the vested reward is minted when claimed
+    let vested_reward = ctx.accounts.vesting_escrow.claim(current_time)?;
+
+    let authority_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+
+    mint_to(
+        ctx.accounts.mint_token_ctx().with_signer(&[&authority_seed[..]]),
+        vested_reward
+    )
+}
@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{set_authority, SetAuthority, Token, Mint, spl_token::instruction::AuthorityType};
+use anchor_spl::token_interface::{set_authority, SetAuthority, TokenInterface, Mint, spl_token_2022::instruction::AuthorityType};
 
 use crate::{state::Details, StakeError};
 
@@ -21,7 +21,7 @@ pub struct CloseStaking<'info> {
         mut,
         mint::authority = token_authority,
     )]
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -34,7 +34,7 @@ pub struct CloseStaking<'info> {
     pub token_authority: UncheckedAccount<'info>,
 
     pub creator: Signer<'info>,
-    pub token_program: Program<'info, Token>
+    pub token_program: Interface<'info, TokenInterface>
 }
 
 impl<'info> CloseStaking<'info> {
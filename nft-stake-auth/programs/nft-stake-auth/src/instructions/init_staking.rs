@@ -1,9 +1,9 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token::{Mint, Token, SetAuthority, set_authority, spl_token::instruction::AuthorityType}, 
+    token_interface::{Mint, TokenInterface, SetAuthority, set_authority, spl_token_2022::instruction::AuthorityType}, 
 };
 
-use crate::{state::Details, StakeError};
+use crate::{state::{Details, CollectionSource}, utils::get_collection_source, StakeError};
 
 #[derive(Accounts)]
 pub struct InitStaking<'info> {
@@ -24,12 +24,10 @@ pub struct InitStaking<'info> {
         mut,
         mint::authority = creator
     )]
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
 
-    #[account(
-        mint::decimals = 0,
-    )]
-    pub collection_address: Account<'info, Mint>,
+    /// CHECK: The collection NFT mint, a Core collection or a creator address, validated in the handler
+    pub collection_address: UncheckedAccount<'info>,
 
     #[account(mut)]
     pub creator: Signer<'info>,
@@ -54,7 +52,7 @@ pub struct InitStaking<'info> {
     )]
     pub nft_authority: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>
 }
 
@@ -71,7 +69,12 @@ impl<'info> InitStaking<'info> {
     }
 }
 
-pub fn init_staking_handler(ctx: Context<InitStaking>, reward: u64, minimum_period: i64) -> Result<()> {
+pub fn init_staking_handler(
+    ctx: Context<InitStaking>, 
+    reward: u64, 
+    minimum_period: i64, 
+    non_custodial: bool
+) -> Result<()> {
    
     require_gte!(minimum_period, 0, StakeError::NegativePeriodValue);
 
@@ -83,6 +86,13 @@ pub fn init_staking_handler(ctx: Context<InitStaking>, reward: u64, minimum_peri
     let nft_auth_bump = *ctx.bumps.get("nft_authority").ok_or(StakeError::NftAuthBumpError)?;
     let token_authority = ctx.accounts.token_authority.key();
 
+    let collection_source = get_collection_source(&ctx.accounts.collection_address.to_account_info())?;
+
+    // The Token Metadata freeze is only available to NFTs with Metaplex metadata
+    if non_custodial {
+        require!(collection_source != CollectionSource::TokenGroup, StakeError::TokenGroupCustodialOnly);
+    }
+
     set_authority(
         ctx.accounts.transfer_auth_ctx(),
         AuthorityType::MintTokens,
@@ -99,7 +109,9 @@ pub fn init_staking_handler(ctx: Context<InitStaking>, reward: u64, minimum_peri
         minimum_period,
         stake_bump,
         token_auth_bump,
-        nft_auth_bump
+        nft_auth_bump,
+        non_custodial,
+        collection_source
     );
 
     Ok(())
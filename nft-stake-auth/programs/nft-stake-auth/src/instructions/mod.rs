@@ -1,11 +1,39 @@
 pub use init_staking::*;
 pub use stake::*;
+pub use stake_many::*;
 pub use withdraw_reward::*;
+pub use withdraw_rewards_batch::*;
+pub use claim_vested::*;
 pub use unstake::*;
+pub use add_collection::*;
+pub use set_allowlist::*;
+pub use set_rarity::*;
+pub use set_reward_override::*;
+pub use set_max_per_wallet::*;
+pub use set_lock_tiers::*;
+pub use set_vesting_duration::*;
 pub use close_staking::*;
+pub use stake_compressed::*;
+pub use unstake_compressed::*;
+pub use stake_core::*;
+pub use unstake_core::*;
 
 pub mod init_staking;
 pub mod stake;
+pub mod stake_many;
 pub mod withdraw_reward;
+pub mod withdraw_rewards_batch;
+pub mod claim_vested;
 pub mod unstake;
-pub mod close_staking;
\ No newline at end of file
+pub mod add_collection;
+pub mod set_allowlist;
+pub mod set_rarity;
+pub mod set_reward_override;
+pub mod set_max_per_wallet;
+pub mod set_lock_tiers;
+pub mod set_vesting_duration;
+pub mod close_staking;
+pub mod stake_compressed;
+pub mod unstake_compressed;
+pub mod stake_core;
+pub mod unstake_core;
\ No newline at end of file
@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{Details, LockTier}, StakeError, MAX_LOCK_TIERS};
+
+#[derive(Accounts)]
+pub struct SetLockTiers<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_lock_tiers_handler(ctx: Context<SetLockTiers>, lock_tiers: Vec<LockTier>) -> Result<()> {
+    let staking_status = ctx.accounts.stake_details.is_active;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(MAX_LOCK_TIERS, lock_tiers.len(), StakeError::MaxLockTiersReached);
+
+    for tier in lock_tiers.iter() {
+        require_gt!(tier.duration, 0, StakeError::InvalidLockDuration);
+        require_gt!(tier.multiplier, 0, StakeError::InvalidMultiplier);
+    }
+
+    // Staked NFTs keep the multiplier and lock expiry of the tier they chose
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_lock_tiers(lock_tiers);
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::Details, StakeError};
+
+#[derive(Accounts)]
+pub struct SetRarity<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_rarity_handler(ctx: Context<SetRarity>, root: Option<[u8; 32]>) -> Result<()> {
+    let staking_status = ctx.accounts.stake_details.is_active;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+
+    // Staked NFTs keep the multiplier proven when they were staked
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_rarity(root);
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{Details, NftRecord, RewardOverride}, utils::calc_reward, StakeError};
+
+#[derive(Accounts)]
+#[instruction(nft_mint: Pubkey)]
+pub struct SetRewardOverride<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = RewardOverride::LEN,
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: Account<'info, RewardOverride>,
+
+    /// CHECK: The record of the NFT, only read and written if the NFT is staked
+    #[account(
+        mut,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub nft_record: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+pub fn set_reward_override_handler(ctx: Context<SetRewardOverride>, nft_mint: Pubkey, multiplier: u64) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let staking_status = stake_details.is_active;
+    let minimum_stake_period = stake_details.minimum_period;
+    let reward_emission = stake_details.reward;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+
+    // A newly created override account is still empty
+    let previous_override = if ctx.accounts.reward_override.nft_mint == nft_mint {
+        Some((*ctx.accounts.reward_override).clone())
+    } else {
+        None
+    };
+
+    let nft_record_info = ctx.accounts.nft_record.to_account_info();
+
+    // Checkpoint the reward of a staked NFT, so the override only applies from now
+    if !nft_record_info.data_is_empty() {
+        let mut nft_record = NftRecord::try_deserialize(&mut &nft_record_info.try_borrow_data()?[..])?;
+        let (accrual_start, previous_multiplier) = nft_record.accrual(previous_override.as_ref());
+
+        let (reward_tokens, _current_time, _is_eligible_for_reward) = calc_reward(
+            nft_record.staked_at,
+            accrual_start,
+            minimum_stake_period,
+            reward_emission,
+            previous_multiplier
+        )?;
+
+        nft_record.accrued_reward = nft_record.accrued_reward
+            .checked_add(reward_tokens)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        nft_record.try_serialize(&mut &mut nft_record_info.try_borrow_mut_data()?[..])?;
+    }
+
+    let bump = *ctx.bumps.get("reward_override").ok_or(StakeError::OverrideBumpError)?;
+
+    let reward_override = &mut ctx.accounts.reward_override;
+    **reward_override = RewardOverride::init(nft_mint, multiplier, current_time, bump);
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::Details, StakeError};
+
+#[derive(Accounts)]
+pub struct SetVestingDuration<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_vesting_duration_handler(ctx: Context<SetVestingDuration>, vesting_duration: i64) -> Result<()> {
+    let staking_status = ctx.accounts.stake_details.is_active;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(vesting_duration, 0, StakeError::InvalidVestingDuration);
+
+    // Rewards already in the escrows keep vesting until their current end
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_vesting_duration(vesting_duration);
+
+    Ok(())
+}
@@ -1,11 +1,16 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, solana_program::sysvar};
 use anchor_spl::{
-    token::{Mint, Token, transfer, TokenAccount, Transfer}, 
-    metadata::{MasterEditionAccount, MetadataAccount, Metadata}, 
+    token_interface::{Mint, TokenInterface, transfer_checked, approve, TokenAccount, TransferChecked, Approve}, 
+    metadata::{MasterEditionAccount, MetadataAccount, Metadata, FreezeDelegatedAccount, freeze_delegated_account}, 
     associated_token::AssociatedToken
 };
 
-use crate::{state::{Details, NftRecord}, StakeError};
+use crate::{
+    state::{Details, NftRecord, CollectionSource, RarityProof, StakerAccount}, 
+    utils::{apply_multiplier, get_token_group, is_programmable, transfer_pnft, delegate_and_lock_pnft, TransferPnft, LockPnft}, 
+    StakeError,
+    MULTIPLIER_BASE
+};
 
 #[derive(Accounts)]
 pub struct Stake<'info> {
@@ -32,11 +37,24 @@ pub struct Stake<'info> {
     )]
     pub nft_record: Account<'info, NftRecord>,
 
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            signer.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
     #[account(
         mint::decimals = 0,
         constraint = nft_mint.supply == 1 @ StakeError::TokenNotNFT
     )]
-    nft_mint: Account<'info, Mint>,
+    nft_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
@@ -44,20 +62,19 @@ pub struct Stake<'info> {
         associated_token::authority = signer,
         constraint = nft_token.amount == 1 @ StakeError::TokenAccountEmpty
     )]
-    nft_token: Account<'info, TokenAccount>,
+    nft_token: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
+        mut,
         seeds = [
             b"metadata",
             Metadata::id().as_ref(),
             nft_mint.key().as_ref()
         ],
         seeds::program = Metadata::id(),
-        bump,
-        constraint = nft_metadata.collection.as_ref().unwrap().verified @ StakeError::CollectionNotVerified,
-        constraint = nft_metadata.collection.as_ref().unwrap().key == stake_details.collection @ StakeError::InvalidCollection
+        bump
     )]
-    nft_metadata: Box<Account<'info, MetadataAccount>>,
+    nft_metadata: Option<Box<Account<'info, MetadataAccount>>>,
 
     #[account(
         seeds = [
@@ -69,7 +86,7 @@ pub struct Stake<'info> {
         seeds::program = Metadata::id(),
         bump
     )]
-    nft_edition: Box<Account<'info, MasterEditionAccount>>,
+    nft_edition: Option<Box<Account<'info, MasterEditionAccount>>>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -87,44 +104,228 @@ pub struct Stake<'info> {
         associated_token::mint = nft_mint,
         associated_token::authority = nft_authority
     )]
-    pub nft_custody: Account<'info, TokenAccount>,
+    pub nft_custody: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Token record of the staker's token account, only required for pNFTs
+    #[account(mut)]
+    pub nft_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Token record of the custody token account, only required for custodial pNFTs
+    #[account(mut)]
+    pub custody_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The rule set of the pNFT, validated by the Token Metadata program
+    pub auth_rules: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Validated by the Token Metadata program
+    pub auth_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: Option<UncheckedAccount<'info>>,
 
     #[account(mut)]
     pub signer: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub metadata_program: Option<Program<'info, Metadata>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>
 }
 
 impl<'info> Stake<'info> {
-    pub fn transfer_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
+    pub fn nft_custody_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_custody.as_ref().ok_or(StakeError::CustodyAccountMissing)?.to_account_info())
+    }
+
+    pub fn metadata_program_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.metadata_program.as_ref().ok_or(StakeError::MetadataProgramMissing)?.to_account_info())
+    }
+
+    pub fn nft_metadata_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_metadata.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn nft_edition_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_edition.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn transfer_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>> {
+        let cpi_accounts = TransferChecked {
             from: self.nft_token.to_account_info(),
-            to: self.nft_custody.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            to: self.nft_custody_info()?,
             authority: self.signer.to_account_info()
         };
     
         let cpi_program = self.token_program.to_account_info();
 
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn approve_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Approve<'info>> {
+        let cpi_accounts = Approve {
+            to: self.nft_token.to_account_info(),
+            delegate: self.nft_authority.to_account_info(),
+            authority: self.signer.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
         CpiContext::new(cpi_program, cpi_accounts)
     }
+
+    pub fn freeze_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, FreezeDelegatedAccount<'info>>> {
+        let cpi_accounts = FreezeDelegatedAccount {
+            metadata: self.nft_metadata_info()?,
+            delegate: self.nft_authority.to_account_info(),
+            token_account: self.nft_token.to_account_info(),
+            edition: self.nft_edition_info()?,
+            mint: self.nft_mint.to_account_info(),
+            token_program: self.token_program.to_account_info()
+        };
+
+        let cpi_program = self.metadata_program_info()?;
+
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn transfer_pnft_accounts(&self) -> Result<TransferPnft<'info>> {
+        Ok(TransferPnft {
+            token: self.nft_token.to_account_info(),
+            token_owner: self.signer.to_account_info(),
+            destination: self.nft_custody_info()?,
+            destination_owner: self.nft_authority.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            owner_token_record: self.nft_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            destination_token_record: self.custody_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            authority: self.signer.to_account_info(),
+            payer: self.signer.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
+
+    pub fn lock_pnft_accounts(&self) -> Result<LockPnft<'info>> {
+        Ok(LockPnft {
+            delegate: self.nft_authority.to_account_info(),
+            token_owner: self.signer.to_account_info(),
+            token: self.nft_token.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            token_record: self.nft_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            payer: self.signer.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
 }
 
-pub fn stake_handler(ctx: Context<Stake>) -> Result<()> {
+pub fn stake_handler(
+    ctx: Context<Stake>,
+    allowlist_proof: Vec<[u8; 32]>,
+    rarity: Option<RarityProof>,
+    lock_tier: Option<u8>
+) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
 
-    let staking_status = ctx.accounts.stake_details.is_active;
+    let staking_status = stake_details.is_active;
+    let nft_auth_bump = stake_details.nft_auth_bump;
+    let non_custodial = stake_details.non_custodial;
+    let collection_source = stake_details.collection_source;
+    let stake_details_key = stake_details.key();
     
     require_eq!(staking_status, true, StakeError::StakingInactive);
+    stake_details.require_wallet_limit(ctx.accounts.staker_account.staked_count, 1)?;
+
+    stake_details.verify_allowlist(&ctx.accounts.nft_mint.key(), &allowlist_proof)?;
+
+    // The master edition proves the mint is an NFT rather than a print or a fungible token,
+    // Token-2022 group members have no edition
+    if collection_source.uses_metadata() {
+        require!(ctx.accounts.nft_edition.is_some(), StakeError::MetadataAccountMissing);
+    }
+
+    // Verify the collection of the NFT and find its reward multiplier
+    let collection_multiplier = if stake_details.allowlist_only {
+        // The allowlist replaces the collection check
+        MULTIPLIER_BASE
+    } else {
+        match collection_source {
+            CollectionSource::Metadata | CollectionSource::Creator => {
+                let nft_metadata = ctx.accounts.nft_metadata.as_ref().ok_or(StakeError::MetadataAccountMissing)?;
+
+                stake_details
+                    .verified_metadata_multiplier(nft_metadata)
+                    .ok_or(StakeError::InvalidCollectionOrCreator)?
+            },
+            CollectionSource::TokenGroup => {
+                let group = get_token_group(&ctx.accounts.nft_mint.to_account_info())?;
+
+                stake_details
+                    .collection_multiplier(&group)
+                    .ok_or(StakeError::InvalidCollection)?
+            },
+            // Core assets are staked through stake_core
+            CollectionSource::Core => return err!(StakeError::InvalidCollection)
+        }
+    };
+
+    // Scale the reward of the NFT by its rarity
+    let rarity_multiplier = stake_details.rarity_multiplier(&ctx.accounts.nft_mint.key(), rarity)?;
+    let multiplier = apply_multiplier(collection_multiplier, rarity_multiplier)?;
+
+    // And by the lock tier chosen by the staker
+    let (lock_multiplier, lock_expires_at) = stake_details.lock_tier(lock_tier, current_time)?;
+    let multiplier = apply_multiplier(multiplier, lock_multiplier)?;
 
     let staker = ctx.accounts.signer.key();
     let nft_mint = ctx.accounts.nft_mint.key();
     let bump = *ctx.bumps.get("nft_record").ok_or(StakeError::NftBumpError)?;
 
-    transfer(ctx.accounts.transfer_nft_ctx(), 1)?;
+    let is_pnft = ctx.accounts.nft_metadata
+        .as_ref()
+        .is_some_and(|metadata| is_programmable(metadata));
+    let nft_auth_seed = &[&b"nft-authority"[..], stake_details_key.as_ref(), &[nft_auth_bump]];
+
+    if non_custodial {
+        // Keep the NFT in the staker's wallet, frozen with the nft authority as delegate
+        if is_pnft {
+            delegate_and_lock_pnft(ctx.accounts.lock_pnft_accounts()?, &[&nft_auth_seed[..]])?;
+        } else {
+            approve(ctx.accounts.approve_nft_ctx(), 1)?;
+            freeze_delegated_account(ctx.accounts.freeze_nft_ctx()?.with_signer(&[&nft_auth_seed[..]]))?;
+        }
+    } else if is_pnft {
+        transfer_pnft(ctx.accounts.transfer_pnft_accounts()?, &[])?;
+    } else {
+        transfer_checked(ctx.accounts.transfer_nft_ctx()?, 1, 0)?;
+    }
 
     let nft_record = &mut ctx.accounts.nft_record;
-    **nft_record = NftRecord::init(staker, nft_mint, bump);
+    **nft_record = NftRecord::init(staker, nft_mint, multiplier, lock_expires_at, bump);
+
+    // Count the NFT in the staker account of the wallet
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staked_at = ctx.accounts.nft_record.staked_at;
+
+    let staker_account = &mut ctx.accounts.staker_account;
+    staker_account.init_if_new(stake_details_key, staker, staker_bump);
+    staker_account.add_staked(1, staked_at)?;
 
     Ok(())
 }
\ No newline at end of file
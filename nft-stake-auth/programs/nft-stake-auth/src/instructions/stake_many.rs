@@ -0,0 +1,230 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, transfer_checked, approve, TokenAccount, TransferChecked, Approve},
+    metadata::{MasterEditionAccount, MetadataAccount, Metadata, FreezeDelegatedAccount, freeze_delegated_account},
+    associated_token::{AssociatedToken, Create, create_idempotent, get_associated_token_address_with_program_id}
+};
+
+use crate::{
+    state::{Details, NftRecord, NftProofs, StakerAccount},
+    utils::{apply_multiplier, create_pda_account, is_programmable},
+    StakeError,
+    MULTIPLIER_BASE
+};
+
+/// The accounts of each NFT in the remaining accounts: nft record, nft mint,
+/// nft token, nft metadata, nft edition and nft custody
+pub const ACCOUNTS_PER_NFT: usize = 6;
+
+#[derive(Accounts)]
+pub struct StakeMany<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            signer.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"nft-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.nft_auth_bump
+    )]
+    pub nft_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub metadata_program: Program<'info, Metadata>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+pub fn stake_many_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, StakeMany<'info>>,
+    proofs: Vec<NftProofs>,
+    lock_tier: Option<u8>
+) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let staking_status = stake_details.is_active;
+    let nft_auth_bump = stake_details.nft_auth_bump;
+    let non_custodial = stake_details.non_custodial;
+    let collection_source = stake_details.collection_source;
+
+    let stake_details_key = stake_details.key();
+    let staker = ctx.accounts.signer.key();
+    let token_program_key = ctx.accounts.token_program.key();
+
+    let nft_count = proofs.len() as u64;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gt!(nft_count, 0, StakeError::InvalidBatchAccounts);
+    require_eq!(ctx.remaining_accounts.len(), proofs.len() * ACCOUNTS_PER_NFT, StakeError::InvalidBatchAccounts);
+    stake_details.require_wallet_limit(ctx.accounts.staker_account.staked_count, nft_count)?;
+
+    // Only NFTs verified through their metadata can be staked in a batch
+    require!(collection_source.uses_metadata(), StakeError::InvalidCollection);
+
+    let nft_auth_seed = &[&b"nft-authority"[..], stake_details_key.as_ref(), &[nft_auth_bump]];
+
+    for (nft_accounts, nft_proofs) in ctx.remaining_accounts.chunks(ACCOUNTS_PER_NFT).zip(proofs) {
+        let nft_record_info = &nft_accounts[0];
+        let nft_mint_info = &nft_accounts[1];
+        let nft_token_info = &nft_accounts[2];
+        let nft_metadata_info = &nft_accounts[3];
+        let nft_edition_info = &nft_accounts[4];
+        let nft_custody_info = &nft_accounts[5];
+
+        // The same checks as the accounts of the stake instruction
+        let nft_mint = InterfaceAccount::<Mint>::try_from(nft_mint_info)?;
+        let nft_mint_key = nft_mint.key();
+
+        require_eq!(nft_mint.decimals, 0, StakeError::TokenNotNFT);
+        require_eq!(nft_mint.supply, 1, StakeError::TokenNotNFT);
+
+        let nft_token = InterfaceAccount::<TokenAccount>::try_from(nft_token_info)?;
+
+        require_keys_eq!(
+            nft_token.key(),
+            get_associated_token_address_with_program_id(&staker, &nft_mint_key, &token_program_key),
+            ErrorCode::ConstraintAssociated
+        );
+        require_eq!(nft_token.amount, 1, StakeError::TokenAccountEmpty);
+
+        let nft_metadata = Account::<MetadataAccount>::try_from(nft_metadata_info)?;
+        require_keys_eq!(nft_metadata.mint, nft_mint_key, ErrorCode::ConstraintSeeds);
+
+        // pNFTs need their token records and rule sets, so they are staked one at a time
+        require!(!is_programmable(&nft_metadata), StakeError::PnftBatchUnsupported);
+
+        let (edition_key, _edition_bump) = Pubkey::find_program_address(
+            &[b"metadata", Metadata::id().as_ref(), nft_mint_key.as_ref(), b"edition"],
+            &Metadata::id()
+        );
+        require_keys_eq!(nft_edition_info.key(), edition_key, ErrorCode::ConstraintSeeds);
+        Account::<MasterEditionAccount>::try_from(nft_edition_info)?;
+
+        stake_details.verify_allowlist(&nft_mint_key, &nft_proofs.allowlist_proof)?;
+
+        // Verify the collection of the NFT and find its reward multiplier
+        let collection_multiplier = if stake_details.allowlist_only {
+            // The allowlist replaces the collection check
+            MULTIPLIER_BASE
+        } else {
+            stake_details
+                .verified_metadata_multiplier(&nft_metadata)
+                .ok_or(StakeError::InvalidCollectionOrCreator)?
+        };
+
+        // Scale the reward of the NFT by its rarity
+        let rarity_multiplier = stake_details.rarity_multiplier(&nft_mint_key, nft_proofs.rarity)?;
+        let multiplier = apply_multiplier(collection_multiplier, rarity_multiplier)?;
+
+        // And by the lock tier chosen by the staker
+        let (lock_multiplier, lock_expires_at) = stake_details.lock_tier(lock_tier, current_time)?;
+        let multiplier = apply_multiplier(multiplier, lock_multiplier)?;
+
+        if non_custodial {
+            // Keep the NFT in the staker's wallet, frozen with the nft authority as delegate
+            let approve_accounts = Approve {
+                to: nft_token_info.clone(),
+                delegate: ctx.accounts.nft_authority.to_account_info(),
+                authority: ctx.accounts.signer.to_account_info()
+            };
+
+            approve(CpiContext::new(ctx.accounts.token_program.to_account_info(), approve_accounts), 1)?;
+
+            let freeze_accounts = FreezeDelegatedAccount {
+                metadata: nft_metadata_info.clone(),
+                delegate: ctx.accounts.nft_authority.to_account_info(),
+                token_account: nft_token_info.clone(),
+                edition: nft_edition_info.clone(),
+                mint: nft_mint_info.clone(),
+                token_program: ctx.accounts.token_program.to_account_info()
+            };
+
+            freeze_delegated_account(
+                CpiContext::new(ctx.accounts.metadata_program.to_account_info(), freeze_accounts)
+                    .with_signer(&[&nft_auth_seed[..]])
+            )?;
+        } else {
+            require_keys_eq!(
+                nft_custody_info.key(),
+                get_associated_token_address_with_program_id(&ctx.accounts.nft_authority.key(), &nft_mint_key, &token_program_key),
+                ErrorCode::ConstraintAssociated
+            );
+
+            let create_accounts = Create {
+                payer: ctx.accounts.signer.to_account_info(),
+                associated_token: nft_custody_info.clone(),
+                authority: ctx.accounts.nft_authority.to_account_info(),
+                mint: nft_mint_info.clone(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info()
+            };
+
+            create_idempotent(CpiContext::new(ctx.accounts.associated_token_program.to_account_info(), create_accounts))?;
+
+            let transfer_accounts = TransferChecked {
+                from: nft_token_info.clone(),
+                mint: nft_mint_info.clone(),
+                to: nft_custody_info.clone(),
+                authority: ctx.accounts.signer.to_account_info()
+            };
+
+            transfer_checked(CpiContext::new(ctx.accounts.token_program.to_account_info(), transfer_accounts), 1, 0)?;
+        }
+
+        // Create the record of the NFT
+        let (nft_record_key, bump) = Pubkey::find_program_address(
+            &[b"nft-record", stake_details_key.as_ref(), nft_mint_key.as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(nft_record_info.key(), nft_record_key, ErrorCode::ConstraintSeeds);
+
+        let nft_record_seed = &[&b"nft-record"[..], stake_details_key.as_ref(), nft_mint_key.as_ref(), &[bump]];
+
+        create_pda_account(
+            &ctx.accounts.signer.to_account_info(),
+            nft_record_info,
+            &ctx.accounts.system_program.to_account_info(),
+            NftRecord::LEN,
+            &nft_record_seed[..]
+        )?;
+
+        let nft_record = NftRecord::init(staker, nft_mint_key, multiplier, lock_expires_at, bump);
+        nft_record.try_serialize(&mut &mut nft_record_info.try_borrow_mut_data()?[..])?;
+    }
+
+    // Count the NFTs in the staker account of the wallet
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+
+    let staker_account = &mut ctx.accounts.staker_account;
+    staker_account.init_if_new(stake_details_key, staker, staker_bump);
+    staker_account.add_staked(nft_count, current_time)?;
+
+    Ok(())
+}
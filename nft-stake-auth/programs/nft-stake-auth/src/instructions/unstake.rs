@@ -1,10 +1,15 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, solana_program::sysvar};
 use anchor_spl::{
-    token::{Mint, Token, TokenAccount, MintTo, Transfer, CloseAccount, mint_to, transfer, close_account}, 
+    token_interface::{Mint, TokenInterface, TokenAccount, MintTo, TransferChecked, CloseAccount, Revoke, mint_to, transfer_checked, close_account, revoke}, 
+    metadata::{MasterEditionAccount, MetadataAccount, Metadata, ThawDelegatedAccount, thaw_delegated_account}, 
     associated_token::AssociatedToken
 };
 
-use crate::{state::{Details, NftRecord}, utils::calc_reward, StakeError};
+use crate::{
+    state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, 
+    utils::{calc_reward, is_programmable, transfer_pnft, unlock_and_revoke_pnft, TransferPnft, LockPnft}, 
+    StakeError
+};
 
 #[derive(Accounts)]
 pub struct Unstake<'info> {
@@ -33,25 +38,65 @@ pub struct Unstake<'info> {
     )]
     pub nft_record: Account<'info, NftRecord>,
 
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
     #[account(
         mut,
         mint::authority = token_authority,
+        mint::token_program = reward_token_program
     )]
-    pub reward_mint: Account<'info, Mint>,
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
         init_if_needed,
         payer = staker,
         associated_token::mint = reward_mint,
-        associated_token::authority = staker
+        associated_token::authority = staker,
+        associated_token::token_program = reward_token_program
     )]
-    pub reward_receive_account: Box<Account<'info, TokenAccount>>,
+    pub reward_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mint::decimals = 0,
         constraint = nft_mint.supply == 1 @ StakeError::TokenNotNFT,
     )]
-    nft_mint: Box<Account<'info, Mint>>,
+    nft_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
         init_if_needed,
@@ -59,7 +104,7 @@ pub struct Unstake<'info> {
         associated_token::mint = nft_mint,
         associated_token::authority = staker,
     )]
-    nft_receive_account: Box<Account<'info, TokenAccount>>,
+    nft_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
@@ -67,7 +112,49 @@ pub struct Unstake<'info> {
         associated_token::authority = nft_authority,
         constraint = nft_custody.amount == 1 @ StakeError::TokenAccountEmpty,
     )]
-    pub nft_custody: Box<Account<'info, TokenAccount>>,
+    pub nft_custody: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            Metadata::id().as_ref(),
+            nft_mint.key().as_ref()
+        ],
+        seeds::program = Metadata::id(),
+        bump
+    )]
+    nft_metadata: Option<Box<Account<'info, MetadataAccount>>>,
+
+    #[account(
+        seeds = [
+            b"metadata",
+            Metadata::id().as_ref(),
+            nft_mint.key().as_ref(),
+            b"edition"
+        ],
+        seeds::program = Metadata::id(),
+        bump
+    )]
+    nft_edition: Option<Box<Account<'info, MasterEditionAccount>>>,
+
+    /// CHECK: Token record of the custody token account, only required for custodial pNFTs
+    #[account(mut)]
+    pub custody_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Token record of the staker's token account, only required for pNFTs
+    #[account(mut)]
+    pub receive_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The rule set of the pNFT, validated by the Token Metadata program
+    pub auth_rules: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Validated by the Token Metadata program
+    pub auth_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: Option<UncheckedAccount<'info>>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -92,9 +179,11 @@ pub struct Unstake<'info> {
     #[account(mut)]
     pub staker: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub reward_token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>
+    pub system_program: Program<'info, System>,
+    pub metadata_program: Option<Program<'info, Metadata>>
 }
 
 impl<'info> Unstake<'info> {
@@ -104,67 +193,201 @@ impl<'info> Unstake<'info> {
             to: self.reward_receive_account.to_account_info(),
             authority: self.token_authority.to_account_info()
         };
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = self.reward_token_program.to_account_info();
         CpiContext::new(cpi_program, cpi_accounts)
     }
 
-    pub fn transfer_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.nft_custody.to_account_info(),
+    pub fn nft_custody_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_custody.as_ref().ok_or(StakeError::CustodyAccountMissing)?.to_account_info())
+    }
+
+    pub fn metadata_program_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.metadata_program.as_ref().ok_or(StakeError::MetadataProgramMissing)?.to_account_info())
+    }
+
+    pub fn nft_metadata_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_metadata.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn nft_edition_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_edition.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn transfer_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>> {
+        let cpi_accounts = TransferChecked {
+            from: self.nft_custody_info()?,
+            mint: self.nft_mint.to_account_info(),
             to: self.nft_receive_account.to_account_info(),
             authority: self.nft_authority.to_account_info()
         };
         let cpi_program = self.token_program.to_account_info();
-        CpiContext::new(cpi_program, cpi_accounts)
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
     }
 
-    pub fn close_account_ctx(&self)-> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+    pub fn close_account_ctx(&self)-> Result<CpiContext<'_, '_, '_, 'info, CloseAccount<'info>>> {
         let cpi_accounts = CloseAccount {
-            account: self.nft_custody.to_account_info(),
+            account: self.nft_custody_info()?,
             destination: self.staker.to_account_info(),
             authority: self.nft_authority.to_account_info()
         };
         let cpi_program = self.token_program.to_account_info();
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn thaw_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, ThawDelegatedAccount<'info>>> {
+        let cpi_accounts = ThawDelegatedAccount {
+            metadata: self.nft_metadata_info()?,
+            delegate: self.nft_authority.to_account_info(),
+            token_account: self.nft_receive_account.to_account_info(),
+            edition: self.nft_edition_info()?,
+            mint: self.nft_mint.to_account_info(),
+            token_program: self.token_program.to_account_info()
+        };
+        let cpi_program = self.metadata_program_info()?;
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn revoke_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Revoke<'info>> {
+        let cpi_accounts = Revoke {
+            source: self.nft_receive_account.to_account_info(),
+            authority: self.staker.to_account_info()
+        };
+        let cpi_program = self.token_program.to_account_info();
         CpiContext::new(cpi_program, cpi_accounts)
     }
+
+    pub fn transfer_pnft_accounts(&self) -> Result<TransferPnft<'info>> {
+        Ok(TransferPnft {
+            token: self.nft_custody_info()?,
+            token_owner: self.nft_authority.to_account_info(),
+            destination: self.nft_receive_account.to_account_info(),
+            destination_owner: self.staker.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            owner_token_record: self.custody_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            destination_token_record: self.receive_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            authority: self.nft_authority.to_account_info(),
+            payer: self.staker.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
+
+    pub fn lock_pnft_accounts(&self) -> Result<LockPnft<'info>> {
+        Ok(LockPnft {
+            delegate: self.nft_authority.to_account_info(),
+            token_owner: self.staker.to_account_info(),
+            token: self.nft_receive_account.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            token_record: self.receive_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            payer: self.staker.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
 }
 
 pub fn unstake_handler(ctx: Context<Unstake>) -> Result<()> {
     let stake_details = &ctx.accounts.stake_details;
 
     let staked_at = ctx.accounts.nft_record.staked_at;
+    let accrued_reward = ctx.accounts.nft_record.accrued_reward;
+
+    // The reward accrues at the overridden multiplier from the time of the override
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (accrual_start, multiplier) = ctx.accounts.nft_record.accrual(reward_override.as_ref());
     let minimum_stake_period = stake_details.minimum_period;
     let reward_emission = stake_details.reward;
     let staking_active = stake_details.is_active;
     let token_auth_bump = stake_details.token_auth_bump;
     let nft_auth_bump = stake_details.nft_auth_bump;
+    let non_custodial = stake_details.non_custodial;
     let stake_details_key = stake_details.key();
 
-    let (reward_tokens, _current_time, is_eligible_for_reward) = calc_reward(
+    let (reward_tokens, current_time, is_eligible_for_reward) = calc_reward(
         staked_at, 
+        accrual_start, 
         minimum_stake_period, 
         reward_emission,
-    ).unwrap();
+        multiplier
+    )?;
+
+    // Closing the staking releases the locked NFTs
+    require!(!staking_active || !ctx.accounts.nft_record.is_locked(current_time), StakeError::NftLocked);
+
+    // Add the reward accrued before the last override
+    let reward_tokens = reward_tokens.checked_add(accrued_reward).ok_or(StakeError::ProgramAddError)?;
+
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staker = ctx.accounts.staker.key();
+    ctx.accounts.staker_account.init_if_new(stake_details_key, staker, staker_bump);
 
     let token_auth_seed = &[&b"token-authority"[..], &stake_details_key.as_ref(), &[token_auth_bump]];
     let nft_auth_seed = &[&b"nft-authority"[..], &stake_details_key.as_ref(), &[nft_auth_bump]];
 
     if is_eligible_for_reward && staking_active {
         // Mint Reward Tokens
-        mint_to(
-            ctx.accounts.mint_token_ctx().with_signer(&[&token_auth_seed[..]]), 
-        reward_tokens
-        )?;
+        let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+        if vesting_duration > 0 {
+            // The reward vests in the escrow of the staker and is minted when claimed
+            let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+            let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+            vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+            vesting_escrow.deposit(reward_tokens, current_time, vesting_duration)?;
+        } else {
+            mint_to(
+                ctx.accounts.mint_token_ctx().with_signer(&[&token_auth_seed[..]]), 
+            reward_tokens
+            )?;
+        }
+
+        ctx.accounts.staker_account.add_claimed(reward_tokens)?;
     }
 
-    // Transfer NFT
-    transfer(
-        ctx.accounts.transfer_nft_ctx().with_signer(&[&nft_auth_seed[..]]), 
-        1
-    )?;
+    // Release NFT
+    let is_pnft = ctx.accounts.nft_metadata
+        .as_ref()
+        .is_some_and(|metadata| is_programmable(metadata));
+
+    if non_custodial {
+        // Thaw the NFT in the staker's wallet and remove the nft authority as delegate
+        if is_pnft {
+            unlock_and_revoke_pnft(ctx.accounts.lock_pnft_accounts()?, &[&nft_auth_seed[..]])?;
+        } else {
+            thaw_delegated_account(ctx.accounts.thaw_nft_ctx()?.with_signer(&[&nft_auth_seed[..]]))?;
+            revoke(ctx.accounts.revoke_nft_ctx())?;
+        }
+    } else {
+        if is_pnft {
+            // Token Metadata thaws the custody account to move the pNFT, and refunds its token record to the staker
+            transfer_pnft(ctx.accounts.transfer_pnft_accounts()?, &[&nft_auth_seed[..]])?;
+        } else {
+            transfer_checked(
+                ctx.accounts.transfer_nft_ctx()?.with_signer(&[&nft_auth_seed[..]]), 
+                1,
+                0
+            )?;
+        }
 
-    // Close NFT Custody Account
-    close_account(ctx.accounts.close_account_ctx().with_signer(&[&nft_auth_seed[..]]))?;
+        // Close NFT Custody Account
+        close_account(ctx.accounts.close_account_ctx()?.with_signer(&[&nft_auth_seed[..]]))?;
+    }
     
+    ctx.accounts.staker_account.remove_staked();
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,245 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, TokenAccount, MintTo, mint_to},
+    associated_token::AssociatedToken
+};
+use mpl_bubblegum::{
+    instructions::TransferCpiBuilder,
+    programs::{SPL_ACCOUNT_COMPRESSION_ID, SPL_NOOP_ID},
+    utils::get_asset_id
+};
+
+use crate::{state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, utils::calc_reward, StakeError};
+
+#[derive(Accounts)]
+#[instruction(root: [u8; 32], data_hash: [u8; 32], creator_hash: [u8; 32], nonce: u64)]
+pub struct UnstakeCompressed<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref(),
+        ],
+        bump = nft_record.bump,
+        has_one = staker,
+        constraint = nft_record.nft_mint == get_asset_id(&merkle_tree.key(), nonce) @ StakeError::InvalidAssetId,
+        close = staker
+    )]
+    pub nft_record: Account<'info, NftRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = token_authority,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker
+    )]
+    pub reward_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Validated by the Bubblegum program
+    #[account(mut)]
+    pub tree_config: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the account compression program
+    #[account(mut)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref(),
+        ],
+        bump = stake_details.token_auth_bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"nft-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.nft_auth_bump
+    )]
+    pub nft_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = mpl_bubblegum::ID)]
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = SPL_NOOP_ID)]
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> UnstakeCompressed<'info> {
+    pub fn mint_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_receive_account.to_account_info(),
+            authority: self.token_authority.to_account_info()
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn unstake_compressed_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, UnstakeCompressed<'info>>,
+    root: [u8; 32],
+    data_hash: [u8; 32],
+    creator_hash: [u8; 32],
+    nonce: u64,
+    index: u32
+) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let staked_at = ctx.accounts.nft_record.staked_at;
+    let accrued_reward = ctx.accounts.nft_record.accrued_reward;
+
+    // The reward accrues at the overridden multiplier from the time of the override
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (accrual_start, multiplier) = ctx.accounts.nft_record.accrual(reward_override.as_ref());
+    let minimum_stake_period = stake_details.minimum_period;
+    let reward_emission = stake_details.reward;
+    let staking_active = stake_details.is_active;
+    let token_auth_bump = stake_details.token_auth_bump;
+    let nft_auth_bump = stake_details.nft_auth_bump;
+    let stake_details_key = stake_details.key();
+
+    let (reward_tokens, current_time, is_eligible_for_reward) = calc_reward(
+        staked_at, 
+        accrual_start, 
+        minimum_stake_period, 
+        reward_emission,
+        multiplier
+    )?;
+
+    // Closing the staking releases the locked NFTs
+    require!(!staking_active || !ctx.accounts.nft_record.is_locked(current_time), StakeError::NftLocked);
+
+    // Add the reward accrued before the last override
+    let reward_tokens = reward_tokens.checked_add(accrued_reward).ok_or(StakeError::ProgramAddError)?;
+
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staker = ctx.accounts.staker.key();
+    ctx.accounts.staker_account.init_if_new(stake_details_key, staker, staker_bump);
+
+    let token_auth_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+    let nft_auth_seed = &[&b"nft-authority"[..], stake_details_key.as_ref(), &[nft_auth_bump]];
+
+    if is_eligible_for_reward && staking_active {
+        // Mint Reward Tokens
+        let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+        if vesting_duration > 0 {
+            // The reward vests in the escrow of the staker and is minted when claimed
+            let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+            let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+            vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+            vesting_escrow.deposit(reward_tokens, current_time, vesting_duration)?;
+        } else {
+            mint_to(
+                ctx.accounts.mint_token_ctx().with_signer(&[&token_auth_seed[..]]), 
+                reward_tokens
+            )?;
+        }
+
+        ctx.accounts.staker_account.add_claimed(reward_tokens)?;
+    }
+
+    let proof: Vec<(&AccountInfo<'info>, bool, bool)> = ctx.remaining_accounts
+        .iter()
+        .map(|node| (node, false, false))
+        .collect();
+
+    // Transfer the leaf back to the staker
+    TransferCpiBuilder::new(&ctx.accounts.bubblegum_program)
+        .tree_config(&ctx.accounts.tree_config)
+        .leaf_owner(&ctx.accounts.nft_authority, true)
+        .leaf_delegate(&ctx.accounts.nft_authority, false)
+        .new_leaf_owner(&ctx.accounts.staker)
+        .merkle_tree(&ctx.accounts.merkle_tree)
+        .log_wrapper(&ctx.accounts.log_wrapper)
+        .compression_program(&ctx.accounts.compression_program)
+        .system_program(&ctx.accounts.system_program)
+        .add_remaining_accounts(&proof)
+        .root(root)
+        .data_hash(data_hash)
+        .creator_hash(creator_hash)
+        .nonce(nonce)
+        .index(index)
+        .invoke_signed(&[&nft_auth_seed[..]])?;
+
+    ctx.accounts.staker_account.remove_staked();
+
+    Ok(())
+}
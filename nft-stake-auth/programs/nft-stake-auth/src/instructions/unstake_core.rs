@@ -0,0 +1,219 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, TokenAccount, MintTo, mint_to},
+    associated_token::AssociatedToken
+};
+
+use crate::{
+    state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, 
+    utils::{calc_reward, thaw_core_asset, CoreFreeze, MplCore}, 
+    StakeError
+};
+
+#[derive(Accounts)]
+pub struct UnstakeCore<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref(),
+        ],
+        bump = nft_record.bump,
+        has_one = staker,
+        constraint = nft_record.nft_mint == asset.key() @ StakeError::InvalidAssetId,
+        close = staker
+    )]
+    pub nft_record: Account<'info, NftRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = token_authority,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker
+    )]
+    pub reward_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Validated by the Core program
+    #[account(mut)]
+    pub asset: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the Core program
+    #[account(
+        mut,
+        constraint = stake_details.collection_multiplier(&collection.key()).is_some() @ StakeError::InvalidCollection
+    )]
+    pub collection: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref(),
+        ],
+        bump = stake_details.token_auth_bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"nft-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.nft_auth_bump
+    )]
+    pub nft_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    pub core_program: Program<'info, MplCore>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> UnstakeCore<'info> {
+    pub fn mint_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_receive_account.to_account_info(),
+            authority: self.token_authority.to_account_info()
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+
+    pub fn thaw_asset_accounts(&self) -> CoreFreeze<'info> {
+        CoreFreeze {
+            asset: self.asset.to_account_info(),
+            collection: self.collection.to_account_info(),
+            owner: self.staker.to_account_info(),
+            delegate: self.nft_authority.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            core_program: self.core_program.to_account_info()
+        }
+    }
+}
+
+pub fn unstake_core_handler(ctx: Context<UnstakeCore>) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let staked_at = ctx.accounts.nft_record.staked_at;
+    let accrued_reward = ctx.accounts.nft_record.accrued_reward;
+
+    // The reward accrues at the overridden multiplier from the time of the override
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (accrual_start, multiplier) = ctx.accounts.nft_record.accrual(reward_override.as_ref());
+    let minimum_stake_period = stake_details.minimum_period;
+    let reward_emission = stake_details.reward;
+    let staking_active = stake_details.is_active;
+    let token_auth_bump = stake_details.token_auth_bump;
+    let nft_auth_bump = stake_details.nft_auth_bump;
+    let stake_details_key = stake_details.key();
+
+    let (reward_tokens, current_time, is_eligible_for_reward) = calc_reward(
+        staked_at, 
+        accrual_start, 
+        minimum_stake_period, 
+        reward_emission,
+        multiplier
+    )?;
+
+    // Closing the staking releases the locked NFTs
+    require!(!staking_active || !ctx.accounts.nft_record.is_locked(current_time), StakeError::NftLocked);
+
+    // Add the reward accrued before the last override
+    let reward_tokens = reward_tokens.checked_add(accrued_reward).ok_or(StakeError::ProgramAddError)?;
+
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staker = ctx.accounts.staker.key();
+    ctx.accounts.staker_account.init_if_new(stake_details_key, staker, staker_bump);
+
+    let token_auth_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+    let nft_auth_seed = &[&b"nft-authority"[..], stake_details_key.as_ref(), &[nft_auth_bump]];
+
+    if is_eligible_for_reward && staking_active {
+        // Mint Reward Tokens
+        let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+        if vesting_duration > 0 {
+            // The reward vests in the escrow of the staker and is minted when claimed
+            let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+            let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+            vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+            vesting_escrow.deposit(reward_tokens, current_time, vesting_duration)?;
+        } else {
+            mint_to(
+                ctx.accounts.mint_token_ctx().with_signer(&[&token_auth_seed[..]]), 
+                reward_tokens
+            )?;
+        }
+
+        ctx.accounts.staker_account.add_claimed(reward_tokens)?;
+    }
+
+    // Thaw the asset in the staker's wallet and remove the freeze delegate
+    thaw_core_asset(ctx.accounts.thaw_asset_accounts(), &[&nft_auth_seed[..]])?;
+
+    ctx.accounts.staker_account.remove_staked();
+
+    Ok(())
+}
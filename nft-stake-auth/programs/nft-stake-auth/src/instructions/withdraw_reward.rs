@@ -1,10 +1,10 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token::{Mint, Token, TokenAccount, MintTo, mint_to}, 
+    token_interface::{Mint, TokenInterface, TokenAccount, MintTo, mint_to}, 
     associated_token::AssociatedToken
 };
 
-use crate::{state::{Details, NftRecord}, utils::calc_reward, StakeError};
+use crate::{state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, utils::calc_reward, StakeError};
 
 #[derive(Accounts)]
 pub struct WithdrawReward<'info> {
@@ -31,11 +31,49 @@ pub struct WithdrawReward<'info> {
     )]
     pub nft_record: Account<'info, NftRecord>,
 
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
     #[account(
         mut,
         mint::authority = token_authority,
     )]
-    pub reward_mint: Account<'info, Mint>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         init_if_needed,
@@ -43,7 +81,7 @@ pub struct WithdrawReward<'info> {
         associated_token::mint = reward_mint,
         associated_token::authority = staker
     )]
-    pub reward_receive_account: Account<'info, TokenAccount>,
+    pub reward_receive_account: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -58,7 +96,7 @@ pub struct WithdrawReward<'info> {
     #[account(mut)]
     pub staker: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>
 }
@@ -81,6 +119,11 @@ pub fn withdraw_reward_handler(ctx: Context<WithdrawReward>) -> Result<()> {
     let stake_details = &ctx.accounts.stake_details;
 
     let staked_at = ctx.accounts.nft_record.staked_at;
+    let accrued_reward = ctx.accounts.nft_record.accrued_reward;
+
+    // The reward accrues at the overridden multiplier from the time of the override
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (accrual_start, multiplier) = ctx.accounts.nft_record.accrual(reward_override.as_ref());
     let minimum_stake_period = stake_details.minimum_period;
     let reward_emission = stake_details.reward;
     let staking_status = stake_details.is_active;
@@ -91,22 +134,45 @@ pub fn withdraw_reward_handler(ctx: Context<WithdrawReward>) -> Result<()> {
 
     let (reward_tokens, current_time, is_eligible_for_reward) = calc_reward(
         staked_at, 
+        accrual_start, 
         minimum_stake_period, 
         reward_emission,
-    ).unwrap();
+        multiplier
+    )?;
+
+    // Add the reward accrued before the last override
+    let reward_tokens = reward_tokens.checked_add(accrued_reward).ok_or(StakeError::ProgramAddError)?;
+
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staker = ctx.accounts.staker.key();
+    ctx.accounts.staker_account.init_if_new(stake_details_key, staker, staker_bump);
 
     let authority_seed = &[&b"token-authority"[..], &stake_details_key.as_ref(), &[token_auth_bump]];
  
     if is_eligible_for_reward {
-        mint_to(
-            ctx.accounts.mint_token_ctx().with_signer(&[&authority_seed[..]]),
-             reward_tokens
-        )?;
+        let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+        if vesting_duration > 0 {
+            // The reward vests in the escrow of the staker and is minted when claimed
+            let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+            let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+            vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+            vesting_escrow.deposit(reward_tokens, current_time, vesting_duration)?;
+        } else {
+            mint_to(
+                ctx.accounts.mint_token_ctx().with_signer(&[&authority_seed[..]]),
+                 reward_tokens
+            )?;
+        }
+
+        ctx.accounts.staker_account.add_claimed(reward_tokens)?;
     } else {
         return err!(StakeError::IneligibleForReward);
     }
 
     ctx.accounts.nft_record.staked_at = current_time;
+    ctx.accounts.nft_record.accrued_reward = 0;
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,193 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, TokenAccount, MintTo, mint_to},
+    associated_token::AssociatedToken
+};
+
+use crate::{state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, utils::calc_reward, StakeError};
+
+/// The accounts of each NFT in the remaining accounts: nft record and reward override
+pub const ACCOUNTS_PER_RECORD: usize = 2;
+
+#[derive(Accounts)]
+pub struct WithdrawRewardsBatch<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    #[account(
+        mut,
+        mint::authority = token_authority,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker
+    )]
+    pub reward_receive_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref(),
+        ],
+        bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> WithdrawRewardsBatch<'info> {
+    pub fn mint_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_receive_account.to_account_info(),
+            authority: self.token_authority.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn withdraw_rewards_batch_handler<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawRewardsBatch<'info>>) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let minimum_stake_period = stake_details.minimum_period;
+    let reward_emission = stake_details.reward;
+    let staking_status = stake_details.is_active;
+    let token_auth_bump = stake_details.token_auth_bump;
+    let stake_details_key = stake_details.key();
+    let staker = ctx.accounts.staker.key();
+
+    let record_accounts = ctx.remaining_accounts;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require!(
+        !record_accounts.is_empty() && record_accounts.chunks_exact(ACCOUNTS_PER_RECORD).remainder().is_empty(),
+        StakeError::InvalidBatchAccounts
+    );
+
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let mut total_reward: u64 = 0;
+
+    for accounts in record_accounts.chunks(ACCOUNTS_PER_RECORD) {
+        let nft_record_info = &accounts[0];
+        let reward_override_info = &accounts[1];
+
+        // The same checks as the accounts of the withdraw reward instruction
+        let mut nft_record = Account::<NftRecord>::try_from(nft_record_info)?;
+
+        let nft_record_key = Pubkey::create_program_address(
+            &[b"nft-record", stake_details_key.as_ref(), nft_record.nft_mint.as_ref(), &[nft_record.bump]],
+            ctx.program_id
+        ).map_err(|_| ErrorCode::ConstraintSeeds)?;
+
+        require_keys_eq!(nft_record_info.key(), nft_record_key, ErrorCode::ConstraintSeeds);
+        require_keys_eq!(nft_record.staker, staker, ErrorCode::ConstraintHasOne);
+
+        let (reward_override_key, _reward_override_bump) = Pubkey::find_program_address(
+            &[b"reward-override", stake_details_key.as_ref(), nft_record.nft_mint.as_ref()],
+            ctx.program_id
+        );
+        require_keys_eq!(reward_override_info.key(), reward_override_key, ErrorCode::ConstraintSeeds);
+
+        // The reward accrues at the overridden multiplier from the time of the override
+        let reward_override = RewardOverride::load(reward_override_info)?;
+        let (accrual_start, multiplier) = nft_record.accrual(reward_override.as_ref());
+
+        let (reward_tokens, _current_time, is_eligible_for_reward) = calc_reward(
+            nft_record.staked_at,
+            accrual_start,
+            minimum_stake_period,
+            reward_emission,
+            multiplier
+        )?;
+
+        require!(is_eligible_for_reward, StakeError::IneligibleForReward);
+
+        // Add the reward accrued before the last override
+        total_reward = total_reward
+            .checked_add(reward_tokens)
+            .ok_or(StakeError::ProgramAddError)?
+            .checked_add(nft_record.accrued_reward)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        // Written back right away, so a record passed twice earns nothing the second time
+        nft_record.staked_at = current_time;
+        nft_record.accrued_reward = 0;
+        nft_record.exit(ctx.program_id)?;
+    }
+
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+
+    let staker_account = &mut ctx.accounts.staker_account;
+    staker_account.init_if_new(stake_details_key, staker, staker_bump);
+    staker_account.add_claimed(total_reward)?;
+
+    let authority_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+
+    let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+    if vesting_duration > 0 {
+        // The reward vests in the escrow of the staker and is minted when claimed
+        let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+        let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+        vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+        vesting_escrow.deposit(total_reward, current_time, vesting_duration)?;
+    } else {
+        mint_to(
+            ctx.accounts.mint_token_ctx().with_signer(&[&authority_seed[..]]),
+            total_reward
+        )?;
+    }
+
+    Ok(())
+}
@@ -5,9 +5,19 @@ mod state;
 mod utils;
 
 use instructions::*;
+use state::{LockTier, NftProofs, RarityProof, StakeCompressedParams};
 
 declare_id!("8AJVDu2KYFQZuW5AK8d9VXbEkowvDu22AUCordG4ZPre");
 
+#[constant]
+pub const MULTIPLIER_BASE: u64 = 10_000;
+
+#[constant]
+pub const MAX_COLLECTIONS: usize = 8;
+
+#[constant]
+pub const MAX_LOCK_TIERS: usize = 4;
+
 #[program]
 pub mod nft_stake_auth {
     use super::*;
@@ -15,26 +25,107 @@ pub mod nft_stake_auth {
     pub fn init_staking(
         ctx: Context<InitStaking>, 
         reward: u64, 
-        minimum_period: i64
+        minimum_period: i64,
+        non_custodial: bool
     ) -> Result<()> {
-        init_staking_handler(ctx, reward, minimum_period)
+        init_staking_handler(ctx, reward, minimum_period, non_custodial)
     }
 
-    pub fn stake(ctx: Context<Stake>) -> Result<()> {
-        stake_handler(ctx)
+    pub fn stake(
+        ctx: Context<Stake>,
+        allowlist_proof: Vec<[u8; 32]>,
+        rarity: Option<RarityProof>,
+        lock_tier: Option<u8>
+    ) -> Result<()> {
+        stake_handler(ctx, allowlist_proof, rarity, lock_tier)
+    }
+
+    pub fn stake_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeMany<'info>>,
+        proofs: Vec<NftProofs>,
+        lock_tier: Option<u8>
+    ) -> Result<()> {
+        stake_many_handler(ctx, proofs, lock_tier)
     }
 
     pub fn withdraw_reward(ctx: Context<WithdrawReward>) -> Result<()> {
         withdraw_reward_handler(ctx)
     }
 
+    pub fn withdraw_rewards_batch<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawRewardsBatch<'info>>) -> Result<()> {
+        withdraw_rewards_batch_handler(ctx)
+    }
+
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        claim_vested_handler(ctx)
+    }
+
     pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
         unstake_handler(ctx)
     }
 
+    pub fn add_collection(ctx: Context<AddCollection>, multiplier: u64) -> Result<()> {
+        add_collection_handler(ctx, multiplier)
+    }
+
+    pub fn set_allowlist(ctx: Context<SetAllowlist>, root: Option<[u8; 32]>, allowlist_only: bool) -> Result<()> {
+        set_allowlist_handler(ctx, root, allowlist_only)
+    }
+
+    pub fn set_rarity(ctx: Context<SetRarity>, root: Option<[u8; 32]>) -> Result<()> {
+        set_rarity_handler(ctx, root)
+    }
+
+    pub fn set_reward_override(ctx: Context<SetRewardOverride>, nft_mint: Pubkey, multiplier: u64) -> Result<()> {
+        set_reward_override_handler(ctx, nft_mint, multiplier)
+    }
+
+    pub fn set_max_per_wallet(ctx: Context<SetMaxPerWallet>, max_per_wallet: Option<u64>) -> Result<()> {
+        set_max_per_wallet_handler(ctx, max_per_wallet)
+    }
+
+    pub fn set_lock_tiers(ctx: Context<SetLockTiers>, lock_tiers: Vec<LockTier>) -> Result<()> {
+        set_lock_tiers_handler(ctx, lock_tiers)
+    }
+
+    pub fn set_vesting_duration(ctx: Context<SetVestingDuration>, vesting_duration: i64) -> Result<()> {
+        set_vesting_duration_handler(ctx, vesting_duration)
+    }
+
     pub fn close_staking(ctx: Context<CloseStaking>) -> Result<()> {
         close_staking_handler(ctx)
     }
+
+    pub fn stake_compressed<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeCompressed<'info>>,
+        params: StakeCompressedParams
+    ) -> Result<()> {
+        stake_compressed_handler(ctx, params)
+    }
+
+    pub fn unstake_compressed<'info>(
+        ctx: Context<'_, '_, '_, 'info, UnstakeCompressed<'info>>,
+        root: [u8; 32],
+        data_hash: [u8; 32],
+        creator_hash: [u8; 32],
+        nonce: u64,
+        index: u32
+    ) -> Result<()> {
+        unstake_compressed_handler(ctx, root, data_hash, creator_hash, nonce, index)
+    }
+
+    pub fn stake_core(
+        ctx: Context<StakeCore>,
+        allowlist_proof: Vec<[u8; 32]>,
+        rarity: Option<RarityProof>,
+        lock_tier: Option<u8>
+    ) -> Result<()> {
+        stake_core_handler(ctx, allowlist_proof, rarity, lock_tier)
+    }
+
+    pub fn unstake_core(ctx: Context<UnstakeCore>) -> Result<()> {
+        unstake_core_handler(ctx)
+    }
 }
 
 #[error_code]
@@ -69,4 +160,70 @@ pub enum StakeError {
     ProgramSubError,
     #[msg("unable to multiply the given values")]
     ProgramMulError,
+    #[msg("the token record, sysvar or metadata program account is missing for the pNFT")]
+    PnftAccountMissing,
+    #[msg("unable to build the token metadata instruction for the pNFT")]
+    PnftInstructionError,
+    #[msg("the nft custody account is required for custodial staking")]
+    CustodyAccountMissing,
+    #[msg("the token metadata program account is missing")]
+    MetadataProgramMissing,
+    #[msg("unable to deserialize or hash the compressed NFT metadata")]
+    InvalidCompressedMetadata,
+    #[msg("the asset id doesn't match the nft record")]
+    InvalidAssetId,
+    #[msg("the mint is not a member of the token group")]
+    InvalidGroupMember,
+    #[msg("the metadata account is required to verify the collection")]
+    MetadataAccountMissing,
+    #[msg("token group collections can only be staked in custody")]
+    TokenGroupCustodialOnly,
+    #[msg("the core asset doesn't belong to the staker or the collection")]
+    InvalidCoreAsset,
+    #[msg("unable to divide the given values")]
+    ProgramDivError,
+    #[msg("the reward multiplier must be greater than zero")]
+    InvalidMultiplier,
+    #[msg("the collection is already accepted by the staking")]
+    CollectionAlreadyAdded,
+    #[msg("max collection count reached")]
+    MaxCollectionsReached,
+    #[msg("the NFT has no verified collection or verified creator accepted by the staking")]
+    InvalidCollectionOrCreator,
+    #[msg("the mint is not in the allowlist of the staking")]
+    NotInAllowlist,
+    #[msg("the allowlist root is required to replace the collection check")]
+    AllowlistRootMissing,
+    #[msg("the rarity proof is required by the staking")]
+    RarityProofMissing,
+    #[msg("the rarity multiplier of the mint is not in the rarity tree")]
+    InvalidRarityProof,
+    #[msg("unable to get reward override bump")]
+    OverrideBumpError,
+    #[msg("unable to get staker account bump")]
+    StakerBumpError,
+    #[msg("the remaining accounts don't match the NFTs of the batch")]
+    InvalidBatchAccounts,
+    #[msg("pNFTs can't be staked in a batch")]
+    PnftBatchUnsupported,
+    #[msg("max NFTs per wallet reached")]
+    WalletLimitReached,
+    #[msg("the max NFTs per wallet must be greater than zero")]
+    InvalidWalletLimit,
+    #[msg("the lock tier doesn't exist in the staking")]
+    InvalidLockTier,
+    #[msg("the NFT is locked until its lock tier expires")]
+    NftLocked,
+    #[msg("max lock tier count exceeded")]
+    MaxLockTiersReached,
+    #[msg("the lock duration must be greater than zero")]
+    InvalidLockDuration,
+    #[msg("the vesting duration can't be negative")]
+    InvalidVestingDuration,
+    #[msg("unable to get vesting escrow bump")]
+    VestingBumpError,
+    #[msg("the vesting escrow is required while the rewards vest")]
+    VestingEscrowMissing,
+    #[msg("the collection address must be a collection NFT mint, a Core collection or a creator wallet")]
+    InvalidCollectionAddress,
 }
\ No newline at end of file
@@ -1,5 +1,11 @@
 mod stake_details;
 mod nft_record;
+mod reward_override;
+mod staker_account;
+mod vesting_escrow;
 
 pub use stake_details::*;
-pub use nft_record::*;
\ No newline at end of file
+pub use nft_record::*;
+pub use reward_override::*;
+pub use staker_account::*;
+pub use vesting_escrow::*;
\ No newline at end of file
@@ -1,5 +1,44 @@
 use anchor_lang::prelude::*;
 
+use crate::state::RewardOverride;
+
+/// The rarity multiplier of a mint and its proof against the rarity root
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RarityProof {
+    /// The rarity multiplier in basis points
+    pub multiplier: u64,
+    /// The merkle proof of the mint and multiplier leaf
+    pub proof: Vec<[u8; 32]>
+}
+
+/// The allowlist and rarity proofs of an NFT staked in a batch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NftProofs {
+    /// The merkle proof of the mint against the allowlist root
+    pub allowlist_proof: Vec<[u8; 32]>,
+    /// The rarity multiplier of the mint and its proof
+    pub rarity: Option<RarityProof>
+}
+
+/// The compressed NFT to stake and its proofs, passed to stake_compressed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StakeCompressedParams {
+    /// The root of the merkle tree the leaf is proven against
+    pub root: [u8; 32],
+    /// The nonce of the leaf, from which the asset id is derived
+    pub nonce: u64,
+    /// The index of the leaf in the merkle tree
+    pub index: u32,
+    /// The serialized metadata of the compressed NFT
+    pub metadata: Vec<u8>,
+    /// The merkle proof of the asset id against the allowlist root
+    pub allowlist_proof: Vec<[u8; 32]>,
+    /// The rarity multiplier of the asset and its proof
+    pub rarity: Option<RarityProof>,
+    /// The lock tier chosen by the staker
+    pub lock_tier: Option<u8>
+}
+
 #[account]
 pub struct NftRecord {
     /// The owner/staker of the NFT (32)
@@ -8,17 +47,36 @@ pub struct NftRecord {
     pub nft_mint: Pubkey,
     /// The staking timestamp (8)
     pub staked_at: i64,
+    /// The reward multiplier of the NFT in basis points (8)
+    pub multiplier: u64,
+    /// The reward accrued before the last override of the NFT and not yet paid (8)
+    pub accrued_reward: u64,
+    /// The time until which the NFT can't be unstaked, by its lock tier (8)
+    pub lock_expires_at: i64,
     /// The bump of NFT Record PDA (1)
     pub bump: u8
 }
 
 impl NftRecord {
-    pub const LEN: usize = 8 + 32 + 32 + 8 + 1;
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 1;
 
-    pub fn init(staker: Pubkey, nft_mint: Pubkey, bump: u8) -> Self {
+    pub fn init(staker: Pubkey, nft_mint: Pubkey, multiplier: u64, lock_expires_at: i64, bump: u8) -> Self {
         let clock = Clock::get().unwrap();
         let staked_at = clock.unix_timestamp;
 
-        Self {staker, nft_mint, staked_at, bump}
+        Self {staker, nft_mint, staked_at, multiplier, accrued_reward: 0, lock_expires_at, bump}
+    }
+
+    /// Whether the lock tier of the NFT still prevents unstaking
+    pub fn is_locked(&self, current_time: i64) -> bool {
+        current_time < self.lock_expires_at
+    }
+
+    /// The time from which the unpaid reward accrues and the multiplier it accrues at
+    pub fn accrual(&self, reward_override: Option<&RewardOverride>) -> (i64, u64) {
+        match reward_override {
+            Some(reward_override) => (self.staked_at.max(reward_override.effective_from), reward_override.multiplier),
+            None => (self.staked_at, self.multiplier)
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct RewardOverride {
+    /// The mint of the NFT (32)
+    pub nft_mint: Pubkey,
+    /// The reward multiplier that replaces the one of the NFT in basis points (8)
+    pub multiplier: u64,
+    /// The time from which the override applies (8)
+    pub effective_from: i64,
+    /// The bump of the reward override PDA (1)
+    pub bump: u8
+}
+
+impl RewardOverride {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 1;
+
+    pub fn init(nft_mint: Pubkey, multiplier: u64, effective_from: i64, bump: u8) -> Self {
+        Self {nft_mint, multiplier, effective_from, bump}
+    }
+
+    /// Reads the reward override of a mint, if the creator has set one
+    pub fn load(reward_override: &AccountInfo) -> Result<Option<Self>> {
+        if reward_override.data_is_empty() {
+            return Ok(None);
+        }
+
+        require_keys_eq!(*reward_override.owner, crate::ID, ErrorCode::AccountOwnedByWrongProgram);
+
+        let data = reward_override.try_borrow_data()?;
+
+        Ok(Some(RewardOverride::try_deserialize(&mut &data[..])?))
+    }
+}
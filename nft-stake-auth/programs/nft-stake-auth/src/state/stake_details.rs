@@ -1,5 +1,53 @@
 use anchor_lang::prelude::*;
+use anchor_spl::metadata::MetadataAccount;
 
+use crate::{state::RarityProof, utils::{mint_leaf, rarity_leaf, verify_proof}, StakeError, MULTIPLIER_BASE, MAX_COLLECTIONS, MAX_LOCK_TIERS};
+
+/// Where the collection membership of the staked NFTs is verified
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionSource {
+    /// The verified collection of the Metaplex metadata
+    Metadata,
+    /// The group member extension of the Token-2022 mint
+    TokenGroup,
+    /// The collection of the Metaplex Core asset
+    Core,
+    /// A verified creator of the Metaplex metadata, for collections without a verified collection
+    Creator
+}
+
+impl CollectionSource {
+    /// Whether the NFTs are verified through their Metaplex metadata
+    pub fn uses_metadata(&self) -> bool {
+        matches!(self, CollectionSource::Metadata | CollectionSource::Creator)
+    }
+}
+
+/// An additional collection accepted by the staking
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CollectionRate {
+    /// The verified collection or creator address (32)
+    pub collection: Pubkey,
+    /// The reward multiplier of the collection in basis points (8)
+    pub multiplier: u64
+}
+
+impl CollectionRate {
+    pub const LEN: usize = 32 + 8;
+}
+
+/// A lock-up period the staker can choose at stake time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LockTier {
+    /// The period for which the NFT can't be unstaked - in seconds (8)
+    pub duration: i64,
+    /// The reward multiplier of the tier in basis points (8)
+    pub multiplier: u64
+}
+
+impl LockTier {
+    pub const LEN: usize = 8 + 8;
+}
 
 #[account]
 pub struct Details {
@@ -11,7 +59,7 @@ pub struct Details {
     pub reward_mint: Pubkey,
     /// The rate of reward emission per second (8)
     pub reward: u64,
-    /// The verified collection address of the NFT (32)
+    /// The verified collection or creator address of the NFT (32)
     pub collection: Pubkey,
     /// The minimum stake period to be eligible for reward - in seconds (8)
     pub minimum_period: i64,
@@ -20,11 +68,29 @@ pub struct Details {
     /// The bump of token authority PDA (1)
     pub token_auth_bump: u8,
     /// The bump of nft authority PDA (1)
-    pub nft_auth_bump: u8
+    pub nft_auth_bump: u8,
+    /// Whether the NFTs stay frozen in the staker's wallet instead of custody (1)
+    pub non_custodial: bool,
+    /// The source of the collection verification (1)
+    pub collection_source: CollectionSource,
+    /// The additional collections and their reward multipliers (4 + 40 * MAX_COLLECTIONS)
+    pub collections: Vec<CollectionRate>,
+    /// The merkle root of the mints that can be staked, if the staking has an allowlist (1 + 32)
+    pub allowlist_root: Option<[u8; 32]>,
+    /// Whether the allowlist replaces the collection check, except for Core assets (1)
+    pub allowlist_only: bool,
+    /// The merkle root of the rarity multipliers of the mints, if the staking has one (1 + 32)
+    pub rarity_root: Option<[u8; 32]>,
+    /// The max number of NFTs a wallet can stake, if the staking has a limit (1 + 8)
+    pub max_per_wallet: Option<u64>,
+    /// The lock tiers the staker can choose from (4 + 16 * MAX_LOCK_TIERS)
+    pub lock_tiers: Vec<LockTier>,
+    /// The time over which claimed rewards vest in the escrow of the staker, paid out directly if zero (8)
+    pub vesting_duration: i64
 }
 
 impl Details {
-    pub const LEN: usize = 8 + 1 + 32 + 32 + 8 + 32 + 8 + 1 + 1 + 1;
+    pub const LEN: usize = 8 + 1 + 32 + 32 + 8 + 32 + 8 + 1 + 1 + 1 + 1 + 1 + (4 + CollectionRate::LEN * MAX_COLLECTIONS) + 33 + 1 + 33 + 9 + (4 + LockTier::LEN * MAX_LOCK_TIERS) + 8;
 
     pub fn init(
         creator: Pubkey,
@@ -34,7 +100,9 @@ impl Details {
         minimum_period: i64,
         stake_bump: u8,
         token_auth_bump: u8,
-        nft_auth_bump: u8
+        nft_auth_bump: u8,
+        non_custodial: bool,
+        collection_source: CollectionSource
     ) -> Self {
         Self {
             is_active: true,
@@ -45,8 +113,132 @@ impl Details {
             minimum_period,
             stake_bump,
             token_auth_bump,
-            nft_auth_bump
+            nft_auth_bump,
+            non_custodial,
+            collection_source,
+            collections: vec![],
+            allowlist_root: None,
+            allowlist_only: false,
+            rarity_root: None,
+            max_per_wallet: None,
+            lock_tiers: vec![],
+            vesting_duration: 0
+        }
+    }
+
+    /// The reward multiplier of the collection, if the staking accepts it
+    pub fn collection_multiplier(&self, collection: &Pubkey) -> Option<u64> {
+        if *collection == self.collection {
+            return Some(MULTIPLIER_BASE);
+        }
+
+        self.collections
+            .iter()
+            .find(|rate| rate.collection == *collection)
+            .map(|rate| rate.multiplier)
+    }
+
+    /// The reward multiplier of an NFT by its verified collection, or else by its first accepted verified creator
+    /// if the staking is of a creator's NFTs
+    pub fn metadata_multiplier(&self, verified_collection: Option<Pubkey>, verified_creators: &[Pubkey]) -> Option<u64> {
+        let verified_creators = match self.collection_source {
+            CollectionSource::Creator => verified_creators,
+            _ => &[]
+        };
+
+        verified_collection
+            .and_then(|collection| self.collection_multiplier(&collection))
+            .or_else(|| verified_creators.iter().find_map(|creator| self.collection_multiplier(creator)))
+    }
+
+    /// The reward multiplier of an NFT by the verified collection and creators of its metadata
+    pub fn verified_metadata_multiplier(&self, metadata: &MetadataAccount) -> Option<u64> {
+        let verified_collection = metadata.collection
+            .as_ref()
+            .filter(|collection| collection.verified)
+            .map(|collection| collection.key);
+
+        let verified_creators: Vec<Pubkey> = metadata.data.creators
+            .iter()
+            .flatten()
+            .filter(|creator| creator.verified)
+            .map(|creator| creator.address)
+            .collect();
+
+        self.metadata_multiplier(verified_collection, &verified_creators)
+    }
+
+    /// Checks that the mint is in the allowlist, if the staking has one
+    pub fn verify_allowlist(&self, mint: &Pubkey, proof: &[[u8; 32]]) -> Result<()> {
+        if let Some(root) = self.allowlist_root {
+            require!(verify_proof(proof, root, mint_leaf(mint)), StakeError::NotInAllowlist);
         }
+
+        Ok(())
+    }
+
+    /// The rarity multiplier of the mint, proven against the rarity root if the staking has one
+    pub fn rarity_multiplier(&self, mint: &Pubkey, rarity: Option<RarityProof>) -> Result<u64> {
+        let root = match self.rarity_root {
+            Some(root) => root,
+            None => return Ok(MULTIPLIER_BASE)
+        };
+
+        let RarityProof { multiplier, proof } = rarity.ok_or(StakeError::RarityProofMissing)?;
+
+        require!(verify_proof(&proof, root, rarity_leaf(mint, multiplier)), StakeError::InvalidRarityProof);
+        require_gt!(multiplier, 0, StakeError::InvalidMultiplier);
+
+        Ok(multiplier)
+    }
+
+    pub fn set_rarity(&mut self, root: Option<[u8; 32]>) {
+        self.rarity_root = root;
+    }
+
+    /// The reward multiplier and lock expiry of the chosen lock tier
+    pub fn lock_tier(&self, lock_tier: Option<u8>, staked_at: i64) -> Result<(u64, i64)> {
+        let tier = match lock_tier {
+            Some(index) => self.lock_tiers.get(index as usize).ok_or(StakeError::InvalidLockTier)?,
+            None => return Ok((MULTIPLIER_BASE, staked_at))
+        };
+
+        let lock_expires_at = staked_at
+            .checked_add(tier.duration)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        Ok((tier.multiplier, lock_expires_at))
+    }
+
+    /// Checks that the wallet stays within the per-wallet limit after staking more NFTs, if the staking has one
+    pub fn require_wallet_limit(&self, staked_count: u64, new_nfts: u64) -> Result<()> {
+        if let Some(max_per_wallet) = self.max_per_wallet {
+            let new_staked_count = staked_count.checked_add(new_nfts).ok_or(StakeError::ProgramAddError)?;
+            require_gte!(max_per_wallet, new_staked_count, StakeError::WalletLimitReached);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_lock_tiers(&mut self, lock_tiers: Vec<LockTier>) {
+        self.lock_tiers = lock_tiers;
+    }
+
+    pub fn set_vesting_duration(&mut self, vesting_duration: i64) {
+        self.vesting_duration = vesting_duration;
+    }
+
+    pub fn set_max_per_wallet(&mut self, max_per_wallet: Option<u64>) {
+        self.max_per_wallet = max_per_wallet;
+    }
+
+    pub fn set_allowlist(&mut self, root: Option<[u8; 32]>, allowlist_only: bool) {
+        self.allowlist_root = root;
+        self.allowlist_only = allowlist_only;
+    }
+
+    pub fn add_collection(&mut self, collection: Pubkey, multiplier: u64) {
+        self.collections.push(CollectionRate { collection, multiplier });
     }
 
     pub fn close_staking(&mut self) -> Result<()> {
@@ -0,0 +1,57 @@
+use anchor_lang::prelude::*;
+
+use crate::StakeError;
+
+#[account]
+pub struct StakerAccount {
+    /// The staking the account belongs to (32)
+    pub stake_details: Pubkey,
+    /// The wallet of the staker (32)
+    pub staker: Pubkey,
+    /// The number of NFTs currently staked by the wallet (8)
+    pub staked_count: u64,
+    /// The total reward claimed by the wallet (8)
+    pub total_claimed: u64,
+    /// The time of the first stake of the wallet (8)
+    pub first_staked_at: i64,
+    /// The bump of the staker account PDA (1)
+    pub bump: u8
+}
+
+impl StakerAccount {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 1;
+
+    /// Sets up a newly created account, an existing one keeps its stats
+    pub fn init_if_new(&mut self, stake_details: Pubkey, staker: Pubkey, bump: u8) {
+        if self.staker == Pubkey::default() {
+            self.stake_details = stake_details;
+            self.staker = staker;
+            self.bump = bump;
+        }
+    }
+
+    pub fn add_staked(&mut self, count: u64, current_time: i64) -> Result<()> {
+        if self.first_staked_at == 0 {
+            self.first_staked_at = current_time;
+        }
+
+        self.staked_count = self.staked_count
+            .checked_add(count)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        Ok(())
+    }
+
+    /// NFTs staked before the wallet had a staker account are not counted
+    pub fn remove_staked(&mut self) {
+        self.staked_count = self.staked_count.saturating_sub(1);
+    }
+
+    pub fn add_claimed(&mut self, reward: u64) -> Result<()> {
+        self.total_claimed = self.total_claimed
+            .checked_add(reward)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        Ok(())
+    }
+}
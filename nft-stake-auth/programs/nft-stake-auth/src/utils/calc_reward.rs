@@ -1,18 +1,55 @@
 use anchor_lang::prelude::*;
-use crate::StakeError;
+use crate::{StakeError, MULTIPLIER_BASE};
+
+/// Scales the amount by a multiplier in basis points
+pub fn apply_multiplier(amount: u64, multiplier: u64) -> Result<u64> {
+    let scaled = (amount as u128)
+        .checked_mul(multiplier as u128)
+        .ok_or(StakeError::ProgramMulError)?
+        .checked_div(MULTIPLIER_BASE as u128)
+        .ok_or(StakeError::ProgramDivError)?;
+
+    match u64::try_from(scaled) {
+        Ok(scaled) => Ok(scaled),
+        _ => err!(StakeError::ProgramMulError)
+    }
+}
 
 pub fn calc_reward(
     staked_at: i64,
+    accrual_start: i64,
     minimum_stake_period: i64,
     reward_emission: u64,
+    multiplier: u64
 ) -> Result<(u64, i64, bool)> {
     let clock = Clock::get().unwrap();
     let current_time = clock.unix_timestamp;
 
+    let (reward_tokens, is_eligible_for_reward) = calc_reward_at(
+        current_time,
+        staked_at,
+        accrual_start,
+        minimum_stake_period,
+        reward_emission,
+        multiplier
+    )?;
+
+    Ok((reward_tokens, current_time, is_eligible_for_reward))
+}
+
+/// The reward accrued since the accrual start at the given time, and whether the minimum period has passed
+pub fn calc_reward_at(
+    current_time: i64,
+    staked_at: i64,
+    accrual_start: i64,
+    minimum_stake_period: i64,
+    reward_emission: u64,
+    multiplier: u64
+) -> Result<(u64, bool)> {
     let reward_eligible_time = staked_at.checked_add(minimum_stake_period).ok_or(StakeError::ProgramAddError)?;
     let is_eligible_for_reward = current_time >= reward_eligible_time;
 
-    let rewardable_time_i64 = current_time.checked_sub(staked_at).ok_or(StakeError::ProgramSubError)?;
+    let rewardable_time_i64 = current_time.checked_sub(accrual_start).ok_or(StakeError::ProgramSubError)?;
 
     let rewardable_time_u64 = match u64::try_from(rewardable_time_i64) {
         Ok(time) => time,
@@ -20,5 +57,48 @@ pub fn calc_reward(
     };
 
     let reward_tokens = rewardable_time_u64.checked_mul(reward_emission).ok_or(StakeError::ProgramMulError)?;
-    Ok((reward_tokens, current_time, is_eligible_for_reward))
+    let reward_tokens = apply_multiplier(reward_tokens, multiplier)?;
+
+    Ok((reward_tokens, is_eligible_for_reward))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reward_accrues_from_the_accrual_start() {
+        // Overridden at 150, the reward before the override is paid separately
+        assert_eq!(calc_reward_at(200, 100, 150, 0, 10, MULTIPLIER_BASE).unwrap(), (500, true));
+        assert_eq!(calc_reward_at(200, 100, 100, 0, 10, MULTIPLIER_BASE).unwrap(), (1_000, true));
+    }
+
+    #[test]
+    fn reward_is_scaled_by_the_multiplier() {
+        assert_eq!(calc_reward_at(200, 100, 100, 0, 10, 25_000).unwrap(), (2_500, true));
+        assert_eq!(calc_reward_at(200, 100, 100, 0, 10, 5_000).unwrap(), (500, true));
+        assert_eq!(calc_reward_at(200, 100, 100, 0, 3, 3_333).unwrap(), (99, true));
+    }
+
+    #[test]
+    fn eligibility_follows_the_stake_time() {
+        // The minimum period counts from the stake, not from the accrual start
+        assert_eq!(calc_reward_at(200, 100, 180, 100, 10, MULTIPLIER_BASE).unwrap(), (200, true));
+        assert_eq!(calc_reward_at(199, 100, 180, 100, 10, MULTIPLIER_BASE).unwrap(), (190, false));
+    }
+
+    #[test]
+    fn overflowing_reward_is_an_error() {
+        // The reward fits in a u64 before the multiplier doubles it
+        let reward_emission = u64::MAX / 100;
+
+        assert!(calc_reward_at(100, 0, 0, 0, reward_emission, MULTIPLIER_BASE).is_ok());
+        assert!(calc_reward_at(100, 0, 0, 0, reward_emission, 2 * MULTIPLIER_BASE) == Err(StakeError::ProgramMulError.into()));
+        assert!(calc_reward_at(200, 0, 0, 0, reward_emission, MULTIPLIER_BASE) == Err(StakeError::ProgramMulError.into()));
+    }
+
+    #[test]
+    fn accrual_start_after_the_current_time_is_an_error() {
+        assert!(calc_reward_at(100, 100, 150, 0, 10, MULTIPLIER_BASE) == Err(StakeError::FailedTimeConversion.into()));
+    }
 }
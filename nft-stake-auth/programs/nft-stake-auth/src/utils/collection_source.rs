@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{token, token_2022, token_interface::Mint};
+
+use crate::{state::CollectionSource, utils::{is_core_collection, is_token_group}, StakeError};
+
+/// Returns where the membership of the given collection is verified
+pub fn get_collection_source(collection: &AccountInfo) -> Result<CollectionSource> {
+    // Core collections are plain accounts, the others are the mint of the collection NFT
+    if is_core_collection(collection)? {
+        return Ok(CollectionSource::Core);
+    }
+
+    // A verified creator is a wallet, or an address no program owns
+    if collection.owner == &System::id() {
+        return Ok(CollectionSource::Creator);
+    }
+
+    require!(
+        collection.owner == &token::ID || collection.owner == &token_2022::ID,
+        StakeError::InvalidCollectionAddress
+    );
+
+    let collection_mint = InterfaceAccount::<Mint>::try_from(collection)?;
+    require_eq!(collection_mint.decimals, 0, StakeError::TokenNotNFT);
+
+    // Collections with the Token-2022 group extension verify membership on the NFT mint
+    if is_token_group(collection)? {
+        Ok(CollectionSource::TokenGroup)
+    } else {
+        Ok(CollectionSource::Metadata)
+    }
+}
@@ -0,0 +1,139 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, program::invoke_signed}
+};
+
+use crate::StakeError;
+
+mod mpl_core {
+    anchor_lang::declare_id!("CoREENxT6tW1HoK8ypY1SxRMZTcVPm7R94rH4PZNhX7d");
+}
+
+pub const MPL_CORE_ID: Pubkey = mpl_core::ID;
+
+#[derive(Clone)]
+pub struct MplCore;
+
+impl Id for MplCore {
+    fn id() -> Pubkey {
+        MPL_CORE_ID
+    }
+}
+
+// Instruction discriminators of the Core program
+const ADD_PLUGIN_V1: u8 = 2;
+const REMOVE_PLUGIN_V1: u8 = 4;
+const UPDATE_PLUGIN_V1: u8 = 6;
+
+// The freeze delegate variant of the plugin and plugin type enums
+const FREEZE_DELEGATE_PLUGIN: u8 = 1;
+// The address variant of the plugin authority enum
+const ADDRESS_AUTHORITY: u8 = 3;
+
+// Account keys of the Core program
+const ASSET_V1_KEY: u8 = 1;
+const COLLECTION_V1_KEY: u8 = 5;
+// The collection variant of the asset update authority
+const COLLECTION_UPDATE_AUTHORITY: u8 = 2;
+
+pub struct CoreFreeze<'info> {
+    pub asset: AccountInfo<'info>,
+    pub collection: AccountInfo<'info>,
+    pub owner: AccountInfo<'info>,
+    pub delegate: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub core_program: AccountInfo<'info>
+}
+
+impl<'info> CoreFreeze<'info> {
+    fn instruction(&self, authority: Pubkey, data: Vec<u8>) -> Instruction {
+        Instruction {
+            program_id: MPL_CORE_ID,
+            accounts: vec![
+                AccountMeta::new(self.asset.key(), false),
+                AccountMeta::new(self.collection.key(), false),
+                AccountMeta::new(self.owner.key(), true),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new_readonly(self.system_program.key(), false),
+                // The log wrapper is not used
+                AccountMeta::new_readonly(MPL_CORE_ID, false)
+            ],
+            data
+        }
+    }
+
+    fn account_infos(&self) -> Vec<AccountInfo<'info>> {
+        vec![
+            self.asset.clone(),
+            self.collection.clone(),
+            self.owner.clone(),
+            self.delegate.clone(),
+            self.system_program.clone(),
+            self.core_program.clone()
+        ]
+    }
+}
+
+/// Returns the owner and the collection of a Core asset
+pub fn get_core_asset(asset: &AccountInfo) -> Result<(Pubkey, Option<Pubkey>)> {
+    require_keys_eq!(*asset.owner, MPL_CORE_ID, StakeError::InvalidCoreAsset);
+
+    let data = asset.try_borrow_data()?;
+
+    // Key (1) + owner (32) + update authority (1 + 32)
+    require!(data.len() >= 66 && data[0] == ASSET_V1_KEY, StakeError::InvalidCoreAsset);
+
+    let owner = Pubkey::try_from(&data[1..33]).unwrap();
+
+    let collection = if data[33] == COLLECTION_UPDATE_AUTHORITY {
+        Some(Pubkey::try_from(&data[34..66]).unwrap())
+    } else {
+        None
+    };
+
+    Ok((owner, collection))
+}
+
+/// Whether the account is a Core collection
+pub fn is_core_collection(collection: &AccountInfo) -> Result<bool> {
+    if collection.owner != &MPL_CORE_ID {
+        return Ok(false);
+    }
+
+    let data = collection.try_borrow_data()?;
+
+    Ok(data.first() == Some(&COLLECTION_V1_KEY))
+}
+
+/// Adds a frozen freeze delegate plugin to the asset, with the delegate as its authority
+pub fn freeze_core_asset(accounts: CoreFreeze) -> Result<()> {
+    let mut data = vec![ADD_PLUGIN_V1, FREEZE_DELEGATE_PLUGIN, 1, 1, ADDRESS_AUTHORITY];
+    data.extend_from_slice(accounts.delegate.key().as_ref());
+
+    let add_plugin_ix = accounts.instruction(accounts.owner.key(), data);
+
+    invoke_signed(&add_plugin_ix, &accounts.account_infos(), &[])?;
+
+    Ok(())
+}
+
+/// Thaws the asset and removes the freeze delegate plugin
+pub fn thaw_core_asset(accounts: CoreFreeze, delegate_seeds: &[&[&[u8]]]) -> Result<()> {
+    let update_plugin_ix = accounts.instruction(
+        accounts.delegate.key(),
+        vec![UPDATE_PLUGIN_V1, FREEZE_DELEGATE_PLUGIN, 0]
+    );
+
+    let remove_plugin_ix = accounts.instruction(
+        accounts.owner.key(),
+        vec![REMOVE_PLUGIN_V1, FREEZE_DELEGATE_PLUGIN]
+    );
+
+    let account_infos = accounts.account_infos();
+
+    // The delegate PDA signs the thaw, the owner signs the removal
+    invoke_signed(&update_plugin_ix, &account_infos, delegate_seeds)?;
+    invoke_signed(&remove_plugin_ix, &account_infos, &[])?;
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer}
+};
+
+/// Creates a PDA owned by the program, the same way anchor's init does,
+/// so an account that already received lamports can still be created
+pub fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]]
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let current_lamports = account.lamports();
+    let signer = &[signer_seeds];
+
+    if current_lamports == 0 {
+        let cpi_accounts = CreateAccount {
+            from: payer.clone(),
+            to: account.clone()
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer);
+
+        return create_account(cpi_ctx, rent, space as u64, &crate::ID);
+    }
+
+    let required_lamports = rent.saturating_sub(current_lamports);
+
+    if required_lamports > 0 {
+        let cpi_accounts = Transfer {
+            from: payer.clone(),
+            to: account.clone()
+        };
+
+        transfer(CpiContext::new(system_program.clone(), cpi_accounts), required_lamports)?;
+    }
+
+    let allocate_accounts = Allocate {
+        account_to_allocate: account.clone()
+    };
+
+    allocate(CpiContext::new_with_signer(system_program.clone(), allocate_accounts, signer), space as u64)?;
+
+    let assign_accounts = Assign {
+        account_to_assign: account.clone()
+    };
+
+    assign(CpiContext::new_with_signer(system_program.clone(), assign_accounts, signer), &crate::ID)
+}
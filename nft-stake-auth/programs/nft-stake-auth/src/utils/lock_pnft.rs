@@ -0,0 +1,180 @@
+use anchor_lang::{prelude::*, solana_program::program::invoke_signed};
+use mpl_token_metadata::instruction::{
+    builders::{DelegateBuilder, LockBuilder, RevokeBuilder, UnlockBuilder},
+    DelegateArgs, InstructionBuilder, LockArgs, RevokeArgs, UnlockArgs
+};
+
+use crate::StakeError;
+
+pub struct LockPnft<'info> {
+    pub delegate: AccountInfo<'info>,
+    pub token_owner: AccountInfo<'info>,
+    pub token: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub metadata: AccountInfo<'info>,
+    pub edition: AccountInfo<'info>,
+    pub token_record: AccountInfo<'info>,
+    pub payer: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub sysvar_instructions: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub metadata_program: AccountInfo<'info>,
+    pub auth_rules_program: Option<AccountInfo<'info>>,
+    pub auth_rules: Option<AccountInfo<'info>>
+}
+
+impl<'info> LockPnft<'info> {
+    fn auth_rules_keys(&self) -> Option<(Pubkey, Pubkey)> {
+        match (&self.auth_rules_program, &self.auth_rules) {
+            (Some(program), Some(rules)) => Some((program.key(), rules.key())),
+            _ => None
+        }
+    }
+
+    fn account_infos(&self) -> Vec<AccountInfo<'info>> {
+        let mut account_infos = vec![
+            self.delegate.clone(),
+            self.token_owner.clone(),
+            self.token.clone(),
+            self.mint.clone(),
+            self.metadata.clone(),
+            self.edition.clone(),
+            self.token_record.clone(),
+            self.payer.clone(),
+            self.system_program.clone(),
+            self.sysvar_instructions.clone(),
+            self.token_program.clone(),
+            self.metadata_program.clone()
+        ];
+
+        if let (Some(program), Some(rules)) = (&self.auth_rules_program, &self.auth_rules) {
+            account_infos.push(program.clone());
+            account_infos.push(rules.clone());
+        }
+
+        account_infos
+    }
+}
+
+/// Approves the delegate as the staking delegate of the pNFT
+/// and locks it in the owner's token account
+pub fn delegate_and_lock_pnft(accounts: LockPnft, delegate_seeds: &[&[&[u8]]]) -> Result<()> {
+    let mut delegate_builder = DelegateBuilder::new();
+
+    delegate_builder
+        .delegate(accounts.delegate.key())
+        .metadata(accounts.metadata.key())
+        .master_edition(accounts.edition.key())
+        .token_record(accounts.token_record.key())
+        .mint(accounts.mint.key())
+        .token(accounts.token.key())
+        .authority(accounts.token_owner.key())
+        .payer(accounts.payer.key())
+        .system_program(accounts.system_program.key())
+        .sysvar_instructions(accounts.sysvar_instructions.key())
+        .spl_token_program(accounts.token_program.key());
+
+    let mut lock_builder = LockBuilder::new();
+
+    lock_builder
+        .authority(accounts.delegate.key())
+        .token_owner(accounts.token_owner.key())
+        .token(accounts.token.key())
+        .mint(accounts.mint.key())
+        .metadata(accounts.metadata.key())
+        .edition(accounts.edition.key())
+        .token_record(accounts.token_record.key())
+        .payer(accounts.payer.key())
+        .system_program(accounts.system_program.key())
+        .sysvar_instructions(accounts.sysvar_instructions.key())
+        .spl_token_program(accounts.token_program.key());
+
+    if let Some((auth_rules_program, auth_rules)) = accounts.auth_rules_keys() {
+        delegate_builder
+            .authorization_rules_program(auth_rules_program)
+            .authorization_rules(auth_rules);
+
+        lock_builder
+            .authorization_rules_program(auth_rules_program)
+            .authorization_rules(auth_rules);
+    }
+
+    let delegate_ix = delegate_builder
+        .build(DelegateArgs::StakingV1 { amount: 1, authorization_data: None })
+        .map_err(|_| StakeError::PnftInstructionError)?
+        .instruction();
+
+    let lock_ix = lock_builder
+        .build(LockArgs::V1 { authorization_data: None })
+        .map_err(|_| StakeError::PnftInstructionError)?
+        .instruction();
+
+    let account_infos = accounts.account_infos();
+
+    // The owner signs the delegation, the delegate PDA signs the lock
+    invoke_signed(&delegate_ix, &account_infos, &[])?;
+    invoke_signed(&lock_ix, &account_infos, delegate_seeds)?;
+
+    Ok(())
+}
+
+/// Unlocks the pNFT in the owner's token account and revokes the staking delegate
+pub fn unlock_and_revoke_pnft(accounts: LockPnft, delegate_seeds: &[&[&[u8]]]) -> Result<()> {
+    let mut unlock_builder = UnlockBuilder::new();
+
+    unlock_builder
+        .authority(accounts.delegate.key())
+        .token_owner(accounts.token_owner.key())
+        .token(accounts.token.key())
+        .mint(accounts.mint.key())
+        .metadata(accounts.metadata.key())
+        .edition(accounts.edition.key())
+        .token_record(accounts.token_record.key())
+        .payer(accounts.payer.key())
+        .system_program(accounts.system_program.key())
+        .sysvar_instructions(accounts.sysvar_instructions.key())
+        .spl_token_program(accounts.token_program.key());
+
+    let mut revoke_builder = RevokeBuilder::new();
+
+    revoke_builder
+        .delegate(accounts.delegate.key())
+        .metadata(accounts.metadata.key())
+        .master_edition(accounts.edition.key())
+        .token_record(accounts.token_record.key())
+        .mint(accounts.mint.key())
+        .token(accounts.token.key())
+        .authority(accounts.token_owner.key())
+        .payer(accounts.payer.key())
+        .system_program(accounts.system_program.key())
+        .sysvar_instructions(accounts.sysvar_instructions.key())
+        .spl_token_program(accounts.token_program.key());
+
+    if let Some((auth_rules_program, auth_rules)) = accounts.auth_rules_keys() {
+        unlock_builder
+            .authorization_rules_program(auth_rules_program)
+            .authorization_rules(auth_rules);
+
+        revoke_builder
+            .authorization_rules_program(auth_rules_program)
+            .authorization_rules(auth_rules);
+    }
+
+    let unlock_ix = unlock_builder
+        .build(UnlockArgs::V1 { authorization_data: None })
+        .map_err(|_| StakeError::PnftInstructionError)?
+        .instruction();
+
+    let revoke_ix = revoke_builder
+        .build(RevokeArgs::StakingV1)
+        .map_err(|_| StakeError::PnftInstructionError)?
+        .instruction();
+
+    let account_infos = accounts.account_infos();
+
+    // The delegate PDA signs the unlock, the owner signs the revoke
+    invoke_signed(&unlock_ix, &account_infos, delegate_seeds)?;
+    invoke_signed(&revoke_ix, &account_infos, &[])?;
+
+    Ok(())
+}
@@ -1,3 +1,17 @@
 pub use calc_reward::*;
+pub use transfer_pnft::*;
+pub use lock_pnft::*;
+pub use token_group::*;
+pub use core_asset::*;
+pub use collection_source::*;
+pub use merkle_proof::*;
+pub use create_pda::*;
 
 pub mod calc_reward;
+pub mod transfer_pnft;
+pub mod lock_pnft;
+pub mod token_group;
+pub mod core_asset;
+pub mod collection_source;
+pub mod merkle_proof;
+pub mod create_pda;
\ No newline at end of file
@@ -0,0 +1,71 @@
+use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
+use anchor_spl::token_2022::{self, spl_token_2022::state::{Account, Mint}};
+
+use crate::StakeError;
+
+// Token-2022 extension types newer than the spl-token-2022 version used by anchor-spl
+const TOKEN_GROUP_EXTENSION: u16 = 21;
+const GROUP_MEMBER_POINTER_EXTENSION: u16 = 22;
+const TOKEN_GROUP_MEMBER_EXTENSION: u16 = 23;
+
+/// Returns the value of the given extension from the TLV data of a Token-2022 mint
+fn get_mint_extension(data: &[u8], extension_type: u16) -> Option<&[u8]> {
+    if data.len() <= Mint::LEN {
+        return None;
+    }
+
+    // The extensions start after the padded base state and the account type
+    let mut index = Account::LEN + 1;
+
+    while index + 4 <= data.len() {
+        let current_type = u16::from_le_bytes([data[index], data[index + 1]]);
+        let length = u16::from_le_bytes([data[index + 2], data[index + 3]]) as usize;
+
+        let start = index + 4;
+        let end = start.checked_add(length).filter(|end| *end <= data.len())?;
+
+        if current_type == extension_type {
+            return Some(&data[start..end]);
+        }
+
+        index = end;
+    }
+
+    None
+}
+
+/// Whether the mint is a Token-2022 mint with the group extension
+pub fn is_token_group(mint: &AccountInfo) -> Result<bool> {
+    if mint.owner != &token_2022::ID {
+        return Ok(false);
+    }
+
+    let data = mint.try_borrow_data()?;
+
+    Ok(get_mint_extension(&data, TOKEN_GROUP_EXTENSION).is_some())
+}
+
+/// Returns the group of a Token-2022 mint that stores its member data on itself
+pub fn get_token_group(mint: &AccountInfo) -> Result<Pubkey> {
+    require_keys_eq!(*mint.owner, token_2022::ID, StakeError::InvalidGroupMember);
+
+    let data = mint.try_borrow_data()?;
+
+    // Group member pointer: authority (32) + member address (32)
+    let pointer = get_mint_extension(&data, GROUP_MEMBER_POINTER_EXTENSION)
+        .filter(|pointer| pointer.len() >= 64)
+        .ok_or(StakeError::InvalidGroupMember)?;
+
+    let member_address = Pubkey::try_from(&pointer[32..64]).unwrap();
+    require_keys_eq!(member_address, mint.key(), StakeError::InvalidGroupMember);
+
+    // Group member: mint (32) + group (32) + member number (4)
+    let member = get_mint_extension(&data, TOKEN_GROUP_MEMBER_EXTENSION)
+        .filter(|member| member.len() >= 64)
+        .ok_or(StakeError::InvalidGroupMember)?;
+
+    let member_mint = Pubkey::try_from(&member[0..32]).unwrap();
+    require_keys_eq!(member_mint, mint.key(), StakeError::InvalidGroupMember);
+
+    Ok(Pubkey::try_from(&member[32..64]).unwrap())
+}
@@ -0,0 +1,94 @@
+use anchor_lang::{prelude::*, solana_program::program::invoke_signed};
+use anchor_spl::metadata::MetadataAccount;
+use mpl_token_metadata::{
+    instruction::{builders::TransferBuilder, InstructionBuilder, TransferArgs}, 
+    state::TokenStandard
+};
+
+use crate::StakeError;
+
+pub struct TransferPnft<'info> {
+    pub token: AccountInfo<'info>,
+    pub token_owner: AccountInfo<'info>,
+    pub destination: AccountInfo<'info>,
+    pub destination_owner: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub metadata: AccountInfo<'info>,
+    pub edition: AccountInfo<'info>,
+    pub owner_token_record: AccountInfo<'info>,
+    pub destination_token_record: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    pub payer: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub sysvar_instructions: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub associated_token_program: AccountInfo<'info>,
+    pub metadata_program: AccountInfo<'info>,
+    pub auth_rules_program: Option<AccountInfo<'info>>,
+    pub auth_rules: Option<AccountInfo<'info>>
+}
+
+pub fn is_programmable(metadata: &MetadataAccount) -> bool {
+    metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible)
+}
+
+/// Transfers a programmable NFT through the Token Metadata program,
+/// since its token account is frozen and can't be moved by a plain spl transfer
+pub fn transfer_pnft(accounts: TransferPnft, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let mut builder = TransferBuilder::new();
+
+    builder
+        .token(accounts.token.key())
+        .token_owner(accounts.token_owner.key())
+        .destination(accounts.destination.key())
+        .destination_owner(accounts.destination_owner.key())
+        .mint(accounts.mint.key())
+        .metadata(accounts.metadata.key())
+        .edition(accounts.edition.key())
+        .owner_token_record(accounts.owner_token_record.key())
+        .destination_token_record(accounts.destination_token_record.key())
+        .authority(accounts.authority.key())
+        .payer(accounts.payer.key())
+        .system_program(accounts.system_program.key())
+        .sysvar_instructions(accounts.sysvar_instructions.key())
+        .spl_token_program(accounts.token_program.key())
+        .spl_ata_program(accounts.associated_token_program.key());
+
+    let mut account_infos = vec![
+        accounts.token,
+        accounts.token_owner,
+        accounts.destination,
+        accounts.destination_owner,
+        accounts.mint,
+        accounts.metadata,
+        accounts.edition,
+        accounts.owner_token_record,
+        accounts.destination_token_record,
+        accounts.authority,
+        accounts.payer,
+        accounts.system_program,
+        accounts.sysvar_instructions,
+        accounts.token_program,
+        accounts.associated_token_program,
+        accounts.metadata_program
+    ];
+
+    // The rule set is only passed when the pNFT has one
+    if let (Some(auth_rules_program), Some(auth_rules)) = (accounts.auth_rules_program, accounts.auth_rules) {
+        builder
+            .authorization_rules_program(auth_rules_program.key())
+            .authorization_rules(auth_rules.key());
+
+        account_infos.push(auth_rules_program);
+        account_infos.push(auth_rules);
+    }
+
+    let transfer_ix = builder
+        .build(TransferArgs::V1 { amount: 1, authorization_data: None })
+        .map_err(|_| StakeError::PnftInstructionError)?
+        .instruction();
+
+    invoke_signed(&transfer_ix, &account_infos, signer_seeds)?;
+
+    Ok(())
+}
@@ -0,0 +1,318 @@
+import * as anchor from "@project-serum/anchor";
+import { utils, BN } from "@project-serum/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { Program } from "@project-serum/anchor";
+import * as token from "@solana/spl-token"
+import { createUmi } from "@metaplex-foundation/umi-bundle-defaults";
+import { generateSigner, keypairIdentity, none, publicKey, PublicKey as UmiPublicKey } from "@metaplex-foundation/umi";
+import {
+  mplBubblegum,
+  createTree,
+  mintV1,
+  findLeafAssetIdPda,
+  findTreeConfigPda,
+  getMetadataArgsSerializer,
+  getMerkleProof,
+  getMerkleRoot,
+  hashLeaf,
+  hashMetadataCreators,
+  hashMetadataData,
+  MetadataArgsArgs,
+  MPL_BUBBLEGUM_PROGRAM_ID
+} from "@metaplex-foundation/mpl-bubblegum";
+import { fromWeb3JsKeypair, fromWeb3JsPublicKey, toWeb3JsPublicKey } from "@metaplex-foundation/umi-web3js-adapters";
+import { NftStakeAuth } from "../target/types/nft_stake_auth";
+import { expect } from "chai";
+
+// Bubblegum, account compression and noop are cloned into the local validator, see Anchor.toml
+const compressionProgram = new PublicKey("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+const logWrapper = new PublicKey("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+const bubblegumProgram = toWeb3JsPublicKey(MPL_BUBBLEGUM_PROGRAM_ID);
+
+// A small tree keeps the proof within the transaction size
+const maxDepth = 5;
+const maxBufferSize = 8;
+
+// Configure the client to use the local cluster.
+anchor.setProvider(anchor.AnchorProvider.env());
+
+const program = anchor.workspace.NftStakeAuth as Program<NftStakeAuth>;
+const programId = program.idl.metadata.address;
+const connection = program.provider.connection;
+const payer = (program.provider as anchor.AnchorProvider).wallet as anchor.Wallet;
+
+const umi = createUmi(connection.rpcEndpoint)
+  .use(mplBubblegum())
+  .use(keypairIdentity(fromWeb3JsKeypair(payer.payer)));
+
+// The wallet is the verified creator of the compressed NFTs, and the creator address of the staking
+const collectionAddress = payer.publicKey;
+
+const [stakeDetails] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("stake"),
+    collectionAddress.toBytes(),
+    payer.publicKey.toBytes()
+], programId);
+
+const [tokenAuthority] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("token-authority"),
+    stakeDetails.toBytes()
+], programId);
+
+const [nftAuthority] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("nft-authority"),
+    stakeDetails.toBytes()
+], programId);
+
+const [stakerAccount] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("staker"),
+    stakeDetails.toBytes(),
+    payer.publicKey.toBytes()
+], programId);
+
+const [vestingEscrow] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("vesting"),
+    stakeDetails.toBytes(),
+    payer.publicKey.toBytes()
+], programId);
+
+function findNftRecord(assetId: PublicKey) {
+  return PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("nft-record"),
+    stakeDetails.toBytes(),
+    assetId.toBytes()
+  ], programId)[0];
+}
+
+function findRewardOverride(assetId: PublicKey) {
+  return PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("reward-override"),
+    stakeDetails.toBytes(),
+    assetId.toBytes()
+  ], programId)[0];
+}
+
+function sleep(ms: number) {
+  return new Promise(resolve => setTimeout(resolve, ms));
+}
+
+// The leaves of the tree, kept in step with the transfers so the proofs can be built locally
+const merkleTree = generateSigner(umi);
+const treeKey = toWeb3JsPublicKey(merkleTree.publicKey);
+const treeConfig = toWeb3JsPublicKey(findTreeConfigPda(umi, { merkleTree: merkleTree.publicKey })[0]);
+const leaves: UmiPublicKey[] = [];
+
+function metadataOf(name: string): MetadataArgsArgs {
+  return {
+    name,
+    uri: "https://example.com/nft.json",
+    sellerFeeBasisPoints: 500,
+    collection: none(),
+    creators: [{ address: fromWeb3JsPublicKey(collectionAddress), verified: true, share: 100 }]
+  };
+}
+
+function setLeaf(index: number, owner: PublicKey, metadata: MetadataArgsArgs) {
+  const leaf = hashLeaf(umi, {
+    merkleTree: merkleTree.publicKey,
+    owner: fromWeb3JsPublicKey(owner),
+    leafIndex: index,
+    metadata
+  });
+
+  leaves[index] = publicKey(leaf);
+}
+
+function rootOf() {
+  return Array.from(toWeb3JsPublicKey(getMerkleRoot(leaves, maxDepth)).toBytes());
+}
+
+function proofOf(index: number) {
+  return getMerkleProof(leaves, maxDepth, leaves[index])
+    .map(node => ({ pubkey: toWeb3JsPublicKey(node), isSigner: false, isWritable: false }));
+}
+
+async function mintCompressed(name: string) {
+  const metadata = metadataOf(name);
+  const index = leaves.length;
+
+  await mintV1(umi, {
+    leafOwner: fromWeb3JsPublicKey(payer.publicKey),
+    merkleTree: merkleTree.publicKey,
+    metadata
+  }).sendAndConfirm(umi);
+
+  setLeaf(index, payer.publicKey, metadata);
+
+  const assetId = toWeb3JsPublicKey(findLeafAssetIdPda(umi, { merkleTree: merkleTree.publicKey, leafIndex: index })[0]);
+
+  return { index, metadata, assetId };
+}
+
+describe("nft-stake-auth compressed NFTs", () => {
+  let tokenMint: PublicKey;
+  let rewardReceiveAccount: PublicKey;
+  let staked: { index: number, metadata: MetadataArgsArgs, assetId: PublicKey };
+
+  before(async() => {
+    await (await createTree(umi, { merkleTree, maxDepth, maxBufferSize })).sendAndConfirm(umi);
+
+    // The rewards are minted by the token authority
+    tokenMint = await token.createMint(connection, payer.payer, payer.publicKey, null, 0);
+    rewardReceiveAccount = await token.createAssociatedTokenAccount(connection, payer.payer, tokenMint, payer.publicKey);
+
+    await program.methods.initStaking(
+      new BN(1),
+      new BN(0),
+      false
+    )
+    .accounts({
+      stakeDetails,
+      tokenMint,
+      tokenAuthority,
+      collectionAddress,
+      nftAuthority
+    })
+    .rpc();
+  });
+
+  it("stakes a compressed NFT", async() => {
+    staked = await mintCompressed("Compressed #1");
+    const nftRecord = findNftRecord(staked.assetId);
+
+    await program.methods.stakeCompressed({
+      root: rootOf(),
+      nonce: new BN(staked.index),
+      index: staked.index,
+      metadata: Buffer.from(getMetadataArgsSerializer().serialize(staked.metadata)),
+      allowlistProof: [],
+      rarity: null,
+      lockTier: null
+    })
+    .accounts({
+      stakeDetails,
+      nftRecord,
+      stakerAccount,
+      treeConfig,
+      merkleTree: treeKey,
+      nftAuthority,
+      bubblegumProgram,
+      compressionProgram,
+      logWrapper
+    })
+    .remainingAccounts(proofOf(staked.index))
+    .rpc();
+
+    // The leaf now belongs to the nft authority
+    setLeaf(staked.index, nftAuthority, staked.metadata);
+
+    const nftRecordAccount = await program.account.nftRecord.fetch(nftRecord);
+    expect(nftRecordAccount.nftMint.toBase58()).to.equal(staked.assetId.toBase58());
+    expect(nftRecordAccount.staker.toBase58()).to.equal(payer.publicKey.toBase58());
+
+    const stakeAccount = await program.account.details.fetch(stakeDetails);
+    expect(stakeAccount.currentStakersCount.toNumber()).to.equal(1);
+  });
+
+  it("claims rewards of the compressed NFT", async() => {
+    await sleep(2000);
+
+    const balanceBefore = await token.getAccount(connection, rewardReceiveAccount);
+
+    await program.methods.withdrawReward()
+    .accounts({
+      stakeDetails,
+      nftRecord: findNftRecord(staked.assetId),
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride: findRewardOverride(staked.assetId),
+      rewardMint: tokenMint,
+      rewardReceiveAccount,
+      tokenAuthority
+    })
+    .rpc();
+
+    const balanceAfter = await token.getAccount(connection, rewardReceiveAccount);
+    expect(balanceAfter.amount > balanceBefore.amount).to.be.true;
+  });
+
+  it("unstakes the compressed NFT", async() => {
+    const nftRecord = findNftRecord(staked.assetId);
+
+    await program.methods.unstakeCompressed(
+      rootOf(),
+      Array.from(hashMetadataData(staked.metadata)),
+      Array.from(hashMetadataCreators(staked.metadata.creators)),
+      new BN(staked.index),
+      staked.index
+    )
+    .accounts({
+      stakeDetails,
+      nftRecord,
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride: findRewardOverride(staked.assetId),
+      rewardMint: tokenMint,
+      rewardReceiveAccount,
+      treeConfig,
+      merkleTree: treeKey,
+      tokenAuthority,
+      nftAuthority,
+      bubblegumProgram,
+      compressionProgram,
+      logWrapper
+    })
+    .remainingAccounts(proofOf(staked.index))
+    .rpc();
+
+    // The leaf is back with the staker
+    setLeaf(staked.index, payer.publicKey, staked.metadata);
+
+    expect(await connection.getAccountInfo(nftRecord)).to.be.null;
+
+    // The staking doesn't vest its rewards, so no vesting escrow is created
+    expect(await connection.getAccountInfo(vestingEscrow)).to.be.null;
+
+    const stakeAccount = await program.account.details.fetch(stakeDetails);
+    expect(stakeAccount.currentStakersCount.toNumber()).to.equal(0);
+  });
+
+  it("stakes a compressed NFT with forged metadata and fails", async() => {
+    const minted = await mintCompressed("Compressed #2");
+    const nftRecord = findNftRecord(minted.assetId);
+
+    // The leaf hashed from the forged metadata isn't in the tree
+    const forgedMetadata = { ...minted.metadata, name: "Compressed #2 Rare" };
+    let error;
+
+    try {
+      await program.methods.stakeCompressed({
+        root: rootOf(),
+        nonce: new BN(minted.index),
+        index: minted.index,
+        metadata: Buffer.from(getMetadataArgsSerializer().serialize(forgedMetadata)),
+        allowlistProof: [],
+        rarity: null,
+        lockTier: null
+      })
+      .accounts({
+        stakeDetails,
+        nftRecord,
+        stakerAccount,
+        treeConfig,
+        merkleTree: treeKey,
+        nftAuthority,
+        bubblegumProgram,
+        compressionProgram,
+        logWrapper
+      })
+      .remainingAccounts(proofOf(minted.index))
+      .rpc();
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error).to.exist;
+    expect(await connection.getAccountInfo(nftRecord)).to.be.null;
+  });
+});
@@ -4,6 +4,7 @@ import {PublicKey} from "@solana/web3.js";
 import { Program } from "@project-serum/anchor";
 import * as token from "@solana/spl-token"
 import { NftStakeAuth } from "../target/types/nft_stake_auth";
+import { expect } from "chai";
 
 //constants
 const collectionAddress = new PublicKey("AyRhD1Yh8MAdZAhQL8eK1FZcogg1GW4Y87HqsWJytTzo"); // Mint Address of the Collection NFT for which the staking to be activated
@@ -57,16 +58,38 @@ const [nftRecord2] = PublicKey.findProgramAddressSync([
     nftMint2.toBytes()
 ], programId);
 
+const [rewardOverride] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("reward-override"),
+    stakeDetails.toBytes(),
+    nftMint.toBytes()
+], programId);
+
+const [stakerAccount] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("staker"),
+    stakeDetails.toBytes(),
+    program.provider.publicKey.toBytes()
+], programId);
+
+const [vestingEscrow] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("vesting"),
+    stakeDetails.toBytes(),
+    program.provider.publicKey.toBytes()
+], programId);
+
 const nftCustody = token.getAssociatedTokenAddressSync(nftMint, nftAuthority, true);
 
 describe("nft-stake-auth", () => {
+  // The first stake time of the wallet, kept by its staker account across unstakes
+  let firstStakedAt: number;
+
   it("initializes staking", async() => {
     const minimumPeriod = new BN(0);
     const reward = new BN(100);
 
     const tx = await program.methods.initStaking(
       reward,
-      minimumPeriod
+      minimumPeriod,
+      false
     )
     .accounts({
       stakeDetails,
@@ -84,10 +107,11 @@ describe("nft-stake-auth", () => {
   });
 
   it("stakes NFT", async() => {
-    const tx = await program.methods.stake()
+    const tx = await program.methods.stake([], null, null)
     .accounts({
       stakeDetails,
       nftRecord,
+      stakerAccount,
       nftMint,
       nftToken,
       nftMetadata,
@@ -104,14 +128,23 @@ describe("nft-stake-auth", () => {
 
     console.log("Stake Details: ", stakeAccount);
     console.log("NFT Record: ", nftRecordAccount);
+
+    let stakerAccountData = await program.account.stakerAccount.fetch(stakerAccount);
+    console.log("Staker Account: ", stakerAccountData);
+
+    expect(stakerAccountData.stakedCount.toNumber()).to.equal(1);
+    expect(stakerAccountData.totalClaimed.toNumber()).to.equal(0);
+    expect(stakerAccountData.firstStakedAt.toNumber()).to.equal(nftRecordAccount.stakedAt.toNumber());
+    firstStakedAt = stakerAccountData.firstStakedAt.toNumber();
   });
 
   it("stakes NFT from different collection and fails", async() => {
     try {
-      const tx = await program.methods.stake()
+      const tx = await program.methods.stake([], null, null)
       .accounts({
         stakeDetails,
         nftRecord: nftRecord2,
+        stakerAccount,
         nftMint: nftMint2,
         nftToken: nftToken2,
         nftMetadata: nftMetadata2,
@@ -133,6 +166,9 @@ describe("nft-stake-auth", () => {
     .accounts({
       stakeDetails,
       nftRecord,
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride,
       rewardMint: tokenMint,
       rewardReceiveAccount: tokenAccount,
       tokenAuthority            
@@ -154,17 +190,31 @@ describe("nft-stake-auth", () => {
     .accounts({
       stakeDetails,
       nftRecord,
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride,
       rewardMint: tokenMint,
       rewardReceiveAccount: tokenAccount,
       tokenAuthority,
       nftAuthority,
       nftCustody,
       nftMint,
+      nftMetadata,
+      nftEdition,
+      rewardTokenProgram: token.TOKEN_PROGRAM_ID,
       nftReceiveAccount: nftToken         
     })
     .rpc()
 
     console.log("TX: ", tx);
+
+    // The staking doesn't vest its rewards, so no vesting escrow is created
+    expect(await program.provider.connection.getAccountInfo(vestingEscrow)).to.be.null;
+
+    // The wallet keeps the time of its first stake with nothing staked
+    const stakerAccountData = await program.account.stakerAccount.fetch(stakerAccount);
+    expect(stakerAccountData.stakedCount.toNumber()).to.equal(0);
+    expect(stakerAccountData.firstStakedAt.toNumber()).to.equal(firstStakedAt);
   });
 
   it("closes staking", async() => {
@@ -0,0 +1,14 @@
+use anchor_lang::prelude::*;
+
+/// A reward transferred from the stake vault to a staker
+#[event]
+pub struct RewardPaid {
+    /// The staking that paid the reward
+    pub stake_details: Pubkey,
+    /// The wallet of the staker
+    pub staker: Pubkey,
+    /// The reward sent from the stake vault
+    pub reward: u64,
+    /// The part of the reward withheld by the transfer fee of the reward mint, the staker receives the rest
+    pub transfer_fee: u64
+}
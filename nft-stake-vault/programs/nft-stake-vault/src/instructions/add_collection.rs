@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::Details,
+    utils::{apply_multiplier, get_collection_source},
+    StakeError,
+    MAX_COLLECTIONS
+};
+
+#[derive(Accounts)]
+pub struct AddCollection<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    /// CHECK: The collection NFT mint, a Core collection or a creator address, validated in the handler
+    pub collection_address: UncheckedAccount<'info>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn add_collection_handler(ctx: Context<AddCollection>, multiplier: u64) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let Details {
+        max_multiplier,
+        max_rarity_multiplier,
+        max_lock_multiplier,
+        staking_ends_at,
+        is_active: staking_status,
+        collection_source,
+        ..
+    } = **stake_details;
+
+    let collection = ctx.accounts.collection_address.key();
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    require_gt!(multiplier, 0, StakeError::InvalidMultiplier);
+    require_gt!(MAX_COLLECTIONS, stake_details.collections.len(), StakeError::MaxCollectionsReached);
+    require!(stake_details.collection_multiplier(&collection).is_none(), StakeError::CollectionAlreadyAdded);
+
+    // All the collections of the staking are verified the same way, a creator only matches
+    // the NFTs of a staking verified by creators and a collection the ones verified by collection
+    let source = get_collection_source(&ctx.accounts.collection_address.to_account_info())?;
+    require!(source == collection_source, StakeError::InvalidCollection);
+
+    // A higher multiplier raises the emission the vault must cover until the staking ends
+    if multiplier > max_multiplier {
+        let new_max_nft_multiplier = apply_multiplier(multiplier, max_rarity_multiplier)?;
+        let new_max_nft_multiplier = apply_multiplier(new_max_nft_multiplier, max_lock_multiplier)?;
+        stake_details.require_emission_covered(new_max_nft_multiplier, current_time)?;
+    }
+
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.add_collection(collection, multiplier);
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{transfer, Transfer, Token, TokenAccount, Mint};
+use anchor_spl::token_interface::{transfer_checked, TransferChecked, TokenInterface, TokenAccount, Mint};
 
 use crate::{state::Details, StakeError};
 
@@ -18,21 +18,21 @@ pub struct AddFunds<'info> {
     )]
     pub stake_details: Account<'info, Details>,
 
-    pub reward_mint: Account<'info, Mint>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = reward_mint,
         associated_token::authority = creator
     )]
-    pub token_account: Account<'info, TokenAccount>,
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = reward_mint,
         associated_token::authority = token_authority,
     )]
-    pub stake_token_vault: Account<'info, TokenAccount>,
+    pub stake_token_vault: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -45,13 +45,14 @@ pub struct AddFunds<'info> {
     pub token_authority: UncheckedAccount<'info>,
 
     pub creator: Signer<'info>,
-    pub token_program: Program<'info, Token>
+    pub token_program: Interface<'info, TokenInterface>
 }
 
 impl<'info> AddFunds<'info> {
-    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
             from: self.token_account.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
             to: self.stake_token_vault.to_account_info(),
             authority: self.creator.to_account_info()
         };
@@ -67,6 +68,17 @@ pub fn add_funds_handler(ctx: Context<AddFunds>, amount: u64) -> Result<()> {
 
     require_eq!(stake_status, true, StakeError::StakingInactive);
 
-    transfer(ctx.accounts.transfer_token_ctx(), amount)?;
-    ctx.accounts.stake_details.increase_current_balance(amount)
+    let decimals = ctx.accounts.reward_mint.decimals;
+    let balance_before = ctx.accounts.stake_token_vault.amount;
+
+    transfer_checked(ctx.accounts.transfer_token_ctx(), amount, decimals)?;
+
+    // Credit only what arrived in the vault, after any transfer fee
+    ctx.accounts.stake_token_vault.reload()?;
+
+    let received = ctx.accounts.stake_token_vault.amount
+        .checked_sub(balance_before)
+        .ok_or(StakeError::ProgramSubError)?;
+
+    ctx.accounts.stake_details.increase_current_balance(received)
 }
\ No newline at end of file
@@ -0,0 +1,153 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{transfer_checked, TransferChecked, TokenInterface, TokenAccount, Mint},
+    associated_token::AssociatedToken
+};
+
+use crate::{state::{Details, EmissionCurve, RewardStream}, utils::calc_total_emission, StakeError, MAX_REWARD_STREAMS};
+
+#[derive(Accounts)]
+pub struct AddRewardStream<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = RewardStream::LEN,
+        seeds = [
+            b"reward-stream",
+            stake_details.key().as_ref(),
+            stream_mint.key().as_ref()
+        ],
+        bump
+    )]
+    pub reward_stream: Account<'info, RewardStream>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    /// The token program is the one of the stream mint, which may differ from the one of the reward
+    #[account(
+        constraint = stream_mint.key() != reward_mint.key() @ StakeError::InvalidStreamMint
+    )]
+    pub stream_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = stream_mint,
+        associated_token::authority = creator
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        associated_token::mint = stream_mint,
+        associated_token::authority = token_authority
+    )]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.token_auth_bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> AddRewardStream<'info> {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.token_account.to_account_info(),
+            mint: self.stream_mint.to_account_info(),
+            to: self.stream_vault.to_account_info(),
+            authority: self.creator.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn add_reward_stream_handler(ctx: Context<AddRewardStream>, reward: u64, funds: u64) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let Details {
+        max_stakers_count,
+        staking_ends_at,
+        is_active: staking_status,
+        ..
+    } = **stake_details;
+
+    let max_nft_multiplier = stake_details.max_nft_multiplier()?;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gt!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    require_gt!(MAX_REWARD_STREAMS, stake_details.reward_streams.len(), StakeError::MaxRewardStreamsReached);
+
+    let decimals = ctx.accounts.stream_mint.decimals;
+    let balance_before = ctx.accounts.stream_vault.amount;
+
+    transfer_checked(ctx.accounts.transfer_token_ctx(), funds, decimals)?;
+
+    // Credit only what arrived in the vault, after any transfer fee
+    ctx.accounts.stream_vault.reload()?;
+
+    let received = ctx.accounts.stream_vault.amount
+        .checked_sub(balance_before)
+        .ok_or(StakeError::ProgramSubError)?;
+
+    // The stream must cover its emission until the staking ends, the same way the reward does
+    let total_emission = calc_total_emission(
+        reward,
+        &EmissionCurve::Constant,
+        max_stakers_count,
+        current_time,
+        staking_ends_at,
+        max_nft_multiplier
+    )?;
+
+    require_gte!(received, total_emission, StakeError::InsufficientBalInVault);
+
+    let stake_details_key = ctx.accounts.stake_details.key();
+    let stream_mint_key = ctx.accounts.stream_mint.key();
+    let bump = *ctx.bumps.get("reward_stream").ok_or(StakeError::StreamBumpError)?;
+
+    let reward_stream = &mut ctx.accounts.reward_stream;
+
+    **reward_stream = RewardStream::init(
+        stake_details_key,
+        stream_mint_key,
+        reward,
+        current_time,
+        staking_ends_at,
+        received - total_emission,
+        bump
+    );
+
+    ctx.accounts.stake_details.add_reward_stream(stream_mint_key);
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 
-use crate::{state::Details, utils::{calc_total_emission, calc_actual_balance}, StakeError};
+use crate::{state::Details, StakeError};
 
 #[derive(Accounts)]
 pub struct ChangeReward<'info> {
@@ -12,10 +12,7 @@ pub struct ChangeReward<'info> {
             stake_details.creator.as_ref()
         ],
         bump = stake_details.stake_bump,
-        has_one = creator,
-        realloc = stake_details.current_len() + 16,
-        realloc::payer = creator,
-        realloc::zero = false
+        has_one = creator
     )]
     pub stake_details: Account<'info, Details>,
 
@@ -30,46 +27,22 @@ pub fn change_reward_handler(ctx: Context<ChangeReward>, new_reward: u64) -> Res
     let current_time = Clock::get().unwrap().unix_timestamp;
 
     let Details {
-        max_stakers_count,
-        current_stakers_count,
         staking_ends_at,
-        current_balance,
-        staked_weight,
         is_active: staking_status,
         ..
     } = **stake_details;
 
-    let current_reward = *stake_details.reward.last().unwrap();
-    let last_reward_change_time = *stake_details.reward_change_time.last().unwrap();
+    let max_nft_multiplier = stake_details.max_nft_multiplier()?;
 
     require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
     require_eq!(staking_status, true, StakeError::StakingInactive);
-
-    let (current_actual_balance, new_staked_weight) = calc_actual_balance(
-        current_stakers_count,
-        staked_weight,
-        current_reward,
-        last_reward_change_time,
-        staking_ends_at,
-        current_time,
-        current_balance,
-        None
-    )?;
-
-    let new_emission = calc_total_emission(
-        new_reward, 
-        max_stakers_count, 
-        current_time, 
-        staking_ends_at
-    )?;
-
-    require_gte!(current_actual_balance, new_emission, StakeError::InsufficientBalInVault);
+    stake_details.require_constant_curve()?;
 
     let stake_details = &mut ctx.accounts.stake_details;
 
-    stake_details.change_reward(new_reward, current_time);
-    stake_details.current_balance = current_actual_balance;
-    stake_details.staked_weight = new_staked_weight;
+    stake_details.change_reward(new_reward, current_time)?;
 
-    Ok(())
+    // The reward accrued until now stays owed to the stakers at the old rate,
+    // the new one applies until the next scheduled change
+    stake_details.require_emission_covered(max_nft_multiplier, current_time)
 }
\ No newline at end of file
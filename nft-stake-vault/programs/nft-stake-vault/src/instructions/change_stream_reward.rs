@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{Details, RewardStream}, StakeError};
+
+#[derive(Accounts)]
+pub struct ChangeStreamReward<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"reward-stream",
+            stake_details.key().as_ref(),
+            reward_stream.reward_mint.as_ref()
+        ],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Account<'info, RewardStream>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn change_stream_reward_handler(ctx: Context<ChangeStreamReward>, new_reward: u64) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let Details {
+        max_stakers_count,
+        staking_ends_at,
+        is_active: staking_status,
+        ..
+    } = **stake_details;
+
+    let max_nft_multiplier = stake_details.max_nft_multiplier()?;
+
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+
+    ctx.accounts.reward_stream.change_reward(
+        new_reward,
+        current_time,
+        staking_ends_at,
+        max_stakers_count,
+        max_nft_multiplier
+    )
+}
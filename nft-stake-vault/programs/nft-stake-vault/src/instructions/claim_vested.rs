@@ -0,0 +1,107 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, TokenAccount, TransferChecked, transfer_checked},
+    associated_token::AssociatedToken
+};
+
+use crate::{events::RewardPaid, state::{Details, VestingEscrow}, utils::calc_transfer_fee};
+
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump = vesting_escrow.bump,
+        has_one = staker
+    )]
+    pub vesting_escrow: Account<'info, VestingEscrow>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = token_authority
+    )]
+    pub stake_token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker
+    )]
+    pub reward_receive_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref(),
+        ],
+        bump = stake_details.token_auth_bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> ClaimVested<'info> {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.stake_token_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_receive_account.to_account_info(),
+            authority: self.token_authority.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn claim_vested_handler(ctx: Context<ClaimVested>) -> Result<()> {
+    let token_auth_bump = ctx.accounts.stake_details.token_auth_bump;
+    let stake_details_key = ctx.accounts.stake_details.key();
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    // The vested reward can be claimed even after the staking is closed
+    let vested_reward = ctx.accounts.vesting_escrow.claim(current_time)?;
+
+    let authority_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+
+    // The staker bears the transfer fee of the reward mint, if it has one
+    let transfer_fee = calc_transfer_fee(&ctx.accounts.reward_mint.to_account_info(), vested_reward)?;
+    emit!(RewardPaid { stake_details: stake_details_key, staker: ctx.accounts.staker.key(), reward: vested_reward, transfer_fee });
+
+    transfer_checked(
+        ctx.accounts.transfer_token_ctx().with_signer(&[&authority_seed[..]]),
+        vested_reward,
+        ctx.accounts.reward_mint.decimals
+    )?;
+
+    // The reward no longer needs to be kept in the stake vault
+    ctx.accounts.stake_details.change_vesting_balance(vested_reward, false)
+}
@@ -1,7 +1,7 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{transfer, Transfer, Token, TokenAccount, Mint};
+use anchor_spl::token_interface::{transfer_checked, TransferChecked, TokenInterface, TokenAccount, Mint};
 
-use crate::{state::Details, StakeError, utils::calc_actual_balance};
+use crate::{state::Details, StakeError};
 
 #[derive(Accounts)]
 pub struct CloseStaking<'info> {
@@ -17,21 +17,21 @@ pub struct CloseStaking<'info> {
     )]
     pub stake_details: Account<'info, Details>,
 
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = token_mint,
         associated_token::authority = creator
     )]
-    pub token_account: Account<'info, TokenAccount>,
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = token_mint,
         associated_token::authority = token_authority,
     )]
-    pub stake_token_vault: Account<'info, TokenAccount>,
+    pub stake_token_vault: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -44,13 +44,14 @@ pub struct CloseStaking<'info> {
     pub token_authority: UncheckedAccount<'info>,
 
     pub creator: Signer<'info>,
-    pub token_program: Program<'info, Token>
+    pub token_program: Interface<'info, TokenInterface>
 }
 
 impl<'info> CloseStaking<'info> {
-    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
             from: self.stake_token_vault.to_account_info(),
+            mint: self.token_mint.to_account_info(),
             to: self.token_account.to_account_info(),
             authority: self.token_authority.to_account_info()
         };
@@ -66,44 +67,40 @@ pub fn close_staking_handler(ctx: Context<CloseStaking>) -> Result<()> {
     let current_time = Clock::get().unwrap().unix_timestamp;
 
     let Details {
-        current_stakers_count,
         staking_ends_at,
-        staked_weight,
         is_active: staking_status,
         token_auth_bump,
         ..
     } = **stake_details;
 
-    let current_reward = *stake_details.reward.last().unwrap();
-    let last_reward_change_time = *stake_details.reward_change_time.last().unwrap();
     let stake_details_key = stake_details.key();
 
-    let current_balance = ctx.accounts.stake_token_vault.amount;
+    // The rewards vesting in the escrows stay in the stake vault until claimed
+    let current_balance = ctx.accounts.stake_token_vault.amount
+        .checked_sub(stake_details.vesting_balance)
+        .ok_or(StakeError::ProgramSubError)?;
     
     require_eq!(staking_status, true, StakeError::StakingInactive);
 
-    let (current_actual_balance, _new_staked_weight) = calc_actual_balance(
-        current_stakers_count,
-        staked_weight,
-        current_reward,
-        last_reward_change_time,
-        staking_ends_at,
-        current_time,
-        current_balance,
-        None
-    )?;
+    // The reward owed to the stakers stays in the vault
+    let liability = stake_details.liability(current_time)?;
+    let refund = current_balance.saturating_sub(liability);
 
     // Transfer remaining balance back to the creator
     let token_auth_seed = &[&b"token-authority"[..], &stake_details_key.as_ref(), &[token_auth_bump]];
-    transfer(
+    transfer_checked(
         ctx.accounts.transfer_token_ctx().with_signer(&[&token_auth_seed[..]]), 
-        current_actual_balance
+        refund,
+        ctx.accounts.token_mint.decimals
     )?;
 
     let stake_details = &mut ctx.accounts.stake_details;
 
     stake_details.close_staking();
 
+    // The vault is left with the reward owed to the stakers, the refund can't exceed the balance
+    stake_details.current_balance = current_balance - refund;
+
     // Allow stakers to instantly withdraw their NFTs
     stake_details.minimum_period = 0;
 
@@ -0,0 +1,289 @@
+use anchor_lang::{prelude::*, solana_program::sysvar};
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, TokenAccount, TransferChecked, CloseAccount, Revoke, transfer_checked, close_account, revoke}, 
+    metadata::{MasterEditionAccount, MetadataAccount, Metadata, ThawDelegatedAccount, thaw_delegated_account}, 
+    associated_token::AssociatedToken
+};
+
+use crate::{
+    state::{Details, NftRecord, StakerAccount}, 
+    utils::{is_programmable, transfer_pnft, unlock_and_revoke_pnft, TransferPnft, LockPnft}, 
+    StakeError
+};
+
+#[derive(Accounts)]
+pub struct CompleteUnstake<'info> {
+    #[account(
+        seeds = [
+            b"stake", 
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"nft-record", 
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref(),
+        ],
+        bump = nft_record.bump,
+        has_one = nft_mint,
+        has_one = staker,
+        constraint = nft_record.is_unbonding() @ StakeError::NftNotUnbonding,
+        close = staker
+    )]
+    pub nft_record: Account<'info, NftRecord>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump = staker_account.bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    #[account(
+        mint::decimals = 0,
+        constraint = nft_mint.supply == 1 @ StakeError::TokenNotNFT,
+    )]
+    nft_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = nft_mint,
+        associated_token::authority = staker,
+    )]
+    nft_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = nft_authority,
+        constraint = nft_custody.amount == 1 @ StakeError::TokenAccountEmpty
+    )]
+    pub nft_custody: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            Metadata::id().as_ref(),
+            nft_mint.key().as_ref()
+        ],
+        seeds::program = Metadata::id(),
+        bump
+    )]
+    nft_metadata: Option<Box<Account<'info, MetadataAccount>>>,
+
+    #[account(
+        seeds = [
+            b"metadata",
+            Metadata::id().as_ref(),
+            nft_mint.key().as_ref(),
+            b"edition"
+        ],
+        seeds::program = Metadata::id(),
+        bump
+    )]
+    nft_edition: Option<Box<Account<'info, MasterEditionAccount>>>,
+
+    /// CHECK: Token record of the custody token account, only required for custodial pNFTs
+    #[account(mut)]
+    pub custody_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Token record of the staker's token account, only required for pNFTs
+    #[account(mut)]
+    pub receive_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The rule set of the pNFT, validated by the Token Metadata program
+    pub auth_rules: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Validated by the Token Metadata program
+    pub auth_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: Option<UncheckedAccount<'info>>,
+
+     /// CHECK: This account is not read or written
+     #[account(
+        seeds = [
+            b"nft-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.nft_auth_bump
+    )]
+    pub nft_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub metadata_program: Option<Program<'info, Metadata>>
+}
+
+impl<'info> CompleteUnstake<'info> {
+    pub fn nft_custody_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_custody.as_ref().ok_or(StakeError::CustodyAccountMissing)?.to_account_info())
+    }
+
+    pub fn metadata_program_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.metadata_program.as_ref().ok_or(StakeError::MetadataProgramMissing)?.to_account_info())
+    }
+
+    pub fn nft_metadata_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_metadata.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn nft_edition_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_edition.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn transfer_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>> {
+        let cpi_accounts = TransferChecked {
+            from: self.nft_custody_info()?,
+            mint: self.nft_mint.to_account_info(),
+            to: self.nft_receive_account.to_account_info(),
+            authority: self.nft_authority.to_account_info()
+        };
+    
+        let cpi_program = self.token_program.clone().to_account_info();
+
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn close_account_ctx(&self)-> Result<CpiContext<'_, '_, '_, 'info, CloseAccount<'info>>> {
+        let cpi_accounts = CloseAccount {
+            account: self.nft_custody_info()?,
+            destination: self.staker.to_account_info(),
+            authority: self.nft_authority.to_account_info()
+        };
+        let cpi_program = self.token_program.to_account_info();
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn thaw_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, ThawDelegatedAccount<'info>>> {
+        let cpi_accounts = ThawDelegatedAccount {
+            metadata: self.nft_metadata_info()?,
+            delegate: self.nft_authority.to_account_info(),
+            token_account: self.nft_receive_account.to_account_info(),
+            edition: self.nft_edition_info()?,
+            mint: self.nft_mint.to_account_info(),
+            token_program: self.token_program.to_account_info()
+        };
+        let cpi_program = self.metadata_program_info()?;
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn revoke_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Revoke<'info>> {
+        let cpi_accounts = Revoke {
+            source: self.nft_receive_account.to_account_info(),
+            authority: self.staker.to_account_info()
+        };
+        let cpi_program = self.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+
+    pub fn transfer_pnft_accounts(&self) -> Result<TransferPnft<'info>> {
+        Ok(TransferPnft {
+            token: self.nft_custody_info()?,
+            token_owner: self.nft_authority.to_account_info(),
+            destination: self.nft_receive_account.to_account_info(),
+            destination_owner: self.staker.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            owner_token_record: self.custody_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            destination_token_record: self.receive_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            authority: self.nft_authority.to_account_info(),
+            payer: self.staker.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
+
+    pub fn lock_pnft_accounts(&self) -> Result<LockPnft<'info>> {
+        Ok(LockPnft {
+            delegate: self.nft_authority.to_account_info(),
+            token_owner: self.staker.to_account_info(),
+            token: self.nft_receive_account.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            token_record: self.receive_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            payer: self.staker.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
+}
+
+pub fn complete_unstake_handler(ctx: Context<CompleteUnstake>) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let Details {
+        is_active: staking_status,
+        nft_auth_bump,
+        non_custodial,
+        ..
+    } = **stake_details;
+
+    let stake_details_key = stake_details.key();
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    // Closing the staking releases the unbonding NFTs
+    require!(!staking_status || ctx.accounts.nft_record.is_unbonded(current_time), StakeError::CooldownNotOver);
+
+    // Release NFT
+    let is_pnft = ctx.accounts.nft_metadata
+        .as_ref()
+        .is_some_and(|metadata| is_programmable(metadata));
+    let nft_auth_seed = &[&b"nft-authority"[..], stake_details_key.as_ref(), &[nft_auth_bump]];
+
+    if non_custodial {
+        // Thaw the NFT in the staker's wallet and remove the nft authority as delegate
+        if is_pnft {
+            unlock_and_revoke_pnft(ctx.accounts.lock_pnft_accounts()?, &[&nft_auth_seed[..]])?;
+        } else {
+            thaw_delegated_account(ctx.accounts.thaw_nft_ctx()?.with_signer(&[&nft_auth_seed[..]]))?;
+            revoke(ctx.accounts.revoke_nft_ctx())?;
+        }
+    } else {
+        if is_pnft {
+            // Token Metadata thaws the custody account to move the pNFT, and refunds its token record to the staker
+            transfer_pnft(ctx.accounts.transfer_pnft_accounts()?, &[&nft_auth_seed[..]])?;
+        } else {
+            transfer_checked(
+                ctx.accounts.transfer_nft_ctx()?.with_signer(&[&nft_auth_seed[..]]), 
+                1,
+                0
+            )?;
+        }
+
+        // Close NFT Custody Account
+        close_account(ctx.accounts.close_account_ctx()?.with_signer(&[&nft_auth_seed[..]]))?;
+    }
+    
+    ctx.accounts.staker_account.remove_staked();
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, TransferChecked, TokenInterface, TokenAccount, Mint};
+
+use crate::{state::{Details, RewardStream}, StakeError};
+
+#[derive(Accounts)]
+pub struct FundRewardStream<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"reward-stream",
+            stake_details.key().as_ref(),
+            stream_mint.key().as_ref()
+        ],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Account<'info, RewardStream>,
+
+    pub stream_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = stream_mint,
+        associated_token::authority = creator
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = stream_mint,
+        associated_token::authority = token_authority
+    )]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.token_auth_bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    pub creator: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+impl<'info> FundRewardStream<'info> {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.token_account.to_account_info(),
+            mint: self.stream_mint.to_account_info(),
+            to: self.stream_vault.to_account_info(),
+            authority: self.creator.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn fund_reward_stream_handler(ctx: Context<FundRewardStream>, amount: u64) -> Result<()> {
+    let stake_status = ctx.accounts.stake_details.is_active;
+
+    require_eq!(stake_status, true, StakeError::StakingInactive);
+
+    let decimals = ctx.accounts.stream_mint.decimals;
+    let balance_before = ctx.accounts.stream_vault.amount;
+
+    transfer_checked(ctx.accounts.transfer_token_ctx(), amount, decimals)?;
+
+    // Credit only what arrived in the vault, after any transfer fee
+    ctx.accounts.stream_vault.reload()?;
+
+    let received = ctx.accounts.stream_vault.amount
+        .checked_sub(balance_before)
+        .ok_or(StakeError::ProgramSubError)?;
+
+    ctx.accounts.reward_stream.increase_current_balance(received)
+}
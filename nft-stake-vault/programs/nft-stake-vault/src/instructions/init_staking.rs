@@ -1,10 +1,15 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token::{Mint, Token, TokenAccount, Transfer, transfer}, 
+    token_interface::{Mint, TokenInterface, TokenAccount, TransferChecked, transfer_checked}, 
     associated_token::AssociatedToken
 };
 
-use crate::{state::Details, StakeError, utils::calc_total_emission};
+use crate::{
+    state::{Details, CollectionSource, EmissionCurve, InitStakingParams}, 
+    StakeError, 
+    utils::{calc_total_emission, calc_pre_fee_amount, get_collection_source}, 
+    MULTIPLIER_BASE
+};
 
 #[derive(Accounts)]
 pub struct InitStaking<'info> {
@@ -21,14 +26,14 @@ pub struct InitStaking<'info> {
     )]
     pub stake_details: Account<'info, Details>,
 
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = token_mint,
         associated_token::authority = creator
     )]
-    pub token_account: Account<'info, TokenAccount>,
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init_if_needed,
@@ -36,12 +41,10 @@ pub struct InitStaking<'info> {
         associated_token::mint = token_mint,
         associated_token::authority = token_authority,
     )]
-    pub stake_token_vault: Account<'info, TokenAccount>,
+    pub stake_token_vault: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(
-        mint::decimals = 0,
-    )]
-    pub collection_address: Account<'info, Mint>,
+    /// CHECK: The collection NFT mint, a Core collection or a creator address, validated in the handler
+    pub collection_address: UncheckedAccount<'info>,
 
     #[account(mut)]
     pub creator: Signer<'info>,
@@ -66,15 +69,16 @@ pub struct InitStaking<'info> {
     )]
     pub nft_authority: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>
 }
 
 impl<'info> InitStaking<'info> {
-    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
             from: self.token_account.to_account_info(),
+            mint: self.token_mint.to_account_info(),
             to: self.stake_token_vault.to_account_info(),
             authority: self.creator.to_account_info()
         };
@@ -85,14 +89,17 @@ impl<'info> InitStaking<'info> {
     }
 }
 
-pub fn init_staking_handler(
-    ctx: Context<InitStaking>, 
-    reward: u64, 
-    minimum_period: i64,
-    staking_starts_at: i64,
-    staking_ends_at: i64,
-    max_stakers_count: u64
-) -> Result<()> {
+pub fn init_staking_handler(ctx: Context<InitStaking>, params: InitStakingParams) -> Result<()> {
+    let InitStakingParams {
+        reward,
+        minimum_period,
+        staking_starts_at,
+        staking_ends_at,
+        max_stakers_count,
+        non_custodial,
+        emission_curve
+    } = params;
+
     let clock = Clock::get().unwrap();
     let current_time = clock.unix_timestamp;
 
@@ -100,6 +107,14 @@ pub fn init_staking_handler(
     require_gt!(staking_ends_at, current_time, StakeError::InvalidStakeEndTime);
     require_gt!(staking_ends_at, staking_starts_at, StakeError::InvalidStakeEndTime);
 
+    match emission_curve {
+        EmissionCurve::Constant | EmissionCurve::Linear { .. } => {},
+        EmissionCurve::Exponential { decay, period } => {
+            require_gt!(period, 0, StakeError::InvalidEmissionCurve);
+            require!(decay > 0 && decay < MULTIPLIER_BASE, StakeError::InvalidEmissionCurve);
+        }
+    }
+
     let reward_mint = ctx.accounts.token_mint.key();
     let collection = ctx.accounts.collection_address.key();
     let creator = ctx.accounts.creator.key();
@@ -107,27 +122,48 @@ pub fn init_staking_handler(
     let token_auth_bump = *ctx.bumps.get("token_authority").ok_or(StakeError::StakeBumpError)?;
     let nft_auth_bump = *ctx.bumps.get("nft_authority").ok_or(StakeError::StakeBumpError)?;
 
-    let total_emission = calc_total_emission(reward, max_stakers_count, staking_starts_at, staking_ends_at)?;
+    let collection_source = get_collection_source(&ctx.accounts.collection_address.to_account_info())?;
+
+    // The Token Metadata freeze is only available to NFTs with Metaplex metadata
+    if non_custodial {
+        require!(collection_source != CollectionSource::TokenGroup, StakeError::TokenGroupCustodialOnly);
+    }
 
-    transfer(ctx.accounts.transfer_token_ctx(), total_emission)?;
+    // The vault is funded with the integral of the curve over the staking
+    let total_emission = calc_total_emission(
+        reward, 
+        &emission_curve,
+        max_stakers_count, 
+        staking_starts_at, 
+        staking_ends_at, 
+        MULTIPLIER_BASE
+    )?;
+
+    let decimals = ctx.accounts.token_mint.decimals;
+    let balance_before = ctx.accounts.stake_token_vault.amount;
+
+    // Gross up the deposit so the vault holds the total emission after any transfer fee
+    let deposit = calc_pre_fee_amount(&ctx.accounts.token_mint.to_account_info(), total_emission)?;
+
+    transfer_checked(ctx.accounts.transfer_token_ctx(), deposit, decimals)?;
+
+    ctx.accounts.stake_token_vault.reload()?;
+
+    let received = ctx.accounts.stake_token_vault.amount
+        .checked_sub(balance_before)
+        .ok_or(StakeError::ProgramSubError)?;
+
+    require_gte!(received, total_emission, StakeError::InsufficientDeposit);
 
     let stake_details = &mut ctx.accounts.stake_details;
 
-    **stake_details = Details::init(
-        creator,
-        reward_mint, 
-        collection,
-        reward,
-        max_stakers_count,
-        staking_starts_at,
-        staking_ends_at,
-        minimum_period,
+    **stake_details = Details {
         stake_bump,
         token_auth_bump,
         nft_auth_bump,
-        total_emission
-    );
-
+        current_balance: received,
+        ..Details::init(params, creator, reward_mint, collection, collection_source)
+    };
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{Details, LegacyNftRecord, NftRecord, RewardOverride}, utils::realloc_pda_account, StakeError};
+
+#[derive(Accounts)]
+#[instruction(nft_mint: Pubkey)]
+pub struct MigrateNftRecord<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        constraint = stake_details.uses_accumulator @ StakeError::MigrationRequired
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    /// CHECK: The record of the NFT created before the accumulator, checked once it is deserialized
+    #[account(
+        mut,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            nft_mint.as_ref()
+        ],
+        bump,
+        owner = crate::ID
+    )]
+    pub nft_record: UncheckedAccount<'info>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+pub fn migrate_nft_record_handler(ctx: Context<MigrateNftRecord>, _nft_mint: Pubkey) -> Result<()> {
+    let nft_record_info = ctx.accounts.nft_record.to_account_info();
+
+    // The records created with the accumulator already have their checkpoint
+    require_gt!(NftRecord::LEN, nft_record_info.data_len(), StakeError::AlreadyMigrated);
+
+    // The fields added since the record was created come before its bump, so it is read in its own layout
+    let legacy_record = LegacyNftRecord::try_from_data(&nft_record_info.try_borrow_data()?)?;
+
+    realloc_pda_account(
+        &ctx.accounts.payer.to_account_info(),
+        &nft_record_info,
+        &ctx.accounts.system_program.to_account_info(),
+        NftRecord::LEN
+    )?;
+
+    let mut nft_record = legacy_record.migrate(0);
+
+    // The checkpoint is the reward per NFT by the reward history when the unpaid reward started accruing
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (accrual_start, multiplier) = nft_record.accrual(reward_override.as_ref());
+
+    nft_record.reward_checkpoint = ctx.accounts.stake_details.migrated_checkpoint(accrual_start)?;
+    nft_record.try_serialize(&mut &mut nft_record_info.try_borrow_mut_data()?[..])?;
+
+    // Add the reward owed for the NFT to the liability of the staking, NFTs staked before the accumulator
+    // are never unbonding and have no reward accrued before an override
+    ctx.accounts.stake_details.add_migrated_record(nft_record.reward_checkpoint, multiplier, nft_record.accrued_reward)
+}
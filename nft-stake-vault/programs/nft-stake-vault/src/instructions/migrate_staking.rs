@@ -0,0 +1,86 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{token_interface::TokenAccount, associated_token::get_associated_token_address_with_program_id};
+
+use crate::{state::Details, utils::realloc_pda_account, StakeError};
+
+/// The offset of the length of the reward history in the stake record
+const REWARD_LEN_OFFSET: usize = 8 + 1 + 32 + 32;
+
+#[derive(Accounts)]
+pub struct MigrateStaking<'info> {
+    /// CHECK: The stake record created before the accumulator, checked once it is deserialized
+    #[account(
+        mut,
+        owner = crate::ID
+    )]
+    pub stake_details: UncheckedAccount<'info>,
+
+    /// The vault of the reward mint, checked once the stake record is deserialized
+    pub stake_token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+pub fn migrate_staking_handler(ctx: Context<MigrateStaking>) -> Result<()> {
+    let stake_details_info = ctx.accounts.stake_details.to_account_info();
+
+    // The stake record grows by the fields added since it was created, which read as zero
+    let reward_len = {
+        let data = stake_details_info.try_borrow_data()?;
+        let len_bytes = data
+            .get(REWARD_LEN_OFFSET..REWARD_LEN_OFFSET + 4)
+            .ok_or(ErrorCode::AccountDidNotDeserialize)?;
+
+        u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize
+    };
+
+    let new_len = (Details::LEN - 16) + (reward_len * 16);
+
+    if stake_details_info.data_len() < new_len {
+        realloc_pda_account(
+            &ctx.accounts.creator.to_account_info(),
+            &stake_details_info,
+            &ctx.accounts.system_program.to_account_info(),
+            new_len
+        )?;
+    }
+
+    let mut stake_details = Details::try_deserialize(&mut &stake_details_info.try_borrow_data()?[..])?;
+
+    // The same checks as the stake record of the other instructions
+    let stake_details_key = Pubkey::create_program_address(
+        &[b"stake", stake_details.collection.as_ref(), stake_details.creator.as_ref(), &[stake_details.stake_bump]],
+        ctx.program_id
+    ).map_err(|_| ErrorCode::ConstraintSeeds)?;
+
+    require_keys_eq!(stake_details_info.key(), stake_details_key, ErrorCode::ConstraintSeeds);
+    require_keys_eq!(stake_details.creator, ctx.accounts.creator.key(), ErrorCode::ConstraintHasOne);
+    require!(!stake_details.uses_accumulator, StakeError::AlreadyMigrated);
+
+    let token_authority_key = Pubkey::create_program_address(
+        &[b"token-authority", stake_details_key.as_ref(), &[stake_details.token_auth_bump]],
+        ctx.program_id
+    ).map_err(|_| ErrorCode::ConstraintSeeds)?;
+
+    let stake_token_vault = &ctx.accounts.stake_token_vault;
+    let stake_token_vault_key = get_associated_token_address_with_program_id(
+        &token_authority_key,
+        &stake_details.reward_mint,
+        stake_token_vault.to_account_info().owner
+    );
+
+    require_keys_eq!(stake_token_vault.key(), stake_token_vault_key, ErrorCode::ConstraintAssociated);
+
+    stake_details.migrate_to_accumulator()?;
+
+    // The balance in record now holds the rewards owed to the stakers, the vesting rewards are held apart
+    stake_details.current_balance = stake_token_vault.amount
+        .checked_sub(stake_details.vesting_balance)
+        .ok_or(StakeError::ProgramSubError)?;
+    stake_details.try_serialize(&mut &mut stake_details_info.try_borrow_mut_data()?[..])?;
+
+    Ok(())
+}
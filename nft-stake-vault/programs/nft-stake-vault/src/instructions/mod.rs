@@ -1,17 +1,65 @@
 pub use init_staking::*;
 pub use stake::*;
+pub use stake_many::*;
 pub use withdraw_reward::*;
+pub use withdraw_rewards_batch::*;
+pub use claim_vested::*;
 pub use unstake::*;
+pub use complete_unstake::*;
 pub use extend_staking::*;
 pub use change_reward::*;
+pub use set_reward_schedule::*;
+pub use migrate_staking::*;
+pub use migrate_nft_record::*;
 pub use add_funds::*;
+pub use add_reward_stream::*;
+pub use fund_reward_stream::*;
+pub use change_stream_reward::*;
+pub use refund_reward_stream::*;
+pub use add_collection::*;
+pub use set_allowlist::*;
+pub use set_rarity::*;
+pub use set_reward_override::*;
+pub use set_max_per_wallet::*;
+pub use set_lock_tiers::*;
+pub use set_unstake_penalty::*;
+pub use set_unstake_cooldown::*;
+pub use set_vesting_duration::*;
 pub use close_staking::*;
+pub use stake_compressed::*;
+pub use unstake_compressed::*;
+pub use stake_core::*;
+pub use unstake_core::*;
 
 pub mod init_staking;
 pub mod stake;
+pub mod stake_many;
 pub mod withdraw_reward;
+pub mod withdraw_rewards_batch;
+pub mod claim_vested;
 pub mod unstake;
+pub mod complete_unstake;
 pub mod extend_staking;
 pub mod change_reward;
+pub mod set_reward_schedule;
+pub mod migrate_staking;
+pub mod migrate_nft_record;
 pub mod add_funds;
-pub mod close_staking;
\ No newline at end of file
+pub mod add_reward_stream;
+pub mod fund_reward_stream;
+pub mod change_stream_reward;
+pub mod refund_reward_stream;
+pub mod add_collection;
+pub mod set_allowlist;
+pub mod set_rarity;
+pub mod set_reward_override;
+pub mod set_max_per_wallet;
+pub mod set_lock_tiers;
+pub mod set_unstake_penalty;
+pub mod set_unstake_cooldown;
+pub mod set_vesting_duration;
+pub mod close_staking;
+pub mod stake_compressed;
+pub mod unstake_compressed;
+pub mod stake_core;
+pub mod unstake_core;
\ No newline at end of file
@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, TransferChecked, TokenInterface, TokenAccount, Mint};
+
+use crate::{state::{Details, RewardStream}, StakeError};
+
+#[derive(Accounts)]
+pub struct RefundRewardStream<'info> {
+    #[account(
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"reward-stream",
+            stake_details.key().as_ref(),
+            stream_mint.key().as_ref()
+        ],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Account<'info, RewardStream>,
+
+    pub stream_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = stream_mint,
+        associated_token::authority = creator
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = stream_mint,
+        associated_token::authority = token_authority
+    )]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.token_auth_bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    pub creator: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+impl<'info> RefundRewardStream<'info> {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.stream_vault.to_account_info(),
+            mint: self.stream_mint.to_account_info(),
+            to: self.token_account.to_account_info(),
+            authority: self.token_authority.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn refund_reward_stream_handler(ctx: Context<RefundRewardStream>) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let Details {
+        max_stakers_count,
+        staking_ends_at,
+        current_stakers_count,
+        is_active: staking_status,
+        token_auth_bump,
+        ..
+    } = **stake_details;
+
+    let max_nft_multiplier = stake_details.max_nft_multiplier()?;
+    let stake_details_key = stake_details.key();
+
+    // The staking ends when it is closed, so the stream can no longer change
+    require_eq!(staking_status, false, StakeError::StakingActive);
+
+    let unearned = ctx.accounts.reward_stream.release_unearned(staking_ends_at, max_stakers_count, max_nft_multiplier)?;
+
+    // The stream reward of an NFT is paid when it is unstaked, so once none is staked the whole vault is left over
+    let refund = if current_stakers_count == 0 {
+        ctx.accounts.stream_vault.amount
+    } else {
+        unearned.min(ctx.accounts.stream_vault.amount)
+    };
+
+    let token_auth_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+    transfer_checked(
+        ctx.accounts.transfer_token_ctx().with_signer(&[&token_auth_seed[..]]),
+        refund,
+        ctx.accounts.stream_mint.decimals
+    )
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::Details, StakeError};
+
+#[derive(Accounts)]
+pub struct SetAllowlist<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_allowlist_handler(
+    ctx: Context<SetAllowlist>,
+    root: Option<[u8; 32]>,
+    allowlist_only: bool
+) -> Result<()> {
+    let staking_status = ctx.accounts.stake_details.is_active;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+
+    // Without an allowlist, the collection check can't be skipped
+    if allowlist_only {
+        require!(root.is_some(), StakeError::AllowlistRootMissing);
+    }
+
+    // Rotating the root only affects new stakes, staked NFTs keep their records
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_allowlist(root, allowlist_only);
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{Details, LockTier}, utils::apply_multiplier, StakeError, MAX_LOCK_TIERS};
+
+#[derive(Accounts)]
+pub struct SetLockTiers<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_lock_tiers_handler(ctx: Context<SetLockTiers>, lock_tiers: Vec<LockTier>) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let Details {
+        max_multiplier,
+        max_rarity_multiplier,
+        staking_ends_at,
+        is_active: staking_status,
+        ..
+    } = **stake_details;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    require_gte!(MAX_LOCK_TIERS, lock_tiers.len(), StakeError::MaxLockTiersReached);
+
+    for tier in lock_tiers.iter() {
+        require_gt!(tier.duration, 0, StakeError::InvalidLockDuration);
+        require_gt!(tier.multiplier, 0, StakeError::InvalidMultiplier);
+    }
+
+    let max_lock_multiplier = lock_tiers.iter().map(|tier| tier.multiplier).max().unwrap_or(0);
+
+    // A higher multiplier raises the emission the vault must cover until the staking ends
+    if max_lock_multiplier > stake_details.max_lock_multiplier {
+        let new_max_nft_multiplier = apply_multiplier(max_multiplier, max_rarity_multiplier)?;
+        let new_max_nft_multiplier = apply_multiplier(new_max_nft_multiplier, max_lock_multiplier)?;
+        stake_details.require_emission_covered(new_max_nft_multiplier, current_time)?;
+    }
+
+    // Staked NFTs keep the multiplier and lock expiry of the tier they chose
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_lock_tiers(lock_tiers);
+
+    Ok(())
+}
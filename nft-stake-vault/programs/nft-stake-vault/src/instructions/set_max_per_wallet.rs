@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::Details, StakeError};
+
+#[derive(Accounts)]
+pub struct SetMaxPerWallet<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_max_per_wallet_handler(ctx: Context<SetMaxPerWallet>, max_per_wallet: Option<u64>) -> Result<()> {
+    let staking_status = ctx.accounts.stake_details.is_active;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+
+    if let Some(max_per_wallet) = max_per_wallet {
+        require_gt!(max_per_wallet, 0, StakeError::InvalidWalletLimit);
+    }
+
+    // Lowering the limit only affects new stakes, wallets above it keep their NFTs staked
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_max_per_wallet(max_per_wallet);
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::Details, utils::apply_multiplier, StakeError};
+
+#[derive(Accounts)]
+pub struct SetRarity<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_rarity_handler(
+    ctx: Context<SetRarity>,
+    root: Option<[u8; 32]>,
+    max_rarity_multiplier: u64
+) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let Details {
+        max_multiplier,
+        max_lock_multiplier,
+        staking_ends_at,
+        is_active: staking_status,
+        ..
+    } = **stake_details;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    require_gt!(max_rarity_multiplier, 0, StakeError::InvalidMultiplier);
+
+    // A higher multiplier raises the emission the vault must cover until the staking ends
+    if max_rarity_multiplier > stake_details.max_rarity_multiplier {
+        let new_max_nft_multiplier = apply_multiplier(max_multiplier, max_rarity_multiplier)?;
+        let new_max_nft_multiplier = apply_multiplier(new_max_nft_multiplier, max_lock_multiplier)?;
+        stake_details.require_emission_covered(new_max_nft_multiplier, current_time)?;
+    }
+
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_rarity(root, max_rarity_multiplier);
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{Details, NftRecord, RewardOverride}, utils::{load_stream_accounts, stream_checkpoints, stream_rewards_of}, StakeError};
+
+#[derive(Accounts)]
+#[instruction(nft_mint: Pubkey)]
+pub struct SetRewardOverride<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = RewardOverride::LEN,
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: Account<'info, RewardOverride>,
+
+    /// CHECK: The record of the NFT, only read and written if the NFT is staked
+    #[account(
+        mut,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub nft_record: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+pub fn set_reward_override_handler(ctx: Context<SetRewardOverride>, nft_mint: Pubkey, multiplier: u64) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let Details {
+        staking_ends_at,
+        is_active: staking_status,
+        ..
+    } = **stake_details;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+
+    // A higher multiplier raises the emission the vault must cover until the staking ends
+    if multiplier > stake_details.max_nft_multiplier()? {
+        stake_details.require_emission_covered(multiplier, current_time)?;
+        ctx.accounts.stake_details.max_override_multiplier = multiplier;
+    }
+
+    // A newly created override account is still empty
+    let previous_override = if ctx.accounts.reward_override.nft_mint == nft_mint {
+        Some((*ctx.accounts.reward_override).clone())
+    } else {
+        None
+    };
+
+    let nft_record_info = ctx.accounts.nft_record.to_account_info();
+
+    // Checkpoint the reward of a staked NFT, so the override only applies from now
+    if !nft_record_info.data_is_empty() {
+        let mut nft_record = NftRecord::try_deserialize(&mut &nft_record_info.try_borrow_data()?[..])?;
+
+        // An unbonding NFT no longer earns rewards
+        require!(!nft_record.is_unbonding(), StakeError::NftUnbonding);
+        let (_accrual_start, previous_multiplier) = nft_record.accrual(previous_override.as_ref());
+        let previous_checkpoint = nft_record.reward_checkpoint;
+
+        let stake_details = &ctx.accounts.stake_details;
+
+        let (reward_tokens, _current_time, _is_eligible_for_reward) = stake_details.accrued_reward(
+            nft_record.staked_at,
+            nft_record.reward_checkpoint,
+            previous_multiplier
+        )?;
+
+        nft_record.accrued_reward = nft_record.accrued_reward
+            .checked_add(reward_tokens)
+            .ok_or(StakeError::ProgramAddError)?;
+        nft_record.reward_checkpoint = stake_details.reward_per_nft_at(current_time)?;
+
+        // The streams pay at the multiplier of the reward, so their reward so far is settled the same way
+        let reward_streams = load_stream_accounts(stake_details, ctx.remaining_accounts)?;
+
+        nft_record.accrued_stream_rewards = stream_rewards_of(&reward_streams, &nft_record, current_time, staking_ends_at, previous_multiplier)?;
+        nft_record.stream_checkpoints = stream_checkpoints(&reward_streams, current_time, staking_ends_at)?;
+
+        nft_record.try_serialize(&mut &mut nft_record_info.try_borrow_mut_data()?[..])?;
+
+        let stake_details = &mut ctx.accounts.stake_details;
+
+        // Move the checkpoint of the NFT to the new multiplier, the reward accrued so far stays owed
+        stake_details.change_staked_checkpoints(previous_checkpoint, previous_multiplier, false)?;
+        stake_details.change_staked_checkpoints(nft_record.reward_checkpoint, multiplier, true)?;
+        stake_details.change_unpaid_accrued_reward(reward_tokens, true)?;
+
+        // Count the NFT with the new multiplier
+        stake_details.decrease_staker_count(previous_multiplier)?;
+        stake_details.increase_staker_count(multiplier)?;
+    }
+
+    let bump = *ctx.bumps.get("reward_override").ok_or(StakeError::OverrideBumpError)?;
+
+    let reward_override = &mut ctx.accounts.reward_override;
+    **reward_override = RewardOverride::init(nft_mint, multiplier, current_time, bump);
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{Details, ScheduledReward}, StakeError, MAX_SCHEDULED_REWARDS};
+
+#[derive(Accounts)]
+pub struct SetRewardSchedule<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_reward_schedule_handler(ctx: Context<SetRewardSchedule>, reward_schedule: Vec<ScheduledReward>) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    let Details {
+        staking_ends_at,
+        is_active: staking_status,
+        ..
+    } = **stake_details;
+
+    let max_nft_multiplier = stake_details.max_nft_multiplier()?;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    stake_details.require_constant_curve()?;
+    require_gte!(MAX_SCHEDULED_REWARDS, reward_schedule.len(), StakeError::MaxScheduledRewardsReached);
+
+    // The changes start in the future and in order of time,
+    // a boost window is a raised reward followed by the reward to revert to
+    let mut last_start = current_time;
+
+    for change in reward_schedule.iter() {
+        require_gt!(change.starts_at, last_start, StakeError::InvalidRewardSchedule);
+        last_start = change.starts_at;
+    }
+
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_reward_schedule(reward_schedule, current_time)?;
+
+    // The vault covers the whole scheduled emission until the staking ends
+    stake_details.require_emission_covered(max_nft_multiplier, current_time)
+}
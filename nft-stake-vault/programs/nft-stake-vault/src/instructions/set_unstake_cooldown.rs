@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::{CollectionSource, Details}, StakeError, MAX_UNSTAKE_COOLDOWN};
+
+#[derive(Accounts)]
+pub struct SetUnstakeCooldown<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_unstake_cooldown_handler(ctx: Context<SetUnstakeCooldown>, unstake_cooldown: i64) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let Details {
+        is_active: staking_status,
+        current_stakers_count: current_stakers,
+        unstake_cooldown: current_cooldown,
+        collection_source,
+        ..
+    } = **stake_details;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(unstake_cooldown, 0, StakeError::InvalidUnstakeCooldown);
+    require_gte!(MAX_UNSTAKE_COOLDOWN, unstake_cooldown, StakeError::InvalidUnstakeCooldown);
+
+    // Core assets are returned by unstake_core, which has no cooldown
+    require!(
+        unstake_cooldown == 0 || collection_source != CollectionSource::Core,
+        StakeError::CooldownUnsupported
+    );
+
+    // The staked NFTs were staked expecting at most the current cooldown
+    require!(
+        current_stakers == 0 || unstake_cooldown <= current_cooldown,
+        StakeError::CooldownRaisedWhileStaked
+    );
+
+    // NFTs already unbonding keep the cooldown they were unstaked with
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_unstake_cooldown(unstake_cooldown);
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+
+use crate::{state::{Details, PenaltyDestination}, StakeError, MULTIPLIER_BASE};
+
+#[derive(Accounts)]
+pub struct SetUnstakePenalty<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = creator,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        token::mint = reward_mint
+    )]
+    pub penalty_treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub creator: Signer<'info>
+}
+
+pub fn set_unstake_penalty_handler(
+    ctx: Context<SetUnstakePenalty>,
+    unstake_penalty: u64,
+    penalty_destination: PenaltyDestination
+) -> Result<()> {
+    let staking_status = ctx.accounts.stake_details.is_active;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gte!(MULTIPLIER_BASE, unstake_penalty, StakeError::InvalidUnstakePenalty);
+
+    let penalty_treasury = match penalty_destination {
+        PenaltyDestination::Treasury => ctx.accounts.penalty_treasury
+            .as_ref()
+            .ok_or(StakeError::TreasuryAccountMissing)?
+            .key(),
+        _ => Pubkey::default()
+    };
+
+    // The penalty applies to the NFTs unstaked from now, including the ones already staked
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    stake_details.set_unstake_penalty(unstake_penalty, penalty_destination, penalty_treasury);
+
+    Ok(())
+}
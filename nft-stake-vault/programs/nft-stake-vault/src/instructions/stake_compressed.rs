@@ -0,0 +1,234 @@
+use anchor_lang::prelude::*;
+use mpl_bubblegum::{
+    hash::{hash_creators, hash_metadata},
+    instructions::TransferCpiBuilder,
+    programs::{SPL_ACCOUNT_COMPRESSION_ID, SPL_NOOP_ID},
+    types::MetadataArgs,
+    utils::get_asset_id
+};
+
+use crate::{
+    state::{Details, NftRecord, RewardOverride, StakeCompressedParams, StakerAccount},
+    utils::{apply_multiplier, load_stream_checkpoints},
+    StakeError,
+    MULTIPLIER_BASE
+};
+
+#[derive(Accounts)]
+#[instruction(params: StakeCompressedParams)]
+pub struct StakeCompressed<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = NftRecord::LEN,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            get_asset_id(&merkle_tree.key(), params.nonce).as_ref(),
+        ],
+        bump
+    )]
+    pub nft_record: Account<'info, NftRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            signer.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            get_asset_id(&merkle_tree.key(), params.nonce).as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the Bubblegum program
+    #[account(mut)]
+    pub tree_config: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the account compression program
+    #[account(mut)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"nft-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.nft_auth_bump
+    )]
+    pub nft_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = mpl_bubblegum::ID)]
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = SPL_NOOP_ID)]
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+pub fn stake_compressed_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, StakeCompressed<'info>>,
+    params: StakeCompressedParams
+) -> Result<()> {
+    let StakeCompressedParams {
+        root,
+        nonce,
+        index,
+        metadata,
+        allowlist_proof,
+        rarity,
+        lock_tier
+    } = params;
+
+    let stake_details = &ctx.accounts.stake_details;
+
+    let Details {
+        current_stakers_count: current_stakers,
+        max_stakers_count: max_stakers,
+        staking_starts_at,
+        staking_ends_at,
+        is_active: staking_status,
+        ..
+    } = **stake_details;
+
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    // The reward of the NFT accrues from the current reward per NFT
+    let reward_checkpoint = stake_details.reward_per_nft_at(current_time)?;
+    let stream_checkpoints = load_stream_checkpoints(stake_details, ctx.remaining_accounts, current_time)?;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gt!(max_stakers, current_stakers, StakeError::MaxStakersReached);
+    require_gte!(current_time, staking_starts_at, StakeError::StakingNotLive);
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    stake_details.require_wallet_limit(ctx.accounts.staker_account.staked_count, 1)?;
+
+    // Compressed NFTs are returned by unstake_compressed, which has no cooldown
+    require_eq!(stake_details.unstake_cooldown, 0, StakeError::CooldownUnsupported);
+
+    // The metadata is hashed into the leaf, so the transfer below fails if it was tampered with
+    let metadata = MetadataArgs::try_from_slice(&metadata)
+        .map_err(|_| StakeError::InvalidCompressedMetadata)?;
+
+    let asset_id = get_asset_id(&ctx.accounts.merkle_tree.key(), nonce);
+
+    stake_details.verify_allowlist(&asset_id, &allowlist_proof)?;
+
+    let collection_multiplier = if stake_details.allowlist_only {
+        // The allowlist replaces the collection check
+        MULTIPLIER_BASE
+    } else {
+        require!(stake_details.collection_source.uses_metadata(), StakeError::InvalidCollection);
+
+        let verified_collection = metadata.collection
+            .as_ref()
+            .filter(|collection| collection.verified)
+            .map(|collection| collection.key);
+
+        let verified_creators: Vec<Pubkey> = metadata.creators
+            .iter()
+            .filter(|creator| creator.verified)
+            .map(|creator| creator.address)
+            .collect();
+
+        stake_details
+            .metadata_multiplier(verified_collection, &verified_creators)
+            .ok_or(StakeError::InvalidCollectionOrCreator)?
+    };
+
+    // Scale the reward of the NFT by its rarity
+    let rarity_multiplier = stake_details.rarity_multiplier(&asset_id, rarity)?;
+    let multiplier = apply_multiplier(collection_multiplier, rarity_multiplier)?;
+
+    // And by the lock tier chosen by the staker
+    let (lock_multiplier, lock_expires_at) = stake_details.lock_tier(lock_tier, current_time)?;
+    let multiplier = apply_multiplier(multiplier, lock_multiplier)?;
+
+    let data_hash = hash_metadata(&metadata).map_err(|_| StakeError::InvalidCompressedMetadata)?;
+    let creator_hash = hash_creators(&metadata.creators);
+
+    // The proof follows the reward stream accounts
+    let proof: Vec<(&AccountInfo<'info>, bool, bool)> = ctx.remaining_accounts[stream_checkpoints.len()..]
+        .iter()
+        .map(|node| (node, false, false))
+        .collect();
+
+    // Bubblegum leaves can't be frozen, so compressed NFTs are held by the nft authority
+    // even when the staking is non-custodial
+    TransferCpiBuilder::new(&ctx.accounts.bubblegum_program)
+        .tree_config(&ctx.accounts.tree_config)
+        .leaf_owner(&ctx.accounts.signer, true)
+        .leaf_delegate(&ctx.accounts.signer, false)
+        .new_leaf_owner(&ctx.accounts.nft_authority)
+        .merkle_tree(&ctx.accounts.merkle_tree)
+        .log_wrapper(&ctx.accounts.log_wrapper)
+        .compression_program(&ctx.accounts.compression_program)
+        .system_program(&ctx.accounts.system_program)
+        .add_remaining_accounts(&proof)
+        .root(root)
+        .data_hash(data_hash)
+        .creator_hash(creator_hash)
+        .nonce(nonce)
+        .index(index)
+        .invoke()?;
+
+    let staker = ctx.accounts.signer.key();
+    let bump = *ctx.bumps.get("nft_record").ok_or(StakeError::NftBumpError)?;
+
+    let nft_record = &mut ctx.accounts.nft_record;
+    **nft_record = NftRecord::init(staker, asset_id, current_time, multiplier, lock_expires_at, reward_checkpoint, bump);
+    nft_record.stream_checkpoints = stream_checkpoints;
+
+    // Count the NFT in the staker account of the wallet
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staked_at = ctx.accounts.nft_record.staked_at;
+    let stake_details_key = ctx.accounts.stake_details.key();
+
+    let staker_account = &mut ctx.accounts.staker_account;
+    staker_account.init_if_new(stake_details_key, staker, staker_bump);
+    staker_account.add_staked(1, staked_at)?;
+
+    // An override set before staking replaces the multiplier of the NFT
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let effective_multiplier = reward_override.map_or(multiplier, |reward_override| reward_override.multiplier);
+
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    // Add the checkpoint of the NFT and increase staker count
+    stake_details.add_staked_nfts(reward_checkpoint, 1, effective_multiplier)
+}
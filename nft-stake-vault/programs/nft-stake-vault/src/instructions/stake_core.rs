@@ -0,0 +1,179 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::{Details, NftRecord, CollectionSource, RarityProof, RewardOverride, StakerAccount}, 
+    utils::{apply_multiplier, freeze_core_asset, load_stream_checkpoints, get_core_asset, CoreFreeze, MplCore}, 
+    StakeError
+};
+
+#[derive(Accounts)]
+pub struct StakeCore<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = NftRecord::LEN,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            asset.key().as_ref(),
+        ],
+        bump
+    )]
+    pub nft_record: Account<'info, NftRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            signer.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            asset.key().as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    /// CHECK: Validated in the handler and by the Core program
+    #[account(mut)]
+    pub asset: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the Core program
+    #[account(
+        mut,
+        constraint = stake_details.collection_multiplier(&collection.key()).is_some() @ StakeError::InvalidCollection
+    )]
+    pub collection: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"nft-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.nft_auth_bump
+    )]
+    pub nft_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub core_program: Program<'info, MplCore>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> StakeCore<'info> {
+    pub fn freeze_asset_accounts(&self) -> CoreFreeze<'info> {
+        CoreFreeze {
+            asset: self.asset.to_account_info(),
+            collection: self.collection.to_account_info(),
+            owner: self.signer.to_account_info(),
+            delegate: self.nft_authority.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            core_program: self.core_program.to_account_info()
+        }
+    }
+}
+
+pub fn stake_core_handler(
+    ctx: Context<StakeCore>,
+    allowlist_proof: Vec<[u8; 32]>,
+    rarity: Option<RarityProof>,
+    lock_tier: Option<u8>
+) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let Details {
+        current_stakers_count: current_stakers,
+        max_stakers_count: max_stakers,
+        staking_starts_at,
+        staking_ends_at,
+        is_active: staking_status,
+        collection_source,
+        ..
+    } = **stake_details;
+
+    let current_time = Clock::get().unwrap().unix_timestamp;
+
+    // The reward of the NFT accrues from the current reward per NFT
+    let reward_checkpoint = stake_details.reward_per_nft_at(current_time)?;
+    let stream_checkpoints = load_stream_checkpoints(stake_details, ctx.remaining_accounts, current_time)?;
+
+    require_eq!(staking_status, true, StakeError::StakingInactive);
+    require_gt!(max_stakers, current_stakers, StakeError::MaxStakersReached);
+    require_gte!(current_time, staking_starts_at, StakeError::StakingNotLive);
+    require_gte!(staking_ends_at, current_time, StakeError::StakingIsOver);
+    require!(collection_source == CollectionSource::Core, StakeError::InvalidCollection);
+    stake_details.require_wallet_limit(ctx.accounts.staker_account.staked_count, 1)?;
+
+    let staker = ctx.accounts.signer.key();
+    let (owner, asset_collection) = get_core_asset(&ctx.accounts.asset)?;
+
+    require_keys_eq!(owner, staker, StakeError::InvalidCoreAsset);
+
+    // The allowlist never replaces the collection check, the Core program needs the collection to freeze the asset
+    stake_details.verify_allowlist(&ctx.accounts.asset.key(), &allowlist_proof)?;
+    require!(asset_collection == Some(ctx.accounts.collection.key()), StakeError::InvalidCoreAsset);
+
+    let collection_multiplier = stake_details
+        .collection_multiplier(&ctx.accounts.collection.key())
+        .ok_or(StakeError::InvalidCollection)?;
+
+    // Scale the reward of the asset by its rarity
+    let rarity_multiplier = stake_details.rarity_multiplier(&ctx.accounts.asset.key(), rarity)?;
+    let multiplier = apply_multiplier(collection_multiplier, rarity_multiplier)?;
+
+    // And by the lock tier chosen by the staker
+    let (lock_multiplier, lock_expires_at) = stake_details.lock_tier(lock_tier, current_time)?;
+    let multiplier = apply_multiplier(multiplier, lock_multiplier)?;
+
+    // The asset stays in the staker's wallet, frozen with the nft authority as delegate
+    freeze_core_asset(ctx.accounts.freeze_asset_accounts())?;
+
+    let asset = ctx.accounts.asset.key();
+    let bump = *ctx.bumps.get("nft_record").ok_or(StakeError::NftBumpError)?;
+
+    let nft_record = &mut ctx.accounts.nft_record;
+    **nft_record = NftRecord::init(staker, asset, current_time, multiplier, lock_expires_at, reward_checkpoint, bump);
+    nft_record.stream_checkpoints = stream_checkpoints;
+
+    // Count the NFT in the staker account of the wallet
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staked_at = ctx.accounts.nft_record.staked_at;
+    let stake_details_key = ctx.accounts.stake_details.key();
+
+    let staker_account = &mut ctx.accounts.staker_account;
+    staker_account.init_if_new(stake_details_key, staker, staker_bump);
+    staker_account.add_staked(1, staked_at)?;
+
+    // An override set before staking replaces the multiplier of the NFT
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let effective_multiplier = reward_override.map_or(multiplier, |reward_override| reward_override.multiplier);
+
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    // Add the checkpoint of the NFT and increase staker count
+    stake_details.add_staked_nfts(reward_checkpoint, 1, effective_multiplier)
+}
@@ -1,10 +1,16 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, solana_program::sysvar};
 use anchor_spl::{
-    token::{Mint, Token, TokenAccount, Transfer, CloseAccount, transfer, close_account}, 
+    token_interface::{Mint, TokenInterface, TokenAccount, TransferChecked, CloseAccount, Revoke, transfer_checked, close_account, revoke}, 
+    metadata::{MasterEditionAccount, MetadataAccount, Metadata, ThawDelegatedAccount, thaw_delegated_account}, 
     associated_token::AssociatedToken
 };
 
-use crate::{state::{Details, NftRecord}, utils::calc_reward, StakeError};
+use crate::{
+    events::RewardPaid,
+    state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, 
+    utils::{calc_transfer_fee, settle_forfeit, ForfeitAccounts, load_reward_streams, pay_reward_streams, stream_rewards_of, StreamPayout, is_programmable, transfer_pnft, unlock_and_revoke_pnft, TransferPnft, LockPnft}, 
+    StakeError
+};
 
 #[derive(Accounts)]
 pub struct Unstake<'info> {
@@ -30,32 +36,79 @@ pub struct Unstake<'info> {
         bump = nft_record.bump,
         has_one = nft_mint,
         has_one = staker,
-        close = staker
+        constraint = !nft_record.is_unbonding() @ StakeError::NftUnbonding
     )]
     pub nft_record: Account<'info, NftRecord>,
 
-    pub reward_mint: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
         associated_token::mint = reward_mint,
-        associated_token::authority = token_authority
+        associated_token::authority = token_authority,
+        associated_token::token_program = reward_token_program
     )]
-    pub stake_token_vault: Account<'info, TokenAccount>,
+    pub stake_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         init_if_needed,
         payer = staker,
         associated_token::mint = reward_mint,
-        associated_token::authority = staker
+        associated_token::authority = staker,
+        associated_token::token_program = reward_token_program
+    )]
+    pub reward_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = stake_details.penalty_treasury
     )]
-    pub reward_receive_account: Box<Account<'info, TokenAccount>>,
+    pub penalty_treasury: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
     #[account(
         mint::decimals = 0,
         constraint = nft_mint.supply == 1 @ StakeError::TokenNotNFT,
     )]
-    nft_mint: Box<Account<'info, Mint>>,
+    nft_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
         init_if_needed,
@@ -63,16 +116,57 @@ pub struct Unstake<'info> {
         associated_token::mint = nft_mint,
         associated_token::authority = staker,
     )]
-    nft_receive_account: Box<Account<'info, TokenAccount>>,
+    nft_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
         associated_token::mint = nft_mint,
         associated_token::authority = nft_authority,
-        constraint = nft_custody.amount == 1 @ StakeError::TokenAccountEmpty,
-        close = staker
+        constraint = nft_custody.amount == 1 @ StakeError::TokenAccountEmpty
+    )]
+    pub nft_custody: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            Metadata::id().as_ref(),
+            nft_mint.key().as_ref()
+        ],
+        seeds::program = Metadata::id(),
+        bump
+    )]
+    nft_metadata: Option<Box<Account<'info, MetadataAccount>>>,
+
+    #[account(
+        seeds = [
+            b"metadata",
+            Metadata::id().as_ref(),
+            nft_mint.key().as_ref(),
+            b"edition"
+        ],
+        seeds::program = Metadata::id(),
+        bump
     )]
-    pub nft_custody: Box<Account<'info, TokenAccount>>,
+    nft_edition: Option<Box<Account<'info, MasterEditionAccount>>>,
+
+    /// CHECK: Token record of the custody token account, only required for custodial pNFTs
+    #[account(mut)]
+    pub custody_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Token record of the staker's token account, only required for pNFTs
+    #[account(mut)]
+    pub receive_token_record: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The rule set of the pNFT, validated by the Token Metadata program
+    pub auth_rules: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Validated by the Token Metadata program
+    pub auth_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: This account is not read or written
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: Option<UncheckedAccount<'info>>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -97,97 +191,300 @@ pub struct Unstake<'info> {
     #[account(mut)]
     pub staker: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub reward_token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>
+    pub system_program: Program<'info, System>,
+    pub metadata_program: Option<Program<'info, Metadata>>
 }
 
 impl<'info> Unstake<'info> {
-    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
             from: self.stake_token_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
             to: self.reward_receive_account.to_account_info(),
             authority: self.token_authority.to_account_info()
         };
     
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = self.reward_token_program.to_account_info();
 
         CpiContext::new(cpi_program, cpi_accounts)
     }
 
-    pub fn transfer_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
-            from: self.nft_custody.to_account_info(),
+    pub fn forfeit_accounts(&self) -> ForfeitAccounts<'info> {
+        ForfeitAccounts {
+            stake_token_vault: self.stake_token_vault.to_account_info(),
+            reward_mint: self.reward_mint.to_account_info(),
+            token_authority: self.token_authority.to_account_info(),
+            penalty_treasury: self.penalty_treasury.as_ref().map(|treasury| treasury.to_account_info()),
+            token_program: self.reward_token_program.to_account_info()
+        }
+    }
+
+    pub fn stream_payout_accounts(&self) -> StreamPayout<'info> {
+        StreamPayout {
+            staker: self.staker.to_account_info(),
+            token_authority: self.token_authority.to_account_info(),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            system_program: self.system_program.to_account_info()
+        }
+    }
+
+    pub fn nft_custody_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_custody.as_ref().ok_or(StakeError::CustodyAccountMissing)?.to_account_info())
+    }
+
+    pub fn metadata_program_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.metadata_program.as_ref().ok_or(StakeError::MetadataProgramMissing)?.to_account_info())
+    }
+
+    pub fn nft_metadata_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_metadata.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn nft_edition_info(&self) -> Result<AccountInfo<'info>> {
+        Ok(self.nft_edition.as_ref().ok_or(StakeError::MetadataAccountMissing)?.to_account_info())
+    }
+
+    pub fn transfer_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>> {
+        let cpi_accounts = TransferChecked {
+            from: self.nft_custody_info()?,
+            mint: self.nft_mint.to_account_info(),
             to: self.nft_receive_account.to_account_info(),
             authority: self.nft_authority.to_account_info()
         };
     
         let cpi_program = self.token_program.clone().to_account_info();
 
-        CpiContext::new(cpi_program, cpi_accounts)
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
     }
 
-    pub fn close_account_ctx(&self)-> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+    pub fn close_account_ctx(&self)-> Result<CpiContext<'_, '_, '_, 'info, CloseAccount<'info>>> {
         let cpi_accounts = CloseAccount {
-            account: self.nft_custody.to_account_info(),
+            account: self.nft_custody_info()?,
             destination: self.staker.to_account_info(),
             authority: self.nft_authority.to_account_info()
         };
         let cpi_program = self.token_program.to_account_info();
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn thaw_nft_ctx(&self) -> Result<CpiContext<'_, '_, '_, 'info, ThawDelegatedAccount<'info>>> {
+        let cpi_accounts = ThawDelegatedAccount {
+            metadata: self.nft_metadata_info()?,
+            delegate: self.nft_authority.to_account_info(),
+            token_account: self.nft_receive_account.to_account_info(),
+            edition: self.nft_edition_info()?,
+            mint: self.nft_mint.to_account_info(),
+            token_program: self.token_program.to_account_info()
+        };
+        let cpi_program = self.metadata_program_info()?;
+        Ok(CpiContext::new(cpi_program, cpi_accounts))
+    }
+
+    pub fn revoke_nft_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Revoke<'info>> {
+        let cpi_accounts = Revoke {
+            source: self.nft_receive_account.to_account_info(),
+            authority: self.staker.to_account_info()
+        };
+        let cpi_program = self.token_program.to_account_info();
         CpiContext::new(cpi_program, cpi_accounts)
     }
+
+    pub fn transfer_pnft_accounts(&self) -> Result<TransferPnft<'info>> {
+        Ok(TransferPnft {
+            token: self.nft_custody_info()?,
+            token_owner: self.nft_authority.to_account_info(),
+            destination: self.nft_receive_account.to_account_info(),
+            destination_owner: self.staker.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            owner_token_record: self.custody_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            destination_token_record: self.receive_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            authority: self.nft_authority.to_account_info(),
+            payer: self.staker.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
+
+    pub fn lock_pnft_accounts(&self) -> Result<LockPnft<'info>> {
+        Ok(LockPnft {
+            delegate: self.nft_authority.to_account_info(),
+            token_owner: self.staker.to_account_info(),
+            token: self.nft_receive_account.to_account_info(),
+            mint: self.nft_mint.to_account_info(),
+            metadata: self.nft_metadata_info()?,
+            edition: self.nft_edition_info()?,
+            token_record: self.receive_token_record.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            payer: self.staker.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            sysvar_instructions: self.sysvar_instructions.as_ref().ok_or(StakeError::PnftAccountMissing)?.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            metadata_program: self.metadata_program_info()?,
+            auth_rules_program: self.auth_rules_program.as_ref().map(|program| program.to_account_info()),
+            auth_rules: self.auth_rules.as_ref().map(|rules| rules.to_account_info())
+        })
+    }
 }
 
-pub fn unstake_handler(ctx: Context<Unstake>) -> Result<()> {
+pub fn unstake_handler<'info>(ctx: Context<'_, '_, '_, 'info, Unstake<'info>>) -> Result<()> {
     let stake_details = &ctx.accounts.stake_details;
 
     let Details {
-        minimum_period,
         staking_ends_at,
+        is_active: staking_status,
         token_auth_bump,
         nft_auth_bump,
+        non_custodial,
         ..
     } = **stake_details;
 
-    let reward_record = &stake_details.reward;
-    let reward_change_time_record = &stake_details.reward_change_time;
     let stake_details_key = stake_details.key();
 
     let staked_at = ctx.accounts.nft_record.staked_at;
+    let accrued_reward = ctx.accounts.nft_record.accrued_reward;
+    let reward_checkpoint = ctx.accounts.nft_record.reward_checkpoint;
+
+    // The reward accrues at the overridden multiplier from the time of the override
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (_accrual_start, multiplier) = ctx.accounts.nft_record.accrual(reward_override.as_ref());
     
-    let (reward_tokens, current_time, is_eligible_for_reward) = calc_reward(
-        staked_at, 
-        minimum_period, 
-        reward_record,
-        reward_change_time_record,
-        staking_ends_at
-    ).unwrap();
-
-    if is_eligible_for_reward {
-        // Transfer Reward Tokens
-        let token_auth_seed = &[&b"token-authority"[..], &stake_details_key.as_ref(), &[token_auth_bump]];
-        transfer(
-            ctx.accounts.transfer_token_ctx().with_signer(&[&token_auth_seed[..]]), 
-            reward_tokens
-        )?;
-    }
-
-    // Transfer NFT
-    let nft_auth_seed = &[&b"nft-authority"[..], &stake_details_key.as_ref(), &[nft_auth_bump]];
-    transfer(
-        ctx.accounts.transfer_nft_ctx().with_signer(&[&nft_auth_seed[..]]), 
-        1
+    let (reward_tokens, current_time, _is_eligible_for_reward) = stake_details.accrued_reward(
+        staked_at,
+        reward_checkpoint,
+        multiplier
+    )?;
+
+    // Closing the staking releases the locked NFTs
+    require!(!staking_status || !ctx.accounts.nft_record.is_locked(current_time), StakeError::NftLocked);
+
+    // Add the reward accrued before the last override
+    let reward_tokens = reward_tokens.checked_add(accrued_reward).ok_or(StakeError::ProgramAddError)?;
+
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staker = ctx.accounts.staker.key();
+    ctx.accounts.staker_account.init_if_new(stake_details_key, staker, staker_bump);
+
+    // Unstaking before the minimum period ends forfeits part of the reward
+    let forfeited_reward = ctx.accounts.stake_details.unstake_forfeit(staked_at, current_time, reward_tokens)?;
+    let reward_tokens = reward_tokens.checked_sub(forfeited_reward).ok_or(StakeError::ProgramSubError)?;
+    let penalty_destination = ctx.accounts.stake_details.penalty_destination;
+
+    let token_auth_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+
+    if reward_tokens > 0 {
+        let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+        if vesting_duration > 0 {
+            // The reward vests in the escrow of the staker and stays in the stake vault
+            let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+            let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+            vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+            vesting_escrow.deposit(reward_tokens, current_time, vesting_duration)?;
+            ctx.accounts.stake_details.change_vesting_balance(reward_tokens, true)?;
+        } else {
+            // The staker bears the transfer fee of the reward mint, if it has one
+            let transfer_fee = calc_transfer_fee(&ctx.accounts.reward_mint.to_account_info(), reward_tokens)?;
+            emit!(RewardPaid { stake_details: stake_details_key, staker, reward: reward_tokens, transfer_fee });
+
+            transfer_checked(
+                ctx.accounts.transfer_token_ctx().with_signer(&[&token_auth_seed[..]]), 
+                reward_tokens,
+                ctx.accounts.reward_mint.decimals
+            )?;
+        }
+
+        ctx.accounts.staker_account.add_claimed(reward_tokens)?;
+    }
+
+    // Burn the forfeited reward or send it to the treasury
+    settle_forfeit(
+        ctx.accounts.forfeit_accounts(),
+        penalty_destination,
+        forfeited_reward,
+        ctx.accounts.reward_mint.decimals,
+        &[&token_auth_seed[..]]
+    )?;
+
+    // Pay the reward streams for the same period, unstaking early forfeits the same share of them
+    let reward_streams = load_reward_streams(&ctx.accounts.stake_details, ctx.remaining_accounts)?;
+
+    let stream_rewards = stream_rewards_of(&reward_streams, &ctx.accounts.nft_record, current_time, staking_ends_at, multiplier)?
+        .into_iter()
+        .map(|stream_reward| {
+            let forfeited = ctx.accounts.stake_details.unstake_forfeit(staked_at, current_time, stream_reward)?;
+
+            Ok(stream_reward - forfeited)
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    pay_reward_streams(
+        &ctx.accounts.stream_payout_accounts(),
+        ctx.remaining_accounts,
+        &reward_streams,
+        &stream_rewards,
+        &[&token_auth_seed[..]]
     )?;
 
-    // Close NFT Custody Account
-    close_account(ctx.accounts.close_account_ctx().with_signer(&[&nft_auth_seed[..]]))?;
-    
     let stake_details = &mut ctx.accounts.stake_details;
 
-    // Delete stake weight and reduce staker count
-    stake_details.update_staked_weight(staked_at, false)?; 
-    stake_details.decrease_staker_count()?;
+    // Delete the checkpoint of the NFT, reduce staker count and decrease the balance in record by the paid and forfeited reward
+    stake_details.remove_unstaked_nft(reward_checkpoint, multiplier, accrued_reward, reward_tokens, forfeited_reward)?;
+
+    let unstake_cooldown = stake_details.unstake_cooldown;
+
+    // Keep the NFT in the staking until the cooldown ends, it no longer earns rewards
+    if staking_status && unstake_cooldown > 0 {
+        let unbonding_ends_at = current_time.checked_add(unstake_cooldown).ok_or(StakeError::ProgramAddError)?;
+
+        let nft_record = &mut ctx.accounts.nft_record;
+        nft_record.accrued_reward = 0;
+        nft_record.accrued_stream_rewards = vec![];
+        nft_record.unbonding_ends_at = Some(unbonding_ends_at);
+
+        return Ok(());
+    }
+
+    // Release NFT
+    let is_pnft = ctx.accounts.nft_metadata
+        .as_ref()
+        .is_some_and(|metadata| is_programmable(metadata));
+    let nft_auth_seed = &[&b"nft-authority"[..], &stake_details_key.as_ref(), &[nft_auth_bump]];
+
+    if non_custodial {
+        // Thaw the NFT in the staker's wallet and remove the nft authority as delegate
+        if is_pnft {
+            unlock_and_revoke_pnft(ctx.accounts.lock_pnft_accounts()?, &[&nft_auth_seed[..]])?;
+        } else {
+            thaw_delegated_account(ctx.accounts.thaw_nft_ctx()?.with_signer(&[&nft_auth_seed[..]]))?;
+            revoke(ctx.accounts.revoke_nft_ctx())?;
+        }
+    } else {
+        if is_pnft {
+            // Token Metadata thaws the custody account to move the pNFT, and refunds its token record to the staker
+            transfer_pnft(ctx.accounts.transfer_pnft_accounts()?, &[&nft_auth_seed[..]])?;
+        } else {
+            transfer_checked(
+                ctx.accounts.transfer_nft_ctx()?.with_signer(&[&nft_auth_seed[..]]), 
+                1,
+                0
+            )?;
+        }
+
+        // Close NFT Custody Account
+        close_account(ctx.accounts.close_account_ctx()?.with_signer(&[&nft_auth_seed[..]]))?;
+    }
+    
+    ctx.accounts.staker_account.remove_staked();
 
-    // Decrease the balance in record
-    stake_details.decrease_current_balance(staked_at, current_time)
+    ctx.accounts.nft_record.close(ctx.accounts.staker.to_account_info())
 }
\ No newline at end of file
@@ -0,0 +1,300 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface, TokenAccount, TransferChecked, transfer_checked},
+    associated_token::AssociatedToken
+};
+
+use crate::{
+    events::RewardPaid,
+    state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, 
+    utils::{calc_transfer_fee, settle_forfeit, ForfeitAccounts, load_reward_streams, pay_reward_streams, stream_rewards_of, StreamPayout, thaw_core_asset, CoreFreeze, MplCore}, 
+    StakeError
+};
+
+#[derive(Accounts)]
+pub struct UnstakeCore<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            stake_details.collection.as_ref(),
+            stake_details.creator.as_ref()
+        ],
+        bump = stake_details.stake_bump,
+        has_one = reward_mint
+    )]
+    pub stake_details: Account<'info, Details>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"nft-record",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref(),
+        ],
+        bump = nft_record.bump,
+        has_one = staker,
+        constraint = nft_record.nft_mint == asset.key() @ StakeError::InvalidAssetId,
+        close = staker
+    )]
+    pub nft_record: Account<'info, NftRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = token_authority
+    )]
+    pub stake_token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        associated_token::mint = reward_mint,
+        associated_token::authority = staker
+    )]
+    pub reward_receive_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = stake_details.penalty_treasury
+    )]
+    pub penalty_treasury: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: Validated by the Core program
+    #[account(mut)]
+    pub asset: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the Core program
+    #[account(
+        mut,
+        constraint = stake_details.collection_multiplier(&collection.key()).is_some() @ StakeError::InvalidCollection
+    )]
+    pub collection: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"token-authority",
+            stake_details.key().as_ref(),
+        ],
+        bump = stake_details.token_auth_bump
+    )]
+    pub token_authority: UncheckedAccount<'info>,
+
+    /// CHECK: This account is not read or written
+    #[account(
+        seeds = [
+            b"nft-authority",
+            stake_details.key().as_ref()
+        ],
+        bump = stake_details.nft_auth_bump
+    )]
+    pub nft_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    pub core_program: Program<'info, MplCore>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+impl<'info> UnstakeCore<'info> {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: self.stake_token_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_receive_account.to_account_info(),
+            authority: self.token_authority.to_account_info()
+        };
+
+        let cpi_program = self.token_program.to_account_info();
+
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+
+    pub fn forfeit_accounts(&self) -> ForfeitAccounts<'info> {
+        ForfeitAccounts {
+            stake_token_vault: self.stake_token_vault.to_account_info(),
+            reward_mint: self.reward_mint.to_account_info(),
+            token_authority: self.token_authority.to_account_info(),
+            penalty_treasury: self.penalty_treasury.as_ref().map(|treasury| treasury.to_account_info()),
+            token_program: self.token_program.to_account_info()
+        }
+    }
+
+    pub fn stream_payout_accounts(&self) -> StreamPayout<'info> {
+        StreamPayout {
+            staker: self.staker.to_account_info(),
+            token_authority: self.token_authority.to_account_info(),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            system_program: self.system_program.to_account_info()
+        }
+    }
+
+    pub fn thaw_asset_accounts(&self) -> CoreFreeze<'info> {
+        CoreFreeze {
+            asset: self.asset.to_account_info(),
+            collection: self.collection.to_account_info(),
+            owner: self.staker.to_account_info(),
+            delegate: self.nft_authority.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            core_program: self.core_program.to_account_info()
+        }
+    }
+}
+
+pub fn unstake_core_handler<'info>(ctx: Context<'_, '_, '_, 'info, UnstakeCore<'info>>) -> Result<()> {
+    let stake_details = &ctx.accounts.stake_details;
+
+    let Details {
+        staking_ends_at,
+        is_active: staking_status,
+        token_auth_bump,
+        nft_auth_bump,
+        ..
+    } = **stake_details;
+
+    let stake_details_key = stake_details.key();
+
+    let staked_at = ctx.accounts.nft_record.staked_at;
+    let accrued_reward = ctx.accounts.nft_record.accrued_reward;
+    let reward_checkpoint = ctx.accounts.nft_record.reward_checkpoint;
+
+    // The reward accrues at the overridden multiplier from the time of the override
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (_accrual_start, multiplier) = ctx.accounts.nft_record.accrual(reward_override.as_ref());
+
+    let (reward_tokens, current_time, _is_eligible_for_reward) = stake_details.accrued_reward(
+        staked_at,
+        reward_checkpoint,
+        multiplier
+    )?;
+
+    // Closing the staking releases the locked NFTs
+    require!(!staking_status || !ctx.accounts.nft_record.is_locked(current_time), StakeError::NftLocked);
+
+    // Add the reward accrued before the last override
+    let reward_tokens = reward_tokens.checked_add(accrued_reward).ok_or(StakeError::ProgramAddError)?;
+
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staker = ctx.accounts.staker.key();
+    ctx.accounts.staker_account.init_if_new(stake_details_key, staker, staker_bump);
+
+    // Unstaking before the minimum period ends forfeits part of the reward
+    let forfeited_reward = ctx.accounts.stake_details.unstake_forfeit(staked_at, current_time, reward_tokens)?;
+    let reward_tokens = reward_tokens.checked_sub(forfeited_reward).ok_or(StakeError::ProgramSubError)?;
+    let penalty_destination = ctx.accounts.stake_details.penalty_destination;
+
+    let token_auth_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+
+    if reward_tokens > 0 {
+        let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+        if vesting_duration > 0 {
+            // The reward vests in the escrow of the staker and stays in the stake vault
+            let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+            let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+            vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+            vesting_escrow.deposit(reward_tokens, current_time, vesting_duration)?;
+            ctx.accounts.stake_details.change_vesting_balance(reward_tokens, true)?;
+        } else {
+            // The staker bears the transfer fee of the reward mint, if it has one
+            let transfer_fee = calc_transfer_fee(&ctx.accounts.reward_mint.to_account_info(), reward_tokens)?;
+            emit!(RewardPaid { stake_details: stake_details_key, staker, reward: reward_tokens, transfer_fee });
+
+            transfer_checked(
+                ctx.accounts.transfer_token_ctx().with_signer(&[&token_auth_seed[..]]),
+                reward_tokens,
+                ctx.accounts.reward_mint.decimals
+            )?;
+        }
+
+        ctx.accounts.staker_account.add_claimed(reward_tokens)?;
+    }
+
+    // Burn the forfeited reward or send it to the treasury
+    settle_forfeit(
+        ctx.accounts.forfeit_accounts(),
+        penalty_destination,
+        forfeited_reward,
+        ctx.accounts.reward_mint.decimals,
+        &[&token_auth_seed[..]]
+    )?;
+
+    // Pay the reward streams for the same period, unstaking early forfeits the same share of them
+    let reward_streams = load_reward_streams(&ctx.accounts.stake_details, ctx.remaining_accounts)?;
+
+    let stream_rewards = stream_rewards_of(&reward_streams, &ctx.accounts.nft_record, current_time, staking_ends_at, multiplier)?
+        .into_iter()
+        .map(|stream_reward| {
+            let forfeited = ctx.accounts.stake_details.unstake_forfeit(staked_at, current_time, stream_reward)?;
+
+            Ok(stream_reward - forfeited)
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    pay_reward_streams(
+        &ctx.accounts.stream_payout_accounts(),
+        ctx.remaining_accounts,
+        &reward_streams,
+        &stream_rewards,
+        &[&token_auth_seed[..]]
+    )?;
+
+    // Thaw the asset in the staker's wallet and remove the freeze delegate
+    let nft_auth_seed = &[&b"nft-authority"[..], stake_details_key.as_ref(), &[nft_auth_bump]];
+    thaw_core_asset(ctx.accounts.thaw_asset_accounts(), &[&nft_auth_seed[..]])?;
+
+    ctx.accounts.staker_account.remove_staked();
+
+    let stake_details = &mut ctx.accounts.stake_details;
+
+    // Delete the checkpoint of the NFT, reduce staker count and decrease the balance in record by the paid and forfeited reward
+    stake_details.remove_unstaked_nft(reward_checkpoint, multiplier, accrued_reward, reward_tokens, forfeited_reward)?;
+
+    Ok(())
+}
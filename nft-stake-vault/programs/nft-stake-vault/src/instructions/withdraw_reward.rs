@@ -1,10 +1,10 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token::{Mint, Token, TokenAccount, Transfer, transfer}, 
+    token_interface::{Mint, TokenInterface, TokenAccount, TransferChecked, transfer_checked}, 
     associated_token::AssociatedToken
 };
 
-use crate::{state::{Details, NftRecord}, utils::calc_reward, StakeError};
+use crate::{events::RewardPaid, state::{Details, NftRecord, RewardOverride, StakerAccount, VestingEscrow}, utils::{calc_transfer_fee, load_reward_streams, pay_reward_streams, stream_checkpoints, stream_rewards_of, StreamPayout}, StakeError};
 
 #[derive(Accounts)]
 pub struct WithdrawReward<'info> {
@@ -28,18 +28,57 @@ pub struct WithdrawReward<'info> {
             nft_record.nft_mint.as_ref(),
         ],
         bump = nft_record.bump,
-        has_one = staker
+        has_one = staker,
+        constraint = !nft_record.is_unbonding() @ StakeError::NftUnbonding
     )]
     pub nft_record: Account<'info, NftRecord>,
 
-    pub reward_mint: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = StakerAccount::LEN,
+        seeds = [
+            b"staker",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub staker_account: Box<Account<'info, StakerAccount>>,
+
+    /// Only required while the rewards of the staking vest
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = VestingEscrow::LEN,
+        seeds = [
+            b"vesting",
+            stake_details.key().as_ref(),
+            staker.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_escrow: Option<Box<Account<'info, VestingEscrow>>>,
+
+    /// CHECK: The reward override of the NFT, read if the creator has set one
+    #[account(
+        seeds = [
+            b"reward-override",
+            stake_details.key().as_ref(),
+            nft_record.nft_mint.as_ref()
+        ],
+        bump
+    )]
+    pub reward_override: UncheckedAccount<'info>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = reward_mint,
         associated_token::authority = token_authority
     )]
-    pub stake_token_vault: Account<'info, TokenAccount>,
+    pub stake_token_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init_if_needed,
@@ -47,7 +86,7 @@ pub struct WithdrawReward<'info> {
         associated_token::mint = reward_mint,
         associated_token::authority = staker
     )]
-    pub reward_receive_account: Account<'info, TokenAccount>,
+    pub reward_receive_account: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: This account is not read or written
     #[account(
@@ -62,15 +101,16 @@ pub struct WithdrawReward<'info> {
     #[account(mut)]
     pub staker: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>
 }
 
 impl<'info> WithdrawReward<'info> {
-    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
-        let cpi_accounts = Transfer {
+    pub fn transfer_token_ctx(&self) -> CpiContext<'_, '_, '_, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
             from: self.stake_token_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
             to: self.reward_receive_account.to_account_info(),
             authority: self.token_authority.to_account_info()
         };
@@ -79,57 +119,106 @@ impl<'info> WithdrawReward<'info> {
 
         CpiContext::new(cpi_program, cpi_accounts)
     }
+
+    pub fn stream_payout_accounts(&self) -> StreamPayout<'info> {
+        StreamPayout {
+            staker: self.staker.to_account_info(),
+            token_authority: self.token_authority.to_account_info(),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            system_program: self.system_program.to_account_info()
+        }
+    }
 }
 
-pub fn withdraw_reward_handler(ctx: Context<WithdrawReward>) -> Result<()> {
+pub fn withdraw_reward_handler<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawReward<'info>>) -> Result<()> {
     let stake_details = &ctx.accounts.stake_details;
 
     let Details {
-        minimum_period,
         staking_ends_at,
         is_active: staking_status,
         token_auth_bump,
         ..
     } = **stake_details;
 
-    let reward_record = &stake_details.reward;
-    let reward_change_time_record = &stake_details.reward_change_time;
     let stake_details_key = stake_details.key();
 
     let staked_at = ctx.accounts.nft_record.staked_at;
+    let accrued_reward = ctx.accounts.nft_record.accrued_reward;
+    let reward_checkpoint = ctx.accounts.nft_record.reward_checkpoint;
+
+    // The reward accrues at the overridden multiplier from the time of the override
+    let reward_override = RewardOverride::load(&ctx.accounts.reward_override)?;
+    let (_accrual_start, multiplier) = ctx.accounts.nft_record.accrual(reward_override.as_ref());
     
     require_eq!(staking_status, true, StakeError::StakingInactive);
     require_gte!(staking_ends_at, staked_at, StakeError::StakingIsOver);
 
-    let (reward_tokens, current_time, is_eligible_for_reward) = calc_reward(
-        staked_at, 
-        minimum_period, 
-        reward_record,
-        reward_change_time_record,
-        staking_ends_at
-    ).unwrap();
+    let (reward_tokens, current_time, is_eligible_for_reward) = stake_details.accrued_reward(
+        staked_at,
+        reward_checkpoint,
+        multiplier
+    )?;
 
-    if is_eligible_for_reward {
-        let authority_seed = &[&b"token-authority"[..], &stake_details_key.as_ref(), &[token_auth_bump]];
+    // Add the reward accrued before the last override
+    let reward_tokens = reward_tokens.checked_add(accrued_reward).ok_or(StakeError::ProgramAddError)?;
 
-        transfer(
-            ctx.accounts.transfer_token_ctx().with_signer(&[&authority_seed[..]]), 
-            reward_tokens)?;
+    // Keep the stats of the wallet in its staker account
+    let staker_bump = *ctx.bumps.get("staker_account").ok_or(StakeError::StakerBumpError)?;
+    let staker = ctx.accounts.staker.key();
+    ctx.accounts.staker_account.init_if_new(stake_details_key, staker, staker_bump);
+
+    let authority_seed = &[&b"token-authority"[..], stake_details_key.as_ref(), &[token_auth_bump]];
+
+    if is_eligible_for_reward {
+        let vesting_duration = ctx.accounts.stake_details.vesting_duration;
+
+        if vesting_duration > 0 {
+            // The reward vests in the escrow of the staker and stays in the stake vault
+            let vesting_escrow = ctx.accounts.vesting_escrow.as_mut().ok_or(StakeError::VestingEscrowMissing)?;
+            let vesting_bump = *ctx.bumps.get("vesting_escrow").ok_or(StakeError::VestingBumpError)?;
+            vesting_escrow.init_if_new(stake_details_key, staker, vesting_bump);
+            vesting_escrow.deposit(reward_tokens, current_time, vesting_duration)?;
+            ctx.accounts.stake_details.change_vesting_balance(reward_tokens, true)?;
+        } else {
+            // The staker bears the transfer fee of the reward mint, if it has one
+            let transfer_fee = calc_transfer_fee(&ctx.accounts.reward_mint.to_account_info(), reward_tokens)?;
+            emit!(RewardPaid { stake_details: stake_details_key, staker, reward: reward_tokens, transfer_fee });
+
+            transfer_checked(
+                ctx.accounts.transfer_token_ctx().with_signer(&[&authority_seed[..]]), 
+                reward_tokens,
+                ctx.accounts.reward_mint.decimals
+            )?;
+        }
+
+        ctx.accounts.staker_account.add_claimed(reward_tokens)?;
     } else {
         return err!(StakeError::IneligibleForReward);
     }
 
-    ctx.accounts.nft_record.staked_at = current_time;
+    // Pay the reward streams for the same period, at the multiplier of the reward
+    let reward_streams = load_reward_streams(&ctx.accounts.stake_details, ctx.remaining_accounts)?;
 
-    let stake_details = &mut ctx.accounts.stake_details;
+    let stream_rewards = stream_rewards_of(&reward_streams, &ctx.accounts.nft_record, current_time, staking_ends_at, multiplier)?;
+
+    pay_reward_streams(
+        &ctx.accounts.stream_payout_accounts(),
+        ctx.remaining_accounts,
+        &reward_streams,
+        &stream_rewards,
+        &[&authority_seed[..]]
+    )?;
 
-    // Remove previous stake weight
-    stake_details.update_staked_weight(staked_at, false)?;
+    ctx.accounts.nft_record.stream_checkpoints = stream_checkpoints(&reward_streams, current_time, staking_ends_at)?;
+    ctx.accounts.nft_record.accrued_stream_rewards = vec![];
+    ctx.accounts.nft_record.staked_at = current_time;
+    ctx.accounts.nft_record.accrued_reward = 0;
+    ctx.accounts.nft_record.reward_checkpoint = ctx.accounts.stake_details.reward_per_nft_at(current_time)?;
 
-    // Add new stake weight
-    stake_details.update_staked_weight(current_time, true)?;
+    let new_checkpoint = ctx.accounts.nft_record.reward_checkpoint;
+    let stake_details = &mut ctx.accounts.stake_details;
 
-    // Decrease the balance in record
-    stake_details.decrease_current_balance(staked_at, current_time)
+    // Move the checkpoint of the NFT to now and decrease the balance in record by the paid reward
+    stake_details.settle_withdrawn_reward(reward_checkpoint, new_checkpoint, multiplier, accrued_reward, reward_tokens)
  
 }
\ No newline at end of file
@@ -1,43 +1,79 @@
 use anchor_lang::prelude::*;
 
+mod events;
 mod instructions;
 mod state;
 mod utils;
 
 use instructions::*;
+use state::{InitStakingParams, LockTier, NftProofs, PenaltyDestination, RarityProof, ScheduledReward, StakeCompressedParams};
 
 declare_id!("FZaTXcKpGef7ew74UHpJAkrZAfhMTZbSFJ297aKjURXN");
 
 #[constant]
-pub const WEIGHT: u128 = 1_000_000_000;
+pub const MULTIPLIER_BASE: u64 = 10_000;
+
+#[constant]
+pub const MAX_COLLECTIONS: usize = 8;
+
+#[constant]
+pub const MAX_LOCK_TIERS: usize = 4;
+
+#[constant]
+pub const MAX_REWARD_STREAMS: usize = 3;
+
+#[constant]
+pub const MAX_SCHEDULED_REWARDS: usize = 8;
+
+// 30 days
+#[constant]
+pub const MAX_UNSTAKE_COOLDOWN: i64 = 2_592_000;
 
 #[program]
 pub mod nft_stake_vault {
     use super::*;
 
-    pub fn init_staking(
-        ctx: Context<InitStaking>, 
-        reward: u64, 
-        minimum_period: i64,
-        staking_starts_at: i64,
-        staking_ends_at: i64,
-        max_stakers_count: u64
+    pub fn init_staking(ctx: Context<InitStaking>, params: InitStakingParams) -> Result<()> {
+        init_staking_handler(ctx, params)
+    }
+
+    pub fn stake(
+        ctx: Context<Stake>,
+        allowlist_proof: Vec<[u8; 32]>,
+        rarity: Option<RarityProof>,
+        lock_tier: Option<u8>
     ) -> Result<()> {
-        init_staking_handler(ctx, reward, minimum_period, staking_starts_at, staking_ends_at, max_stakers_count)
+        stake_handler(ctx, allowlist_proof, rarity, lock_tier)
     }
 
-    pub fn stake(ctx: Context<Stake>) -> Result<()> {
-        stake_handler(ctx)
+    pub fn stake_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeMany<'info>>,
+        proofs: Vec<NftProofs>,
+        lock_tier: Option<u8>
+    ) -> Result<()> {
+        stake_many_handler(ctx, proofs, lock_tier)
     }
 
-    pub fn withdraw_reward(ctx: Context<WithdrawReward>) -> Result<()> {
+    pub fn withdraw_reward<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawReward<'info>>) -> Result<()> {
         withdraw_reward_handler(ctx)
     }
 
-    pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
+    pub fn withdraw_rewards_batch<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawRewardsBatch<'info>>) -> Result<()> {
+        withdraw_rewards_batch_handler(ctx)
+    }
+
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        claim_vested_handler(ctx)
+    }
+
+    pub fn unstake<'info>(ctx: Context<'_, '_, '_, 'info, Unstake<'info>>) -> Result<()> {
         unstake_handler(ctx)
     }
 
+    pub fn complete_unstake(ctx: Context<CompleteUnstake>) -> Result<()> {
+        complete_unstake_handler(ctx)
+    }
+
     pub fn extend_staking(ctx: Context<ExtendStaking>, new_end_time: i64) -> Result<()> {
         extend_staking_handler(ctx, new_end_time)
     }
@@ -46,13 +82,112 @@ pub mod nft_stake_vault {
         change_reward_handler(ctx, new_reward)
     }
 
+    pub fn set_reward_schedule(ctx: Context<SetRewardSchedule>, reward_schedule: Vec<ScheduledReward>) -> Result<()> {
+        set_reward_schedule_handler(ctx, reward_schedule)
+    }
+
+    pub fn migrate_staking(ctx: Context<MigrateStaking>) -> Result<()> {
+        migrate_staking_handler(ctx)
+    }
+
+    pub fn migrate_nft_record(ctx: Context<MigrateNftRecord>, nft_mint: Pubkey) -> Result<()> {
+        migrate_nft_record_handler(ctx, nft_mint)
+    }
+
     pub fn add_funds(ctx: Context<AddFunds>, amount: u64) -> Result<()> {
         add_funds_handler(ctx, amount)
     }
 
+    pub fn add_reward_stream(ctx: Context<AddRewardStream>, reward: u64, funds: u64) -> Result<()> {
+        add_reward_stream_handler(ctx, reward, funds)
+    }
+
+    pub fn fund_reward_stream(ctx: Context<FundRewardStream>, amount: u64) -> Result<()> {
+        fund_reward_stream_handler(ctx, amount)
+    }
+
+    pub fn change_stream_reward(ctx: Context<ChangeStreamReward>, new_reward: u64) -> Result<()> {
+        change_stream_reward_handler(ctx, new_reward)
+    }
+
+    pub fn refund_reward_stream(ctx: Context<RefundRewardStream>) -> Result<()> {
+        refund_reward_stream_handler(ctx)
+    }
+
+    pub fn add_collection(ctx: Context<AddCollection>, multiplier: u64) -> Result<()> {
+        add_collection_handler(ctx, multiplier)
+    }
+
+    pub fn set_allowlist(ctx: Context<SetAllowlist>, root: Option<[u8; 32]>, allowlist_only: bool) -> Result<()> {
+        set_allowlist_handler(ctx, root, allowlist_only)
+    }
+
+    pub fn set_rarity(ctx: Context<SetRarity>, root: Option<[u8; 32]>, max_rarity_multiplier: u64) -> Result<()> {
+        set_rarity_handler(ctx, root, max_rarity_multiplier)
+    }
+
+    pub fn set_reward_override(ctx: Context<SetRewardOverride>, nft_mint: Pubkey, multiplier: u64) -> Result<()> {
+        set_reward_override_handler(ctx, nft_mint, multiplier)
+    }
+
+    pub fn set_max_per_wallet(ctx: Context<SetMaxPerWallet>, max_per_wallet: Option<u64>) -> Result<()> {
+        set_max_per_wallet_handler(ctx, max_per_wallet)
+    }
+
+    pub fn set_lock_tiers(ctx: Context<SetLockTiers>, lock_tiers: Vec<LockTier>) -> Result<()> {
+        set_lock_tiers_handler(ctx, lock_tiers)
+    }
+
+    pub fn set_unstake_penalty(
+        ctx: Context<SetUnstakePenalty>,
+        unstake_penalty: u64,
+        penalty_destination: PenaltyDestination
+    ) -> Result<()> {
+        set_unstake_penalty_handler(ctx, unstake_penalty, penalty_destination)
+    }
+
+    pub fn set_unstake_cooldown(ctx: Context<SetUnstakeCooldown>, unstake_cooldown: i64) -> Result<()> {
+        set_unstake_cooldown_handler(ctx, unstake_cooldown)
+    }
+
+    pub fn set_vesting_duration(ctx: Context<SetVestingDuration>, vesting_duration: i64) -> Result<()> {
+        set_vesting_duration_handler(ctx, vesting_duration)
+    }
+
     pub fn close_staking(ctx: Context<CloseStaking>) -> Result<()> {
         close_staking_handler(ctx)
     }
+
+    pub fn stake_compressed<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeCompressed<'info>>,
+        params: StakeCompressedParams
+    ) -> Result<()> {
+        stake_compressed_handler(ctx, params)
+    }
+
+    pub fn unstake_compressed<'info>(
+        ctx: Context<'_, '_, '_, 'info, UnstakeCompressed<'info>>,
+        root: [u8; 32],
+        data_hash: [u8; 32],
+        creator_hash: [u8; 32],
+        nonce: u64,
+        index: u32
+    ) -> Result<()> {
+        unstake_compressed_handler(ctx, root, data_hash, creator_hash, nonce, index)
+    }
+
+    pub fn stake_core(
+        ctx: Context<StakeCore>,
+        allowlist_proof: Vec<[u8; 32]>,
+        rarity: Option<RarityProof>,
+        lock_tier: Option<u8>
+    ) -> Result<()> {
+        stake_core_handler(ctx, allowlist_proof, rarity, lock_tier)
+    }
+
+    pub fn unstake_core<'info>(ctx: Context<'_, '_, '_, 'info, UnstakeCore<'info>>) -> Result<()> {
+        unstake_core_handler(ctx)
+    }
 }
 
 #[error_code]
@@ -97,4 +232,114 @@ pub enum StakeError {
     ProgramMulError,
     #[msg("unable to divide the given values")]
     ProgramDivError,
+    #[msg("the token record, sysvar or metadata program account is missing for the pNFT")]
+    PnftAccountMissing,
+    #[msg("unable to build the token metadata instruction for the pNFT")]
+    PnftInstructionError,
+    #[msg("the nft custody account is required for custodial staking")]
+    CustodyAccountMissing,
+    #[msg("the token metadata program account is missing")]
+    MetadataProgramMissing,
+    #[msg("unable to deserialize or hash the compressed NFT metadata")]
+    InvalidCompressedMetadata,
+    #[msg("the asset id doesn't match the nft record")]
+    InvalidAssetId,
+    #[msg("the mint is not a member of the token group")]
+    InvalidGroupMember,
+    #[msg("the metadata account is required to verify the collection")]
+    MetadataAccountMissing,
+    #[msg("token group collections can only be staked in custody")]
+    TokenGroupCustodialOnly,
+    #[msg("the core asset doesn't belong to the staker or the collection")]
+    InvalidCoreAsset,
+    #[msg("unable to calculate the transfer fee of the reward mint")]
+    TransferFeeError,
+    #[msg("the stake vault received less than the total emission")]
+    InsufficientDeposit,
+    #[msg("the reward multiplier must be greater than zero")]
+    InvalidMultiplier,
+    #[msg("the collection is already accepted by the staking")]
+    CollectionAlreadyAdded,
+    #[msg("max collection count reached")]
+    MaxCollectionsReached,
+    #[msg("the NFT has no verified collection or verified creator accepted by the staking")]
+    InvalidCollectionOrCreator,
+    #[msg("the mint is not in the allowlist of the staking")]
+    NotInAllowlist,
+    #[msg("the allowlist root is required to replace the collection check")]
+    AllowlistRootMissing,
+    #[msg("the rarity proof is required by the staking")]
+    RarityProofMissing,
+    #[msg("the rarity multiplier of the mint is not in the rarity tree")]
+    InvalidRarityProof,
+    #[msg("unable to get reward override bump")]
+    OverrideBumpError,
+    #[msg("unable to get staker account bump")]
+    StakerBumpError,
+    #[msg("the rarity multiplier exceeds the max rarity multiplier of the staking")]
+    RarityMultiplierTooHigh,
+    #[msg("the remaining accounts don't match the NFTs of the batch")]
+    InvalidBatchAccounts,
+    #[msg("pNFTs can't be staked in a batch")]
+    PnftBatchUnsupported,
+    #[msg("max NFTs per wallet reached")]
+    WalletLimitReached,
+    #[msg("the max NFTs per wallet must be greater than zero")]
+    InvalidWalletLimit,
+    #[msg("the lock tier doesn't exist in the staking")]
+    InvalidLockTier,
+    #[msg("the NFT is locked until its lock tier expires")]
+    NftLocked,
+    #[msg("max lock tier count exceeded")]
+    MaxLockTiersReached,
+    #[msg("the lock duration must be greater than zero")]
+    InvalidLockDuration,
+    #[msg("the unstake penalty can't exceed 100%")]
+    InvalidUnstakePenalty,
+    #[msg("the treasury token account is required by the penalty destination")]
+    TreasuryAccountMissing,
+    #[msg("the unstake cooldown can't be negative or exceed the max unstake cooldown")]
+    InvalidUnstakeCooldown,
+    #[msg("the NFT is unstaked and waiting for its cooldown to end")]
+    NftUnbonding,
+    #[msg("the NFT isn't unbonding")]
+    NftNotUnbonding,
+    #[msg("the unstake cooldown of the NFT isn't over")]
+    CooldownNotOver,
+    #[msg("the vesting duration can't be negative")]
+    InvalidVestingDuration,
+    #[msg("unable to get vesting escrow bump")]
+    VestingBumpError,
+    #[msg("max reward stream count exceeded")]
+    MaxRewardStreamsReached,
+    #[msg("the stream mint must differ from the reward mint")]
+    InvalidStreamMint,
+    #[msg("the remaining accounts don't match the reward streams of the staking")]
+    InvalidStreamAccounts,
+    #[msg("unable to get reward stream bump")]
+    StreamBumpError,
+    #[msg("the staking must be migrated to the reward accumulator")]
+    MigrationRequired,
+    #[msg("the account is already migrated")]
+    AlreadyMigrated,
+    #[msg("the NFT records of the staking must be migrated to the reward accumulator")]
+    RecordsNotMigrated,
+    #[msg("max scheduled reward count exceeded")]
+    MaxScheduledRewardsReached,
+    #[msg("the scheduled rewards must start in the future and in order of time")]
+    InvalidRewardSchedule,
+    #[msg("the exponential curve needs a positive period and a decay below 100%")]
+    InvalidEmissionCurve,
+    #[msg("the reward of the staking follows its emission curve")]
+    RewardFollowsCurve,
+    #[msg("compressed and Core NFTs can't be staked with an unstake cooldown")]
+    CooldownUnsupported,
+    #[msg("the unstake cooldown can't be raised while NFTs are staked")]
+    CooldownRaisedWhileStaked,
+    #[msg("the vesting escrow is required while the rewards vest")]
+    VestingEscrowMissing,
+    #[msg("the collection address must be a collection NFT mint, a Core collection or a creator wallet")]
+    InvalidCollectionAddress,
+    #[msg("the staking must be closed to refund its reward streams")]
+    StakingActive,
 }
\ No newline at end of file
@@ -1,5 +1,13 @@
 mod stake_details;
 mod nft_record;
+mod reward_override;
+mod staker_account;
+mod vesting_escrow;
+mod reward_stream;
 
 pub use stake_details::*;
 pub use nft_record::*;
+pub use reward_override::*;
+pub use staker_account::*;
+pub use vesting_escrow::*;
+pub use reward_stream::*;
@@ -1,4 +1,43 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{state::RewardOverride, MULTIPLIER_BASE, MAX_REWARD_STREAMS};
+
+/// The rarity multiplier of a mint and its proof against the rarity root
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RarityProof {
+    /// The rarity multiplier in basis points
+    pub multiplier: u64,
+    /// The merkle proof of the mint and multiplier leaf
+    pub proof: Vec<[u8; 32]>
+}
+
+/// The allowlist and rarity proofs of an NFT staked in a batch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NftProofs {
+    /// The merkle proof of the mint against the allowlist root
+    pub allowlist_proof: Vec<[u8; 32]>,
+    /// The rarity multiplier of the mint and its proof
+    pub rarity: Option<RarityProof>
+}
+
+/// The compressed NFT to stake and its proofs, passed to stake_compressed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StakeCompressedParams {
+    /// The root of the merkle tree the leaf is proven against
+    pub root: [u8; 32],
+    /// The nonce of the leaf, from which the asset id is derived
+    pub nonce: u64,
+    /// The index of the leaf in the merkle tree
+    pub index: u32,
+    /// The serialized metadata of the compressed NFT
+    pub metadata: Vec<u8>,
+    /// The merkle proof of the asset id against the allowlist root
+    pub allowlist_proof: Vec<[u8; 32]>,
+    /// The rarity multiplier of the asset and its proof
+    pub rarity: Option<RarityProof>,
+    /// The lock tier chosen by the staker
+    pub lock_tier: Option<u8>
+}
 
 #[account]
 pub struct NftRecord {
@@ -8,14 +47,147 @@ pub struct NftRecord {
     pub nft_mint: Pubkey,
     /// The staking timestamp (8)
     pub staked_at: i64,
+    /// The reward multiplier of the NFT in basis points (8)
+    pub multiplier: u64,
+    /// The reward accrued before the last override of the NFT and not yet paid (8)
+    pub accrued_reward: u64,
+    /// The time until which the NFT can't be unstaked, by its lock tier (8)
+    pub lock_expires_at: i64,
+    /// The time from which an unstaked NFT can be returned, none while the NFT is staked (9)
+    pub unbonding_ends_at: Option<i64>,
     /// The bump of NFT Record PDA (1)
-    pub bump: u8
+    pub bump: u8,
+    /// The reward per NFT of the staking when the reward of the NFT last started accruing (16)
+    pub reward_checkpoint: u128,
+    /// The reward per NFT of each reward stream when the stream reward of the NFT last started accruing,
+    /// in the order of the streams (4 + 16 * MAX_REWARD_STREAMS)
+    pub stream_checkpoints: Vec<u128>,
+    /// The reward of each reward stream accrued before the last override of the NFT and not yet paid,
+    /// in the order of the streams (4 + 8 * MAX_REWARD_STREAMS)
+    pub accrued_stream_rewards: Vec<u64>
 }
 
 impl NftRecord {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 9 + 1 + 16 + (4 + 16 * MAX_REWARD_STREAMS) + (4 + 8 * MAX_REWARD_STREAMS);
+
+    pub fn init(staker: Pubkey, nft_mint: Pubkey, staked_at: i64, multiplier: u64, lock_expires_at: i64, reward_checkpoint: u128, bump: u8) -> Self {
+        Self {staker, nft_mint, staked_at, multiplier, accrued_reward: 0, lock_expires_at, unbonding_ends_at: None, bump, reward_checkpoint, stream_checkpoints: vec![], accrued_stream_rewards: vec![]}
+    }
+
+    /// Whether the lock tier of the NFT still prevents unstaking
+    pub fn is_locked(&self, current_time: i64) -> bool {
+        current_time < self.lock_expires_at
+    }
+
+    /// Whether the NFT is unstaked and waiting for its cooldown to end
+    pub fn is_unbonding(&self) -> bool {
+        self.unbonding_ends_at.is_some()
+    }
+
+    /// Whether the cooldown of the unbonding NFT is over
+    pub fn is_unbonded(&self, current_time: i64) -> bool {
+        self.unbonding_ends_at.is_some_and(|unbonding_ends_at| current_time >= unbonding_ends_at)
+    }
+
+    /// The checkpoint of the NFT in a reward stream, a stream added after the NFT was staked accrues from its start
+    pub fn stream_checkpoint(&self, stream_index: usize) -> u128 {
+        self.stream_checkpoints.get(stream_index).copied().unwrap_or(0)
+    }
+
+    /// The reward of the NFT in a reward stream accrued before its last override
+    pub fn accrued_stream_reward(&self, stream_index: usize) -> u64 {
+        self.accrued_stream_rewards.get(stream_index).copied().unwrap_or(0)
+    }
+
+    /// The time from which the unpaid reward accrues and the multiplier it accrues at
+    pub fn accrual(&self, reward_override: Option<&RewardOverride>) -> (i64, u64) {
+        match reward_override {
+            Some(reward_override) => (self.staked_at.max(reward_override.effective_from), reward_override.multiplier),
+            None => (self.staked_at, self.multiplier)
+        }
+    }
+}
+
+/// The record of an NFT staked before the accumulator, as it was laid out then
+#[derive(AnchorDeserialize)]
+pub struct LegacyNftRecord {
+    /// The owner/staker of the NFT (32)
+    pub staker: Pubkey,
+    /// The mint of the staked NFT (32)
+    pub nft_mint: Pubkey,
+    /// The staking timestamp (8)
+    pub staked_at: i64,
+    /// The bump of NFT Record PDA (1)
+    pub bump: u8
+}
+
+impl LegacyNftRecord {
     pub const LEN: usize = 8 + 32 + 32 + 8 + 1;
 
-    pub fn init(staker: Pubkey, nft_mint: Pubkey, staked_at: i64, bump: u8) -> Self {
-        Self {staker, nft_mint, staked_at, bump}
+    /// Reads a record that still has the legacy layout, behind the discriminator it shares with the current one
+    pub fn try_from_data(data: &[u8]) -> Result<Self> {
+        require_eq!(data.len(), LegacyNftRecord::LEN, ErrorCode::AccountDidNotDeserialize);
+        require!(data[..8] == NftRecord::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        LegacyNftRecord::deserialize(&mut &data[8..]).map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))
+    }
+
+    /// The record in the current layout, with the base multiplier and no lock like every NFT staked before
+    pub fn migrate(&self, reward_checkpoint: u128) -> NftRecord {
+        NftRecord::init(self.staker, self.nft_mint, self.staked_at, MULTIPLIER_BASE, self.staked_at, reward_checkpoint, self.bump)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_data(staker: Pubkey, nft_mint: Pubkey, staked_at: i64, bump: u8) -> Vec<u8> {
+        let mut data = NftRecord::DISCRIMINATOR.to_vec();
+
+        data.extend_from_slice(staker.as_ref());
+        data.extend_from_slice(nft_mint.as_ref());
+        data.extend_from_slice(&staked_at.to_le_bytes());
+        data.push(bump);
+
+        data
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn migrates_legacy_layout() {
+        let staker = Pubkey::new_unique();
+        let nft_mint = Pubkey::new_unique();
+        let data = legacy_data(staker, nft_mint, 1_700_000_000, 254);
+
+        let legacy_record = LegacyNftRecord::try_from_data(&data).unwrap();
+        let nft_record = legacy_record.migrate(42);
+
+        // Written back and read in the current layout, the way the unstake instructions read it
+        let mut migrated_data = [0; NftRecord::LEN];
+        nft_record.try_serialize(&mut &mut migrated_data[..]).unwrap();
+        let nft_record = NftRecord::try_deserialize(&mut &migrated_data[..]).unwrap();
+
+        assert_eq!(nft_record.staker, staker);
+        assert_eq!(nft_record.nft_mint, nft_mint);
+        assert_eq!(nft_record.staked_at, 1_700_000_000);
+        assert_eq!(nft_record.multiplier, MULTIPLIER_BASE);
+        assert_eq!(nft_record.accrued_reward, 0);
+        assert!(!nft_record.is_locked(1_700_000_000));
+        assert!(!nft_record.is_unbonding());
+        assert_eq!(nft_record.bump, 254);
+        assert_eq!(nft_record.reward_checkpoint, 42);
+    }
+
+    #[test]
+    fn rejects_migrated_or_foreign_records() {
+        let data = legacy_data(Pubkey::new_unique(), Pubkey::new_unique(), 0, 255);
+
+        let mut migrated_data = data.clone();
+        migrated_data.resize(NftRecord::LEN, 0);
+        assert!(LegacyNftRecord::try_from_data(&migrated_data).is_err());
+
+        let mut foreign_data = data;
+        foreign_data[0] ^= 1;
+        assert!(LegacyNftRecord::try_from_data(&foreign_data).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::EmissionCurve, utils::{accumulate_reward, apply_multiplier, calc_total_emission}, StakeError};
+
+#[account]
+pub struct RewardStream {
+    /// The staking the stream belongs to (32)
+    pub stake_details: Pubkey,
+    /// The mint of the reward token paid by the stream (32)
+    pub reward_mint: Pubkey,
+    /// The reward accrued by an NFT with the base multiplier from the start of the stream until its last reward change (16)
+    pub reward_per_nft: u128,
+    /// The current reward emission of the stream per NFT per second (8)
+    pub current_reward: u64,
+    /// The time of the last reward change of the stream, its start until the reward is changed (8)
+    pub last_reward_change: i64,
+    /// The funds of the stream vault not committed to the emission (8)
+    pub current_balance: u64,
+    /// The time until which the emission of the current reward is committed, the stream accrues until then (8)
+    pub emission_ends_at: i64,
+    /// The bump of the reward stream PDA (1)
+    pub bump: u8
+}
+
+impl RewardStream {
+    pub const LEN: usize = 8 + 32 + 32 + 16 + 8 + 8 + 8 + 8 + 1;
+
+    pub fn init(
+        stake_details: Pubkey,
+        reward_mint: Pubkey,
+        reward: u64,
+        starts_at: i64,
+        emission_ends_at: i64,
+        current_balance: u64,
+        bump: u8
+    ) -> Self {
+        Self {
+            stake_details,
+            reward_mint,
+            reward_per_nft: 0,
+            current_reward: reward,
+            last_reward_change: starts_at,
+            current_balance,
+            emission_ends_at,
+            bump
+        }
+    }
+
+    /// The reward accrued by an NFT with the base multiplier from the start of the stream until the given time,
+    /// nothing accrues after the staking ends or past the time the emission of the stream is funded until
+    pub fn reward_per_nft_at(&self, time: i64, staking_ends_at: i64) -> Result<u128> {
+        let accrual_ends_at = staking_ends_at.min(self.emission_ends_at);
+
+        accumulate_reward(self.reward_per_nft, self.current_reward, self.last_reward_change, time, accrual_ends_at)
+    }
+
+    /// The reward of an NFT since its checkpoint in the stream
+    pub fn reward_of(&self, reward_checkpoint: u128, current_time: i64, staking_ends_at: i64, multiplier: u64) -> Result<u64> {
+        let reward_per_nft = self.reward_per_nft_at(current_time, staking_ends_at)?
+            .checked_sub(reward_checkpoint)
+            .ok_or(StakeError::ProgramSubError)?;
+
+        let reward_tokens = match u64::try_from(reward_per_nft) {
+            Ok(reward) => reward,
+            _ => return err!(StakeError::ProgramMulError)
+        };
+
+        apply_multiplier(reward_tokens, multiplier)
+    }
+
+    /// Checkpoints the accumulator of the stream at the new reward,
+    /// replacing the committed emission of the current reward with the one of the new reward
+    pub fn change_reward(
+        &mut self,
+        new_reward: u64,
+        current_time: i64,
+        staking_ends_at: i64,
+        max_stakers_count: u64,
+        max_nft_multiplier: u64
+    ) -> Result<()> {
+        // The emission committed from now is released, nothing is left of it after the committed end
+        let released_emission = calc_total_emission(
+            self.current_reward,
+            &EmissionCurve::Constant,
+            max_stakers_count,
+            current_time.min(self.emission_ends_at),
+            self.emission_ends_at,
+            max_nft_multiplier
+        )?;
+
+        let new_emission = calc_total_emission(
+            new_reward,
+            &EmissionCurve::Constant,
+            max_stakers_count,
+            current_time,
+            staking_ends_at,
+            max_nft_multiplier
+        )?;
+
+        let available_balance = self.current_balance
+            .checked_add(released_emission)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        require_gte!(available_balance, new_emission, StakeError::InsufficientBalInVault);
+
+        self.reward_per_nft = self.reward_per_nft_at(current_time, staking_ends_at)?;
+        self.current_reward = new_reward;
+        self.last_reward_change = current_time.max(self.last_reward_change);
+
+        self.current_balance = available_balance - new_emission;
+        self.emission_ends_at = staking_ends_at;
+
+        Ok(())
+    }
+
+    /// Releases the funds no NFT can earn once the staking is over, the uncommitted funds
+    /// and the emission committed past the end of the staking
+    pub fn release_unearned(&mut self, staking_ends_at: i64, max_stakers_count: u64, max_nft_multiplier: u64) -> Result<u64> {
+        // The current reward is committed from its change, nothing accrues after the staking ends
+        let released_from = staking_ends_at.min(self.emission_ends_at).max(self.last_reward_change);
+
+        let released_emission = calc_total_emission(
+            self.current_reward,
+            &EmissionCurve::Constant,
+            max_stakers_count,
+            released_from,
+            self.emission_ends_at,
+            max_nft_multiplier
+        )?;
+
+        let unearned = self.current_balance
+            .checked_add(released_emission)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        self.current_balance = 0;
+        self.emission_ends_at = released_from;
+
+        Ok(unearned)
+    }
+
+    pub fn increase_current_balance(&mut self, added_funds: u64) -> Result<()> {
+        self.current_balance = self.current_balance
+            .checked_add(added_funds)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MULTIPLIER_BASE;
+
+    #[test]
+    fn accrues_across_reward_changes() {
+        let mut reward_stream = RewardStream::init(Pubkey::new_unique(), Pubkey::new_unique(), 5, 100, 1_000, u64::MAX / 2, 255);
+
+        // Nothing accrues before the stream starts
+        assert_eq!(reward_stream.reward_per_nft_at(50, 1_000).unwrap(), 0);
+
+        // An NFT staked at 150 has the reward per NFT of then as its checkpoint
+        let checkpoint = reward_stream.reward_per_nft_at(150, 1_000).unwrap();
+        assert_eq!(checkpoint, 250);
+
+        reward_stream.change_reward(2, 300, 1_000, 10, MULTIPLIER_BASE).unwrap();
+        reward_stream.change_reward(7, 400, 1_000, 10, MULTIPLIER_BASE).unwrap();
+
+        // 150 seconds at 5, 100 at 2 and 50 at 7, at twice the base multiplier
+        let reward = reward_stream.reward_of(checkpoint, 450, 1_000, 2 * MULTIPLIER_BASE).unwrap();
+        assert_eq!(reward, 2 * (150 * 5 + 100 * 2 + 50 * 7));
+
+        // Nothing accrues after the staking ends
+        let reward = reward_stream.reward_of(checkpoint, 5_000, 1_000, MULTIPLIER_BASE).unwrap();
+        assert_eq!(reward, 150 * 5 + 100 * 2 + 600 * 7);
+
+        // A checkpoint ahead of the accumulator can't happen, and fails rather than paying
+        assert!(reward_stream.reward_of(u128::MAX, 450, 1_000, MULTIPLIER_BASE).is_err());
+    }
+
+    #[test]
+    fn accrues_until_the_funded_end() {
+        let mut reward_stream = RewardStream::init(Pubkey::new_unique(), Pubkey::new_unique(), 5, 100, 1_000, 0, 255);
+
+        // Extending the staking to 2_000 doesn't extend the emission funded until 1_000
+        assert_eq!(reward_stream.reward_per_nft_at(1_500, 2_000).unwrap(), 900 * 5);
+
+        // Funding the rest of the staking resumes the stream from then
+        assert!(reward_stream.change_reward(5, 1_500, 2_000, 10, MULTIPLIER_BASE).is_err());
+        reward_stream.increase_current_balance(500 * 5 * 10).unwrap();
+        reward_stream.change_reward(5, 1_500, 2_000, 10, MULTIPLIER_BASE).unwrap();
+
+        assert_eq!(reward_stream.current_balance, 0);
+        assert_eq!(reward_stream.reward_per_nft_at(2_500, 2_000).unwrap(), 900 * 5 + 500 * 5);
+    }
+
+    #[test]
+    fn releases_the_emission_after_the_staking_ends() {
+        let mut reward_stream = RewardStream::init(Pubkey::new_unique(), Pubkey::new_unique(), 1, 0, 1_000, 50, 255);
+
+        // Closed at 400, the emission committed for the last 600 seconds can't be earned
+        assert_eq!(reward_stream.release_unearned(400, 10, MULTIPLIER_BASE).unwrap(), 50 + 600 * 10);
+        assert_eq!(reward_stream.release_unearned(400, 10, MULTIPLIER_BASE).unwrap(), 0);
+
+        assert_eq!(reward_stream.reward_per_nft_at(1_000, 400).unwrap(), 400);
+    }
+}
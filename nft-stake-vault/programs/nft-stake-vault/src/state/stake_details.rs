@@ -1,6 +1,121 @@
 use anchor_lang::prelude::*;
+use anchor_spl::metadata::MetadataAccount;
 
-use crate::{StakeError, WEIGHT};
+use crate::{
+    state::RarityProof,
+    utils::{accumulate_reward, apply_multiplier, curve_reward_per_nft, mint_leaf, rarity_leaf, verify_proof},
+    StakeError, MULTIPLIER_BASE, MAX_COLLECTIONS, MAX_LOCK_TIERS, MAX_REWARD_STREAMS, MAX_SCHEDULED_REWARDS
+};
+
+/// Where the collection membership of the staked NFTs is verified
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionSource {
+    /// The verified collection of the Metaplex metadata
+    Metadata,
+    /// The group member extension of the Token-2022 mint
+    TokenGroup,
+    /// The collection of the Metaplex Core asset
+    Core,
+    /// A verified creator of the Metaplex metadata, for collections without a verified collection
+    Creator
+}
+
+impl CollectionSource {
+    /// Whether the NFTs are verified through their Metaplex metadata
+    pub fn uses_metadata(&self) -> bool {
+        matches!(self, CollectionSource::Metadata | CollectionSource::Creator)
+    }
+}
+
+/// Where the reward forfeited by unstaking early goes
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PenaltyDestination {
+    /// Back to the balance of the stake vault
+    Vault,
+    /// Burned from the stake vault
+    Burn,
+    /// Transferred to the treasury token account of the staking
+    Treasury
+}
+
+/// The curve the reward emission follows from the start of the staking
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum EmissionCurve {
+    /// The reward stays the same, unless changed or scheduled by the creator
+    Constant,
+    /// The reward ramps linearly from the initial reward to the end reward until the staking ends,
+    /// then stays there if the staking is extended
+    Linear {
+        end_reward: u64
+    },
+    /// The reward decays continuously by a share in basis points every period, compounding
+    Exponential {
+        decay: u64,
+        period: i64
+    }
+}
+
+impl EmissionCurve {
+    pub const LEN: usize = 1 + 8 + 8;
+}
+
+/// An additional collection accepted by the staking
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CollectionRate {
+    /// The verified collection or creator address (32)
+    pub collection: Pubkey,
+    /// The reward multiplier of the collection in basis points (8)
+    pub multiplier: u64
+}
+
+impl CollectionRate {
+    pub const LEN: usize = 32 + 8;
+}
+
+/// A lock-up period the staker can choose at stake time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LockTier {
+    /// The period for which the NFT can't be unstaked - in seconds (8)
+    pub duration: i64,
+    /// The reward multiplier of the tier in basis points (8)
+    pub multiplier: u64
+}
+
+impl LockTier {
+    pub const LEN: usize = 8 + 8;
+}
+
+/// A future change of the reward emission, registered ahead by the creator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledReward {
+    /// The time from which the reward applies (8)
+    pub starts_at: i64,
+    /// The reward emission per NFT per second from then on (8)
+    pub reward: u64
+}
+
+impl ScheduledReward {
+    pub const LEN: usize = 8 + 8;
+}
+
+/// The settings of a new staking, passed to init_staking
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct InitStakingParams {
+    /// The initial reward emission per NFT per second
+    pub reward: u64,
+    /// The period an NFT has to be staked for to earn its reward - in seconds
+    pub minimum_period: i64,
+    /// The time from which NFTs can be staked
+    pub staking_starts_at: i64,
+    /// The time at which the reward emission ends
+    pub staking_ends_at: i64,
+    /// The max number of NFTs that can be staked
+    pub max_stakers_count: u64,
+    /// Whether the NFTs stay frozen in the wallet of the staker instead of moving to custody
+    pub non_custodial: bool,
+    /// The curve of the reward emission, starting at the initial reward
+    pub emission_curve: EmissionCurve
+}
 
 #[account]
 pub struct Details {
@@ -10,18 +125,18 @@ pub struct Details {
     pub creator: Pubkey,
     /// The mint of the token to be given as reward (32)
     pub reward_mint: Pubkey,
-    /// The record of the current and prev reward emissions
+    /// The record of the reward emissions before the accumulator, kept to migrate the NFT records
     pub reward: Vec<u64>,
-    /// the record of the time when reward emission changed
+    /// the record of the time when reward emission changed before the accumulator
     pub reward_change_time: Vec<i64>,
-    /// The verified collection address of the NFT (32)
+    /// The verified collection or creator address of the NFT (32)
     pub collection: Pubkey,
     /// The max number of NFTs that can be staked (8)
     pub max_stakers_count: u64,
     /// The current number of NFTs staked (8)
     pub current_stakers_count: u64,
-    /// Accrued weight of the staked NFTs (16)
-    pub staked_weight: u128, 
+    /// The sum of the reward checkpoints of the staked NFTs, each scaled by its multiplier (16)
+    pub staked_checkpoints: u128,
     /// The starting time of the staking (8)
     pub staking_starts_at: i64,
     /// The period for which staking is funded (8)
@@ -34,27 +149,91 @@ pub struct Details {
     pub token_auth_bump: u8,
     /// The bump of the nft authority PDA (1)
     pub nft_auth_bump: u8,
-    /// The current balance in Stake Vault (8)
-    pub current_balance: u64
+    /// The balance in Stake Vault held for the staking, including the rewards owed to the stakers (8)
+    pub current_balance: u64,
+    /// Whether the NFTs stay frozen in the staker's wallet instead of custody (1)
+    pub non_custodial: bool,
+    /// The source of the collection verification (1)
+    pub collection_source: CollectionSource,
+    /// The additional collections and their reward multipliers (4 + 40 * MAX_COLLECTIONS)
+    pub collections: Vec<CollectionRate>,
+    /// The sum of the reward multipliers of the staked NFTs (8)
+    pub staked_multiplier: u64,
+    /// The highest reward multiplier of the accepted collections (8)
+    pub max_multiplier: u64,
+    /// The merkle root of the mints that can be staked, if the staking has an allowlist (1 + 32)
+    pub allowlist_root: Option<[u8; 32]>,
+    /// Whether the allowlist replaces the collection check, except for Core assets (1)
+    pub allowlist_only: bool,
+    /// The merkle root of the rarity multipliers of the mints, if the staking has one (1 + 32)
+    pub rarity_root: Option<[u8; 32]>,
+    /// The highest rarity multiplier in the rarity tree (8)
+    pub max_rarity_multiplier: u64,
+    /// The highest multiplier set by a reward override (8)
+    pub max_override_multiplier: u64,
+    /// The max number of NFTs a wallet can stake, if the staking has a limit (1 + 8)
+    pub max_per_wallet: Option<u64>,
+    /// The lock tiers the staker can choose from (4 + 16 * MAX_LOCK_TIERS)
+    pub lock_tiers: Vec<LockTier>,
+    /// The highest multiplier of the lock tiers, including previous ones (8)
+    pub max_lock_multiplier: u64,
+    /// The share of the reward forfeited by unstaking right after staking in basis points,
+    /// decaying linearly until the minimum period ends, the whole reward is forfeited if zero (8)
+    pub unstake_penalty: u64,
+    /// Where the forfeited reward goes (1)
+    pub penalty_destination: PenaltyDestination,
+    /// The reward token account receiving the forfeited reward, for the treasury destination (32)
+    pub penalty_treasury: Pubkey,
+    /// The time an unstaked NFT waits in custody before it can be returned, unstaking is immediate if zero.
+    /// Compressed and Core NFTs have no cooldown, so they can't be staked in a staking with one (8)
+    pub unstake_cooldown: i64,
+    /// The time over which claimed rewards vest in the escrow of the staker, paid out directly if zero (8)
+    pub vesting_duration: i64,
+    /// The rewards held in the stake vault for the vesting escrows (8)
+    pub vesting_balance: u64,
+    /// The reward mints of the additional reward streams, paid along with the reward (4 + 32 * MAX_REWARD_STREAMS)
+    pub reward_streams: Vec<Pubkey>,
+    /// The reward accrued by an NFT with the base multiplier from the start until the last reward change (16)
+    pub reward_per_nft: u128,
+    /// The current reward emission per NFT per second, or the initial one of a curve (8)
+    pub current_reward: u64,
+    /// The time of the last reward change (8)
+    pub last_reward_change: i64,
+    /// Whether the rewards accrue through the accumulator, false for stakings created before it until migrated (1)
+    pub uses_accumulator: bool,
+    /// The sum of the rewards accrued by the staked NFTs before their last override and not yet paid (8)
+    pub unpaid_accrued_reward: u64,
+    /// The number of staked NFTs whose record is yet to be migrated to the accumulator (8)
+    pub unmigrated_records: u64,
+    /// The future changes of the reward emission in order of time, applied as they start (4 + 16 * MAX_SCHEDULED_REWARDS)
+    pub reward_schedule: Vec<ScheduledReward>,
+    /// The curve of the reward emission, starting at the initial reward (17)
+    pub emission_curve: EmissionCurve,
+    /// The end of the staking when it was created, which a linear curve ramps down to (8)
+    pub curve_ends_at: i64
 }
 
 impl Details {
-    pub const LEN: usize = 8 + 1 + 32 + 32 + 12 + 12 + 32 + 8 + 8 + 16 + 8 + 8 + 8 + 1 + 1 + 1 + 8;
+    pub const LEN: usize = 8 + 1 + 32 + 32 + 12 + 12 + 32 + 8 + 8 + 16 + 8 + 8 + 8 + 1 + 1 + 1 + 8 + 1 + 1 + (4 + CollectionRate::LEN * MAX_COLLECTIONS) + 8 + 8 + 33 + 1 + 33 + 8 + 8 + 9 + (4 + LockTier::LEN * MAX_LOCK_TIERS) + 8 + 8 + 1 + 32 + 8 + 8 + 8 + (4 + 32 * MAX_REWARD_STREAMS) + 16 + 8 + 8 + 1 + 8 + 8 + (4 + ScheduledReward::LEN * MAX_SCHEDULED_REWARDS) + EmissionCurve::LEN + 8;
 
+    /// A new staking, its bumps and balance are set by init_staking
     pub fn init(
+        params: InitStakingParams,
         creator: Pubkey,
         reward_mint: Pubkey,
         collection: Pubkey,
-        reward: u64,
-        max_stakers_count: u64,
-        staking_starts_at: i64,
-        staking_ends_at: i64,
-        minimum_period: i64,
-        stake_bump: u8,
-        token_auth_bump: u8,
-        nft_auth_bump: u8,
-        current_balance: u64
+        collection_source: CollectionSource
     ) -> Self {
+        let InitStakingParams {
+            reward,
+            minimum_period,
+            staking_starts_at,
+            staking_ends_at,
+            max_stakers_count,
+            non_custodial,
+            emission_curve
+        } = params;
+
         Self {
             is_active: true,
             creator,
@@ -63,69 +242,528 @@ impl Details {
             reward: vec![reward],
             reward_change_time: vec![staking_starts_at],
             max_stakers_count,
-            staked_weight: 0,
+            staked_checkpoints: 0,
             current_stakers_count: 0,
             staking_starts_at,
             staking_ends_at,
             minimum_period,
-            stake_bump,
-            token_auth_bump,
-            nft_auth_bump,
-            current_balance
+            stake_bump: 0,
+            token_auth_bump: 0,
+            nft_auth_bump: 0,
+            current_balance: 0,
+            non_custodial,
+            collection_source,
+            collections: vec![],
+            staked_multiplier: 0,
+            max_multiplier: MULTIPLIER_BASE,
+            allowlist_root: None,
+            allowlist_only: false,
+            rarity_root: None,
+            max_rarity_multiplier: MULTIPLIER_BASE,
+            max_override_multiplier: 0,
+            max_per_wallet: None,
+            lock_tiers: vec![],
+            max_lock_multiplier: MULTIPLIER_BASE,
+            unstake_penalty: 0,
+            penalty_destination: PenaltyDestination::Vault,
+            penalty_treasury: Pubkey::default(),
+            unstake_cooldown: 0,
+            vesting_duration: 0,
+            vesting_balance: 0,
+            reward_streams: vec![],
+            reward_per_nft: 0,
+            current_reward: reward,
+            last_reward_change: staking_starts_at,
+            uses_accumulator: true,
+            unpaid_accrued_reward: 0,
+            unmigrated_records: 0,
+            reward_schedule: vec![],
+            emission_curve,
+            curve_ends_at: staking_ends_at
         }
     }
 
+    /// The size of the account with its reward history, which no longer grows
     pub fn current_len(&self) -> usize {
         (Details::LEN - 16) + (self.reward.len() * 16)
     }
 
-    pub fn change_reward(&mut self, new_reward: u64, current_time: i64) {
-        self.reward.push(new_reward);
-        self.reward_change_time.push(current_time);
+    /// Checkpoints the accumulator and continues it at the new reward, until the next scheduled change
+    pub fn change_reward(&mut self, new_reward: u64, current_time: i64) -> Result<()> {
+        self.apply_reward_schedule(current_time)?;
+
+        self.reward_per_nft = self.reward_per_nft_at(current_time)?;
+        self.current_reward = new_reward;
+        self.last_reward_change = current_time.max(self.last_reward_change);
+
+        Ok(())
+    }
+
+    /// Moves the scheduled changes that started by the given time into the accumulator
+    pub fn apply_reward_schedule(&mut self, current_time: i64) -> Result<()> {
+        let started = self.reward_schedule.iter().take_while(|change| change.starts_at <= current_time).count();
+
+        for change in self.reward_schedule.drain(..started).collect::<Vec<ScheduledReward>>() {
+            self.reward_per_nft = self.accumulate(self.reward_per_nft, self.current_reward, self.last_reward_change, change.starts_at)?;
+            self.current_reward = change.reward;
+            self.last_reward_change = change.starts_at.max(self.last_reward_change);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the future changes of the reward, applying the ones already started first
+    pub fn set_reward_schedule(&mut self, reward_schedule: Vec<ScheduledReward>, current_time: i64) -> Result<()> {
+        self.apply_reward_schedule(current_time)?;
+        self.reward_schedule = reward_schedule;
+
+        Ok(())
+    }
+
+    /// Adds the reward accrued at the given rate between the two times, within the staking
+    fn accumulate(&self, reward_per_nft: u128, reward: u64, from: i64, to: i64) -> Result<u128> {
+        accumulate_reward(reward_per_nft, reward, from, to, self.staking_ends_at)
+    }
+
+    /// Checks that the creator can change the reward, which a curve sets for the whole staking
+    pub fn require_constant_curve(&self) -> Result<()> {
+        require!(self.emission_curve == EmissionCurve::Constant, StakeError::RewardFollowsCurve);
+
+        Ok(())
+    }
+
+    /// Checks that the staking accrues through the accumulator, since the current reward reads as zero before
+    pub fn require_migrated(&self) -> Result<()> {
+        require!(self.uses_accumulator, StakeError::MigrationRequired);
+
+        Ok(())
+    }
+
+    /// The reward accrued by an NFT with the base multiplier from the start of the staking until the given time,
+    /// following the scheduled changes not yet applied
+    pub fn reward_per_nft_at(&self, time: i64) -> Result<u128> {
+        self.require_migrated()?;
+
+        // The reward of a curve is integrated from the start of the staking, over the window it was created with
+        if self.emission_curve != EmissionCurve::Constant {
+            let elapsed = time
+                .min(self.staking_ends_at)
+                .checked_sub(self.staking_starts_at)
+                .ok_or(StakeError::ProgramSubError)?
+                .max(0);
+
+            let curve_duration = self.curve_ends_at
+                .checked_sub(self.staking_starts_at)
+                .ok_or(StakeError::ProgramSubError)?;
+
+            return curve_reward_per_nft(&self.emission_curve, self.current_reward, elapsed, curve_duration);
+        }
+
+        let mut reward_per_nft = self.reward_per_nft;
+        let mut reward = self.current_reward;
+        let mut last_change = self.last_reward_change;
+
+        for change in self.reward_schedule.iter().take_while(|change| change.starts_at < time) {
+            reward_per_nft = self.accumulate(reward_per_nft, reward, last_change, change.starts_at)?;
+            reward = change.reward;
+            last_change = change.starts_at.max(last_change);
+        }
+
+        self.accumulate(reward_per_nft, reward, last_change, time)
+    }
+
+    /// The reward of an NFT since its checkpoint, the current time and whether the NFT is eligible for it
+    pub fn accrued_reward(&self, staked_at: i64, reward_checkpoint: u128, multiplier: u64) -> Result<(u64, i64, bool)> {
+        let current_time = Clock::get().unwrap().unix_timestamp;
+
+        let reward_eligible_time = staked_at.checked_add(self.minimum_period).ok_or(StakeError::ProgramAddError)?;
+        let is_eligible_for_reward = current_time >= reward_eligible_time;
+
+        let reward_per_nft = self.reward_per_nft_at(current_time)?
+            .checked_sub(reward_checkpoint)
+            .ok_or(StakeError::ProgramSubError)?;
+
+        let reward_tokens = match u64::try_from(reward_per_nft) {
+            Ok(reward) => reward,
+            _ => return err!(StakeError::ProgramMulError)
+        };
+
+        let reward_tokens = apply_multiplier(reward_tokens, multiplier)?;
+
+        Ok((reward_tokens, current_time, is_eligible_for_reward))
+    }
+
+    /// The reward accrued by an NFT with the base multiplier until the given time by the reward history,
+    /// walking the changes only until the last one
+    pub fn history_reward_per_nft(&self, time: i64) -> Result<u128> {
+        let mut reward_per_nft: u128 = 0;
+        let total_changes = self.reward_change_time.len() - 1;
+
+        for ix in 0..total_changes {
+            let start = self.reward_change_time[ix];
+            let end = self.reward_change_time[ix + 1].min(time);
+
+            if end <= start {
+                break;
+            }
+
+            // directly converting to u128 since it can't be negative
+            let reward = (self.reward[ix] as u128)
+                .checked_mul((end - start) as u128)
+                .ok_or(StakeError::ProgramMulError)?;
+
+            reward_per_nft = reward_per_nft.checked_add(reward).ok_or(StakeError::ProgramAddError)?;
+        }
+
+        Ok(reward_per_nft)
+    }
+
+    /// Moves a staking created before the accumulator onto it, from its last reward change
+    pub fn migrate_to_accumulator(&mut self) -> Result<()> {
+        let last_reward_change = *self.reward_change_time.last().unwrap();
+
+        self.reward_per_nft = self.history_reward_per_nft(last_reward_change)?;
+        self.current_reward = *self.reward.last().unwrap();
+        self.last_reward_change = last_reward_change;
+        self.uses_accumulator = true;
+
+        // The liability and multipliers of the staked NFTs are added back as their records are migrated
+        self.staked_checkpoints = 0;
+        self.staked_multiplier = 0;
+        self.unpaid_accrued_reward = 0;
+        self.unmigrated_records = self.current_stakers_count;
+
+        // The multipliers added after the staking was created read as zero
+        self.max_multiplier = self.max_multiplier.max(MULTIPLIER_BASE);
+        self.max_rarity_multiplier = self.max_rarity_multiplier.max(MULTIPLIER_BASE);
+        self.max_lock_multiplier = self.max_lock_multiplier.max(MULTIPLIER_BASE);
+
+        Ok(())
+    }
+
+    /// The checkpoint of an NFT record created before the accumulator, by the time its reward started accruing
+    pub fn migrated_checkpoint(&self, accrual_start: i64) -> Result<u128> {
+        if accrual_start >= self.last_reward_change {
+            self.reward_per_nft_at(accrual_start)
+        } else {
+            self.history_reward_per_nft(accrual_start)
+        }
     }
 
     pub fn extend_staking(&mut self, new_end_time: i64) {
         self.staking_ends_at = new_end_time;
     }
 
-    pub fn update_staked_weight(&mut self, stake_time: i64, increase_weight: bool) -> Result<()> {
-        let last_reward_time = *self.reward_change_time.last().unwrap();
+    /// The reward multiplier of the collection, if the staking accepts it
+    pub fn collection_multiplier(&self, collection: &Pubkey) -> Option<u64> {
+        if *collection == self.collection {
+            return Some(MULTIPLIER_BASE);
+        }
+
+        self.collections
+            .iter()
+            .find(|rate| rate.collection == *collection)
+            .map(|rate| rate.multiplier)
+    }
+
+    /// The reward multiplier of an NFT by its verified collection, or else by its first accepted verified creator
+    /// if the staking is of a creator's NFTs
+    pub fn metadata_multiplier(&self, verified_collection: Option<Pubkey>, verified_creators: &[Pubkey]) -> Option<u64> {
+        let verified_creators = match self.collection_source {
+            CollectionSource::Creator => verified_creators,
+            _ => &[]
+        };
 
-        let base = self.staking_ends_at
-            .checked_sub(last_reward_time)
-            .ok_or(StakeError::ProgramSubError)? as u128; // directly converting to u128 since it can't be negative
+        verified_collection
+            .and_then(|collection| self.collection_multiplier(&collection))
+            .or_else(|| verified_creators.iter().find_map(|creator| self.collection_multiplier(creator)))
+    }
 
-        let weight_time = stake_time.max(last_reward_time);
+    /// The reward multiplier of an NFT by the verified collection and creators of its metadata
+    pub fn verified_metadata_multiplier(&self, metadata: &MetadataAccount) -> Option<u64> {
+        let verified_collection = metadata.collection
+            .as_ref()
+            .filter(|collection| collection.verified)
+            .map(|collection| collection.key);
 
-        let mut num = self.staking_ends_at
-            .checked_sub(weight_time)
-            .ok_or(StakeError::ProgramSubError)? as u128; // directly converting to u128 since it can't be negative
+        let verified_creators: Vec<Pubkey> = metadata.data.creators
+            .iter()
+            .flatten()
+            .filter(|creator| creator.verified)
+            .map(|creator| creator.address)
+            .collect();
 
-        num = num.checked_mul(WEIGHT).ok_or(StakeError::ProgramMulError)?;
-        
-        let weight = num.checked_div(base).ok_or(StakeError::ProgramDivError)?;
+        self.metadata_multiplier(verified_collection, &verified_creators)
+    }
+
+    /// Checks that the mint is in the allowlist, if the staking has one
+    pub fn verify_allowlist(&self, mint: &Pubkey, proof: &[[u8; 32]]) -> Result<()> {
+        if let Some(root) = self.allowlist_root {
+            require!(verify_proof(proof, root, mint_leaf(mint)), StakeError::NotInAllowlist);
+        }
+
+        Ok(())
+    }
+
+    /// The rarity multiplier of the mint, proven against the rarity root if the staking has one
+    pub fn rarity_multiplier(&self, mint: &Pubkey, rarity: Option<RarityProof>) -> Result<u64> {
+        let root = match self.rarity_root {
+            Some(root) => root,
+            None => return Ok(MULTIPLIER_BASE)
+        };
+
+        let RarityProof { multiplier, proof } = rarity.ok_or(StakeError::RarityProofMissing)?;
+
+        require!(verify_proof(&proof, root, rarity_leaf(mint, multiplier)), StakeError::InvalidRarityProof);
+        require_gt!(multiplier, 0, StakeError::InvalidMultiplier);
+        require_gte!(self.max_rarity_multiplier, multiplier, StakeError::RarityMultiplierTooHigh);
+
+        Ok(multiplier)
+    }
+
+    /// The highest reward multiplier an NFT can have, by its collection, rarity and lock tier or an override
+    pub fn max_nft_multiplier(&self) -> Result<u64> {
+        let max_multiplier = apply_multiplier(self.max_multiplier, self.max_rarity_multiplier)?;
+        let max_multiplier = apply_multiplier(max_multiplier, self.max_lock_multiplier)?;
+
+        Ok(max_multiplier.max(self.max_override_multiplier))
+    }
+
+    /// Checks that the vault covers the emission until the staking ends, 
+    /// if every NFT is staked with the given multiplier from now
+    pub fn require_emission_covered(&self, max_nft_multiplier: u64, current_time: i64) -> Result<()> {
+        let uncommitted_balance = self.uncommitted_balance(current_time)?;
+        let new_emission = self.future_emission(max_nft_multiplier, current_time)?;
 
-        if increase_weight {
-            self.staked_weight = self.staked_weight.checked_add(weight).ok_or(StakeError::ProgramAddError)?;
+        require_gte!(uncommitted_balance, new_emission, StakeError::InsufficientBalInVault);
+
+        Ok(())
+    }
+
+    /// The emission from now until the staking ends by the reward schedule,
+    /// if every NFT is staked with the given multiplier
+    pub fn future_emission(&self, max_nft_multiplier: u64, current_time: i64) -> Result<u64> {
+        let future_reward_per_nft = self.reward_per_nft_at(self.staking_ends_at)?
+            .checked_sub(self.reward_per_nft_at(current_time)?)
+            .ok_or(StakeError::ProgramSubError)?
+            .checked_mul(self.max_stakers_count as u128)
+            .ok_or(StakeError::ProgramMulError)?;
+
+        let total_emission = match u64::try_from(future_reward_per_nft) {
+            Ok(emission) => emission,
+            _ => return err!(StakeError::ProgramMulError)
+        };
+
+        // Every NFT could be staked from the collection with the highest multiplier
+        apply_multiplier(total_emission, max_nft_multiplier)
+    }
+
+    /// The reward owed to the stakers and not yet paid, rounded up so it covers the reward of every NFT
+    pub fn liability(&self, current_time: i64) -> Result<u64> {
+        require_eq!(self.unmigrated_records, 0, StakeError::RecordsNotMigrated);
+
+        let scaled_reward = self.reward_per_nft_at(current_time)?
+            .checked_mul(self.staked_multiplier as u128)
+            .ok_or(StakeError::ProgramMulError)?
+            .checked_sub(self.staked_checkpoints)
+            .ok_or(StakeError::ProgramSubError)?;
+
+        let base = MULTIPLIER_BASE as u128;
+
+        let accrued_reward = scaled_reward
+            .checked_add(base - 1)
+            .ok_or(StakeError::ProgramAddError)?
+            / base;
+
+        let accrued_reward = match u64::try_from(accrued_reward) {
+            Ok(reward) => reward,
+            _ => return err!(StakeError::ProgramMulError)
+        };
+
+        Ok(accrued_reward.checked_add(self.unpaid_accrued_reward).ok_or(StakeError::ProgramAddError)?)
+    }
+
+    /// The balance of the stake vault not owed to the stakers yet
+    pub fn uncommitted_balance(&self, current_time: i64) -> Result<u64> {
+        let liability = self.liability(current_time)?;
+
+        Ok(self.current_balance.checked_sub(liability).ok_or(StakeError::InsufficientBalInVault)?)
+    }
+
+    /// Replaces the rarity root, the max rarity multiplier can only grow since staked NFTs keep theirs
+    pub fn set_rarity(&mut self, root: Option<[u8; 32]>, max_rarity_multiplier: u64) {
+        self.rarity_root = root;
+        self.max_rarity_multiplier = self.max_rarity_multiplier.max(max_rarity_multiplier);
+    }
+
+    /// The reward multiplier and lock expiry of the chosen lock tier, locks end with the staking at the latest
+    pub fn lock_tier(&self, lock_tier: Option<u8>, staked_at: i64) -> Result<(u64, i64)> {
+        let tier = match lock_tier {
+            Some(index) => self.lock_tiers.get(index as usize).ok_or(StakeError::InvalidLockTier)?,
+            None => return Ok((MULTIPLIER_BASE, staked_at))
+        };
+
+        let lock_expires_at = staked_at
+            .checked_add(tier.duration)
+            .ok_or(StakeError::ProgramAddError)?
+            .min(self.staking_ends_at);
+
+        Ok((tier.multiplier, lock_expires_at))
+    }
+
+    /// The part of the reward forfeited by unstaking before the minimum period ends
+    pub fn unstake_forfeit(&self, staked_at: i64, current_time: i64, reward: u64) -> Result<u64> {
+        let staked_for = current_time.checked_sub(staked_at).ok_or(StakeError::ProgramSubError)?;
+
+        if staked_for >= self.minimum_period {
+            return Ok(0);
+        }
+
+        if self.unstake_penalty == 0 {
+            return Ok(reward);
+        }
+
+        // directly converting to u128 since it can't be negative
+        let remaining_period = (self.minimum_period - staked_for) as u128;
+
+        let forfeit = (reward as u128)
+            .checked_mul(self.unstake_penalty as u128)
+            .ok_or(StakeError::ProgramMulError)?
+            .checked_mul(remaining_period)
+            .ok_or(StakeError::ProgramMulError)?
+            .checked_div((MULTIPLIER_BASE as u128) * (self.minimum_period as u128))
+            .ok_or(StakeError::ProgramDivError)?;
+
+        // The forfeit can't exceed the reward since the penalty is at most 100%
+        Ok(forfeit as u64)
+    }
+
+    pub fn set_unstake_penalty(&mut self, unstake_penalty: u64, penalty_destination: PenaltyDestination, penalty_treasury: Pubkey) {
+        self.unstake_penalty = unstake_penalty;
+        self.penalty_destination = penalty_destination;
+        self.penalty_treasury = penalty_treasury;
+    }
+
+    pub fn set_unstake_cooldown(&mut self, unstake_cooldown: i64) {
+        self.unstake_cooldown = unstake_cooldown;
+    }
+
+    pub fn add_reward_stream(&mut self, reward_mint: Pubkey) {
+        self.reward_streams.push(reward_mint);
+    }
+
+    pub fn set_vesting_duration(&mut self, vesting_duration: i64) {
+        self.vesting_duration = vesting_duration;
+    }
+
+    pub fn change_vesting_balance(&mut self, amount: u64, increase: bool) -> Result<()> {
+        self.vesting_balance = if increase {
+            self.vesting_balance.checked_add(amount).ok_or(StakeError::ProgramAddError)?
         } else {
-            self.staked_weight = self.staked_weight.checked_sub(weight).ok_or(StakeError::ProgramSubError)?;
+            self.vesting_balance.checked_sub(amount).ok_or(StakeError::ProgramSubError)?
+        };
+
+        Ok(())
+    }
+
+    /// Checks that the wallet stays within the per-wallet limit after staking more NFTs, if the staking has one
+    pub fn require_wallet_limit(&self, staked_count: u64, new_nfts: u64) -> Result<()> {
+        if let Some(max_per_wallet) = self.max_per_wallet {
+            let new_staked_count = staked_count.checked_add(new_nfts).ok_or(StakeError::ProgramAddError)?;
+            require_gte!(max_per_wallet, new_staked_count, StakeError::WalletLimitReached);
         }
 
         Ok(())
     }
 
-    pub fn increase_staker_count(&mut self) -> Result<()> {
+    /// Replaces the lock tiers, the max lock multiplier can only grow since staked NFTs keep theirs
+    pub fn set_lock_tiers(&mut self, lock_tiers: Vec<LockTier>) {
+        let max_lock_multiplier = lock_tiers.iter().map(|tier| tier.multiplier).max().unwrap_or(MULTIPLIER_BASE);
+
+        self.lock_tiers = lock_tiers;
+        self.max_lock_multiplier = self.max_lock_multiplier.max(max_lock_multiplier);
+    }
+
+    pub fn set_max_per_wallet(&mut self, max_per_wallet: Option<u64>) {
+        self.max_per_wallet = max_per_wallet;
+    }
+
+    pub fn set_allowlist(&mut self, root: Option<[u8; 32]>, allowlist_only: bool) {
+        self.allowlist_root = root;
+        self.allowlist_only = allowlist_only;
+    }
+
+    pub fn add_collection(&mut self, collection: Pubkey, multiplier: u64) {
+        self.collections.push(CollectionRate { collection, multiplier });
+        self.max_multiplier = self.max_multiplier.max(multiplier);
+    }
+
+    /// Adds or removes the reward checkpoint of staked NFTs, scaled by the sum of their multipliers
+    pub fn change_staked_checkpoints(&mut self, reward_checkpoint: u128, multiplier: u64, increase: bool) -> Result<()> {
+        let scaled_checkpoint = reward_checkpoint
+            .checked_mul(multiplier as u128)
+            .ok_or(StakeError::ProgramMulError)?;
+
+        self.staked_checkpoints = if increase {
+            self.staked_checkpoints.checked_add(scaled_checkpoint).ok_or(StakeError::ProgramAddError)?
+        } else {
+            self.staked_checkpoints.checked_sub(scaled_checkpoint).ok_or(StakeError::ProgramSubError)?
+        };
+
+        Ok(())
+    }
+
+    pub fn change_unpaid_accrued_reward(&mut self, amount: u64, increase: bool) -> Result<()> {
+        self.unpaid_accrued_reward = if increase {
+            self.unpaid_accrued_reward.checked_add(amount).ok_or(StakeError::ProgramAddError)?
+        } else {
+            self.unpaid_accrued_reward.checked_sub(amount).ok_or(StakeError::ProgramSubError)?
+        };
+
+        Ok(())
+    }
+
+    /// Counts a staked NFT whose record was migrated to the accumulator, it is already in the staker count
+    pub fn add_migrated_record(&mut self, reward_checkpoint: u128, multiplier: u64, accrued_reward: u64) -> Result<()> {
+        self.change_staked_checkpoints(reward_checkpoint, multiplier, true)?;
+        self.change_unpaid_accrued_reward(accrued_reward, true)?;
+
+        self.staked_multiplier = self.staked_multiplier
+            .checked_add(multiplier)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        self.unmigrated_records = self.unmigrated_records
+            .checked_sub(1)
+            .ok_or(StakeError::ProgramSubError)?;
+
+        Ok(())
+    }
+
+    pub fn increase_staker_count(&mut self, multiplier: u64) -> Result<()> {
+        self.increase_staker_count_by(1, multiplier)
+    }
+
+    /// Adds a batch of NFTs, the multiplier is the sum of their multipliers
+    pub fn increase_staker_count_by(&mut self, count: u64, multiplier: u64) -> Result<()> {
         self.current_stakers_count = self.current_stakers_count
-        .checked_add(1)
+        .checked_add(count)
+        .ok_or(StakeError::ProgramAddError)?;
+
+        self.staked_multiplier = self.staked_multiplier
+        .checked_add(multiplier)
         .ok_or(StakeError::ProgramAddError)?;
         
         Ok(())
     }
 
-    pub fn decrease_staker_count(&mut self) -> Result<()> {
+    pub fn decrease_staker_count(&mut self, multiplier: u64) -> Result<()> {
         self.current_stakers_count = self.current_stakers_count
         .checked_sub(1)
         .ok_or(StakeError::ProgramSubError)?;
+
+        self.staked_multiplier = self.staked_multiplier
+        .checked_sub(multiplier)
+        .ok_or(StakeError::ProgramSubError)?;
         
         Ok(())
     }
@@ -138,34 +776,397 @@ impl Details {
         Ok(())
     }
 
-    pub fn decrease_current_balance(&mut self, staked_at: i64, current_time: i64) -> Result<()> {
-        let last_reward_time = *self.reward_change_time.last().unwrap();
-        let last_reward = *self.reward.last().unwrap();
+    /// Debits the reward paid out of the stake vault
+    pub fn decrease_current_balance_by(&mut self, paid_reward: u64) -> Result<()> {
+        self.current_balance = self.current_balance
+            .checked_sub(paid_reward)
+            .ok_or(StakeError::ProgramSubError)?;
+        
+        Ok(())
+    }
 
-        let reward_time = staked_at.max(last_reward_time);
-        let cutoff_time = current_time.min(self.staking_ends_at);
+    /// Counts staked NFTs checkpointed at the same reward per NFT, the multiplier is the sum of their multipliers
+    pub fn add_staked_nfts(&mut self, reward_checkpoint: u128, count: u64, multiplier: u64) -> Result<()> {
+        self.change_staked_checkpoints(reward_checkpoint, multiplier, true)?;
+        self.increase_staker_count_by(count, multiplier)
+    }
 
-        let rewardable_time_since_change = cutoff_time
-            .checked_sub(reward_time)
-            .ok_or(StakeError::ProgramSubError)?;
+    /// Moves the checkpoint of an NFT whose reward was paid and debits the paid reward,
+    /// which includes the reward accrued before the last override of the NFT
+    pub fn settle_withdrawn_reward(
+        &mut self,
+        reward_checkpoint: u128,
+        new_checkpoint: u128,
+        multiplier: u64,
+        accrued_reward: u64,
+        paid_reward: u64
+    ) -> Result<()> {
+        self.change_staked_checkpoints(reward_checkpoint, multiplier, false)?;
+        self.change_staked_checkpoints(new_checkpoint, multiplier, true)?;
+        self.change_unpaid_accrued_reward(accrued_reward, false)?;
 
-        let rewardable_time_u64 = match u64::try_from(rewardable_time_since_change) {
-            Ok(time) => time,
-            _ => return err!(StakeError::FailedTimeConversion)
-        };
+        self.decrease_current_balance_by(paid_reward)
+    }
 
-        let reward_since_change = last_reward
-            .checked_mul(rewardable_time_u64)
-            .ok_or(StakeError::ProgramMulError)?;
+    /// Removes an unstaked NFT and debits its paid and forfeited reward,
+    /// a reward forfeited to the vault stays in the balance for the other stakers
+    pub fn remove_unstaked_nft(
+        &mut self,
+        reward_checkpoint: u128,
+        multiplier: u64,
+        accrued_reward: u64,
+        paid_reward: u64,
+        forfeited_reward: u64
+    ) -> Result<()> {
+        self.change_staked_checkpoints(reward_checkpoint, multiplier, false)?;
+        self.change_unpaid_accrued_reward(accrued_reward, false)?;
+        self.decrease_staker_count(multiplier)?;
+
+        let debited_reward = paid_reward.checked_add(forfeited_reward).ok_or(StakeError::ProgramAddError)?;
+        self.decrease_current_balance_by(debited_reward)?;
+
+        if self.penalty_destination == PenaltyDestination::Vault {
+            self.increase_current_balance(forfeited_reward)?;
+        }
 
-        self.current_balance = self.current_balance
-            .checked_sub(reward_since_change)
-            .ok_or(StakeError::ProgramSubError)?;
-        
         Ok(())
     }
 
     pub fn close_staking(&mut self) {
         self.is_active = false;
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STARTS_AT: i64 = 1_000;
+    const ENDS_AT: i64 = 101_000;
+
+    fn details(reward: u64) -> Details {
+        let params = InitStakingParams {
+            reward,
+            minimum_period: 0,
+            staking_starts_at: STARTS_AT,
+            staking_ends_at: ENDS_AT,
+            max_stakers_count: 10,
+            non_custodial: false,
+            emission_curve: EmissionCurve::Constant
+        };
+
+        Details::init(params, Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), CollectionSource::Metadata)
+    }
+
+    /// A staking created before the accumulator, with the fields added since reading as zero
+    /// but for stale checkpoints, as if its NFTs were counted from the last reward change
+    fn legacy_details(reward: Vec<u64>, reward_change_time: Vec<i64>, current_stakers_count: u64) -> Details {
+        let mut details = details(0);
+
+        details.reward = reward;
+        details.reward_change_time = reward_change_time;
+        details.current_stakers_count = current_stakers_count;
+
+        let last_reward_change = *details.reward_change_time.last().unwrap();
+        let stale_checkpoint = details.history_reward_per_nft(last_reward_change).unwrap();
+
+        details.staked_checkpoints = stale_checkpoint * (current_stakers_count * MULTIPLIER_BASE) as u128;
+        details.staked_multiplier = 0;
+        details.max_multiplier = 0;
+        details.max_rarity_multiplier = 0;
+        details.max_lock_multiplier = 0;
+        details.reward_per_nft = 0;
+        details.current_reward = 0;
+        details.last_reward_change = 0;
+        details.uses_accumulator = false;
+
+        details
+    }
+
+    #[test]
+    fn migrated_staking_owes_the_reward_of_its_records() {
+        let mut details = legacy_details(vec![2, 3], vec![STARTS_AT, STARTS_AT + 100], 2);
+
+        assert_ne!(details.staked_checkpoints, 0);
+
+        details.migrate_to_accumulator().unwrap();
+        assert_eq!(details.staked_checkpoints, 0);
+        assert_eq!(details.staked_multiplier, 0);
+        assert!(details.liability(STARTS_AT + 200).is_err());
+
+        // One NFT staked before the reward change and one after it
+        let first_checkpoint = details.migrated_checkpoint(STARTS_AT + 50).unwrap();
+        let second_checkpoint = details.migrated_checkpoint(STARTS_AT + 150).unwrap();
+        assert_eq!(first_checkpoint, 100);
+        assert_eq!(second_checkpoint, 200 + 150);
+
+        details.add_migrated_record(first_checkpoint, MULTIPLIER_BASE, 0).unwrap();
+        details.add_migrated_record(second_checkpoint, MULTIPLIER_BASE, 0).unwrap();
+        assert_eq!(details.staked_multiplier, 2 * MULTIPLIER_BASE);
+
+        // 50 seconds at 2 and 100 at 3 for the first NFT, 50 at 3 for the second
+        assert_eq!(details.liability(STARTS_AT + 200).unwrap(), 100 + 300 + 150);
+
+        // Unstaking the NFTs removes their checkpoints and multipliers
+        details.change_staked_checkpoints(first_checkpoint, MULTIPLIER_BASE, false).unwrap();
+        details.decrease_staker_count(MULTIPLIER_BASE).unwrap();
+        assert_eq!(details.liability(STARTS_AT + 200).unwrap(), 150);
+
+        details.change_staked_checkpoints(second_checkpoint, MULTIPLIER_BASE, false).unwrap();
+        details.decrease_staker_count(MULTIPLIER_BASE).unwrap();
+        assert_eq!(details.liability(STARTS_AT + 200).unwrap(), 0);
+        assert_eq!(details.current_stakers_count, 0);
+    }
+
+    #[test]
+    fn creators_are_read_only_for_a_creator_staking() {
+        let mut details = details(1);
+        let creator = details.collection;
+        let other_creator = Pubkey::new_unique();
+
+        // An NFT without a verified collection, made by the address of the staking
+        assert_eq!(details.metadata_multiplier(None, &[other_creator, creator]), None);
+        assert_eq!(details.metadata_multiplier(Some(creator), &[]), Some(MULTIPLIER_BASE));
+
+        details.collection_source = CollectionSource::Creator;
+        assert_eq!(details.metadata_multiplier(None, &[other_creator, creator]), Some(MULTIPLIER_BASE));
+        assert_eq!(details.metadata_multiplier(None, &[other_creator]), None);
+    }
+
+    /// A staked NFT as its record keeps it
+    struct StakedNft {
+        staked_at: i64,
+        checkpoint: u128,
+        multiplier: u64
+    }
+
+    /// The reward the staking pays for the NFT at the given time, rounded down
+    fn reward_of(details: &Details, nft: &StakedNft, time: i64) -> u64 {
+        let reward_per_nft = details.reward_per_nft_at(time).unwrap() - nft.checkpoint;
+
+        apply_multiplier(u64::try_from(reward_per_nft).unwrap(), nft.multiplier).unwrap()
+    }
+
+    /// The bookkeeping of the stake instructions
+    fn stake(details: &mut Details, multiplier: u64, time: i64) -> StakedNft {
+        let checkpoint = details.reward_per_nft_at(time).unwrap();
+
+        details.add_staked_nfts(checkpoint, 1, multiplier).unwrap();
+
+        StakedNft { staked_at: time, checkpoint, multiplier }
+    }
+
+    /// The bookkeeping of the withdraw reward instructions
+    fn withdraw(details: &mut Details, nft: &mut StakedNft, time: i64) -> u64 {
+        let reward = reward_of(details, nft, time);
+        let checkpoint = details.reward_per_nft_at(time).unwrap();
+
+        details.settle_withdrawn_reward(nft.checkpoint, checkpoint, nft.multiplier, 0, reward).unwrap();
+        nft.staked_at = time;
+        nft.checkpoint = checkpoint;
+
+        reward
+    }
+
+    /// The bookkeeping of the unstake instructions
+    fn unstake(details: &mut Details, nft: StakedNft, time: i64) -> u64 {
+        let reward = reward_of(details, &nft, time);
+        let forfeited_reward = details.unstake_forfeit(nft.staked_at, time, reward).unwrap();
+
+        details.remove_unstaked_nft(nft.checkpoint, nft.multiplier, 0, reward - forfeited_reward, forfeited_reward).unwrap();
+
+        reward - forfeited_reward
+    }
+
+    /// A xorshift generator, so every run replays the same sequences
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+
+            self.0 % bound
+        }
+    }
+
+    #[test]
+    fn reward_per_nft_follows_changes_and_schedule() {
+        let mut details = details(2);
+
+        assert_eq!(details.reward_per_nft_at(STARTS_AT - 50).unwrap(), 0);
+        assert_eq!(details.reward_per_nft_at(STARTS_AT + 100).unwrap(), 200);
+
+        details.change_reward(5, STARTS_AT + 100).unwrap();
+        details.set_reward_schedule(vec![
+            ScheduledReward { starts_at: STARTS_AT + 300, reward: 1 },
+            ScheduledReward { starts_at: ENDS_AT - 100, reward: 4 }
+        ], STARTS_AT + 100).unwrap();
+
+        // 100 seconds at 2, then 5 until the first scheduled change
+        assert_eq!(details.reward_per_nft_at(STARTS_AT + 200).unwrap(), 200 + 500);
+        assert_eq!(details.reward_per_nft_at(STARTS_AT + 300).unwrap(), 200 + 1_000);
+        assert_eq!(details.reward_per_nft_at(STARTS_AT + 400).unwrap(), 200 + 1_000 + 100);
+
+        // Nothing accrues after the staking ends
+        let at_end = 200 + 1_000 + (ENDS_AT - 100 - STARTS_AT - 300) as u128 + 400;
+        assert_eq!(details.reward_per_nft_at(ENDS_AT).unwrap(), at_end);
+        assert_eq!(details.reward_per_nft_at(ENDS_AT + 1_000).unwrap(), at_end);
+
+        // Applying the schedule moves it into the accumulator without changing the reward
+        details.apply_reward_schedule(STARTS_AT + 500).unwrap();
+        assert_eq!(details.reward_schedule.len(), 1);
+        assert_eq!(details.current_reward, 1);
+        assert_eq!(details.reward_per_nft_at(ENDS_AT).unwrap(), at_end);
+    }
+
+    #[test]
+    fn curve_accrues_from_the_start_until_the_end() {
+        let mut details = details(1_000);
+        details.emission_curve = EmissionCurve::Exponential { decay: 1_000, period: 10_000 };
+
+        let total_time = ENDS_AT - STARTS_AT;
+        let at_end = curve_reward_per_nft(&details.emission_curve, 1_000, total_time, total_time).unwrap();
+
+        assert_eq!(details.reward_per_nft_at(STARTS_AT - 500).unwrap(), 0);
+        assert_eq!(details.reward_per_nft_at(STARTS_AT).unwrap(), 0);
+
+        // Halfway through the second period, 1_000 * 10_000 * (1 - 0.9^1.5) / ln(1 / 0.9)
+        assert_eq!(details.reward_per_nft_at(STARTS_AT + 15_000).unwrap() / 1_000, 13_874);
+
+        assert_eq!(details.reward_per_nft_at(ENDS_AT).unwrap(), at_end);
+        assert_eq!(details.reward_per_nft_at(ENDS_AT + 50_000).unwrap(), at_end);
+
+        // The curve sets the reward for the whole staking
+        assert!(details.require_constant_curve().is_err());
+    }
+
+    #[test]
+    fn reward_change_matches_the_step_sum() {
+        let mut details = details(7);
+        let change_time = STARTS_AT + 1_234;
+
+        // The rate of every second, from the start of the staking until after its end
+        let step_sum = |time: i64| -> u128 {
+            (STARTS_AT..time.min(ENDS_AT))
+                .map(|second| if second < change_time { 7 } else { 3 })
+                .sum()
+        };
+
+        for time in [STARTS_AT, STARTS_AT + 1, change_time - 1, change_time] {
+            assert_eq!(details.reward_per_nft_at(time).unwrap(), step_sum(time), "at {}", time);
+        }
+
+        // The accumulator is read from its last change on
+        details.change_reward(3, change_time).unwrap();
+
+        for time in [change_time, change_time + 1, ENDS_AT - 1, ENDS_AT, ENDS_AT + 10] {
+            assert_eq!(details.reward_per_nft_at(time).unwrap(), step_sum(time), "at {}", time);
+        }
+    }
+
+    #[test]
+    fn liability_is_the_reward_since_the_checkpoints() {
+        let mut details = details(3);
+
+        let first = stake(&mut details, MULTIPLIER_BASE, STARTS_AT + 10);
+        let second = stake(&mut details, 15_000, STARTS_AT + 25);
+        assert_eq!(details.liability(STARTS_AT + 25).unwrap(), 45);
+
+        // 3 * 41 at the base multiplier and 3 * 26 * 1.5, which rounds up
+        let time = STARTS_AT + 51;
+        assert_eq!(reward_of(&details, &first, time), 123);
+        assert_eq!(reward_of(&details, &second, time), 117);
+        assert_eq!(details.liability(time).unwrap(), 123 + 117);
+
+        let third = stake(&mut details, 7_501, time);
+        assert_eq!(details.liability(time).unwrap(), 123 + 117);
+        assert_eq!(reward_of(&details, &third, time + 1), 2);
+
+        // 126 + 121.5 + 2.2503 rounds up as a whole, it covers the reward of every NFT
+        assert_eq!(details.liability(time + 1).unwrap(), 250);
+
+        // The accrued reward kept in a record is owed on top of the accumulator
+        details.change_unpaid_accrued_reward(7, true).unwrap();
+        assert_eq!(details.liability(time + 1).unwrap(), 250 + 7);
+
+        details.change_unpaid_accrued_reward(7, false).unwrap();
+        details.current_balance = 1_000;
+        assert_eq!(unstake(&mut details, first, time + 1), 126);
+        assert_eq!(unstake(&mut details, second, time + 1), 121);
+        assert_eq!(unstake(&mut details, third, time + 1), 2);
+        assert_eq!(details.liability(time + 1).unwrap(), 0);
+        assert_eq!(details.staked_checkpoints, 0);
+        assert_eq!(details.current_balance, 1_000 - 126 - 121 - 2);
+    }
+
+    #[test]
+    fn funded_staking_stays_solvent() {
+        for seed in 1..=32u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let mut details = details(1 + rng.next(5));
+            let multipliers = [MULTIPLIER_BASE, 15_000, 7_500, 20_000];
+
+            details.add_collection(Pubkey::new_unique(), 20_000);
+
+            // Unstaking early forfeits part of the reward to the vault
+            details.minimum_period = 3_000;
+            details.set_unstake_penalty(5_000, PenaltyDestination::Vault, Pubkey::default());
+            details.set_reward_schedule(vec![
+                ScheduledReward { starts_at: STARTS_AT + 20_000, reward: rng.next(6) },
+                ScheduledReward { starts_at: STARTS_AT + 60_000, reward: rng.next(6) }
+            ], STARTS_AT).unwrap();
+
+            // The creator funds the emission of every NFT staked at the highest multiplier
+            let max_nft_multiplier = details.max_nft_multiplier().unwrap();
+            details.current_balance = details.future_emission(max_nft_multiplier, STARTS_AT).unwrap();
+
+            let mut staked: Vec<StakedNft> = vec![];
+            let mut time = STARTS_AT;
+
+            while time < ENDS_AT + 5_000 {
+                time += rng.next(1_500) as i64;
+
+                match rng.next(5) {
+                    0 | 1 if time < ENDS_AT && details.current_stakers_count < details.max_stakers_count => {
+                        let multiplier = multipliers[rng.next(4) as usize];
+                        staked.push(stake(&mut details, multiplier, time));
+                    }
+                    2 if time <= ENDS_AT && !staked.is_empty() => {
+                        let index = rng.next(staked.len() as u64) as usize;
+                        withdraw(&mut details, &mut staked[index], time);
+                    }
+                    3 if !staked.is_empty() => {
+                        let index = rng.next(staked.len() as u64) as usize;
+                        unstake(&mut details, staked.swap_remove(index), time);
+                    }
+                    4 if time <= ENDS_AT => {
+                        // The creator changes the reward only while the vault still covers the emission
+                        let mut changed = details.clone();
+                        changed.change_reward(rng.next(8), time).unwrap();
+
+                        if changed.require_emission_covered(max_nft_multiplier, time).is_ok() {
+                            details = changed;
+                        }
+                    }
+                    _ => {}
+                }
+
+                let liability = details.liability(time).unwrap();
+                let owed: u64 = staked.iter().map(|nft| reward_of(&details, nft, time)).sum();
+
+                assert!(details.current_balance >= liability, "seed {}: balance {} below liability {}", seed, details.current_balance, liability);
+                assert!(liability >= owed, "seed {}: liability {} below the owed reward {}", seed, liability, owed);
+            }
+
+            // Every NFT can still be unstaked with its reward
+            while let Some(nft) = staked.pop() {
+                unstake(&mut details, nft, time);
+            }
+
+            assert_eq!(details.liability(time).unwrap(), 0);
+            assert_eq!(details.current_stakers_count, 0);
+        }
+    }
 }
\ No newline at end of file
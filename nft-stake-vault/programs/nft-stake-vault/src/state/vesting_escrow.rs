@@ -0,0 +1,181 @@
+use anchor_lang::prelude::*;
+
+use crate::StakeError;
+
+#[account]
+pub struct VestingEscrow {
+    /// The staking the escrow belongs to (32)
+    pub stake_details: Pubkey,
+    /// The wallet of the staker (32)
+    pub staker: Pubkey,
+    /// The reward still vesting (8)
+    pub locked_amount: u64,
+    /// The vested reward not yet claimed (8)
+    pub vested_amount: u64,
+    /// The time from which the locked reward vests (8)
+    pub vesting_start: i64,
+    /// The time at which the locked reward is fully vested (8)
+    pub vesting_ends_at: i64,
+    /// The bump of the vesting escrow PDA (1)
+    pub bump: u8
+}
+
+impl VestingEscrow {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 1;
+
+    /// Sets up a newly created escrow, an existing one keeps its balances
+    pub fn init_if_new(&mut self, stake_details: Pubkey, staker: Pubkey, bump: u8) {
+        if self.staker == Pubkey::default() {
+            self.stake_details = stake_details;
+            self.staker = staker;
+            self.bump = bump;
+        }
+    }
+
+    /// Moves the linearly vested part of the locked reward to the vested amount
+    pub fn release(&mut self, current_time: i64) -> Result<()> {
+        if current_time >= self.vesting_ends_at {
+            self.vested_amount = self.vested_amount
+                .checked_add(self.locked_amount)
+                .ok_or(StakeError::ProgramAddError)?;
+            self.locked_amount = 0;
+        } else if current_time > self.vesting_start {
+            // directly converting to u128 since they can't be negative
+            let elapsed = (current_time - self.vesting_start) as u128;
+            let remaining_period = (self.vesting_ends_at - self.vesting_start) as u128;
+
+            let released = (self.locked_amount as u128)
+                .checked_mul(elapsed)
+                .ok_or(StakeError::ProgramMulError)?
+                .checked_div(remaining_period)
+                .ok_or(StakeError::ProgramDivError)? as u64;
+
+            self.locked_amount -= released;
+            self.vested_amount = self.vested_amount
+                .checked_add(released)
+                .ok_or(StakeError::ProgramAddError)?;
+        }
+
+        // The rest of the locked reward keeps vesting at the same rate until the end
+        self.vesting_start = self.vesting_start.max(current_time.min(self.vesting_ends_at));
+
+        Ok(())
+    }
+
+    /// Adds a claimed reward, the locked reward vests until the end times of the rewards weighted by their amounts
+    pub fn deposit(&mut self, reward: u64, current_time: i64, vesting_duration: i64) -> Result<()> {
+        self.release(current_time)?;
+
+        let locked_amount = self.locked_amount
+            .checked_add(reward)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        if locked_amount == 0 {
+            return Ok(());
+        }
+
+        // directly converting to u128 since they can't be negative, the released reward moved the start to now
+        let remaining_period = (self.vesting_ends_at - self.vesting_start) as u128;
+
+        let locked_weight = (self.locked_amount as u128)
+            .checked_mul(remaining_period)
+            .ok_or(StakeError::ProgramMulError)?;
+
+        let reward_weight = (reward as u128)
+            .checked_mul(vesting_duration as u128)
+            .ok_or(StakeError::ProgramMulError)?;
+
+        // At most the longer of the two periods, so it fits in an i64
+        let vesting_period = locked_weight
+            .checked_add(reward_weight)
+            .ok_or(StakeError::ProgramAddError)?
+            .checked_div(locked_amount as u128)
+            .ok_or(StakeError::ProgramDivError)? as i64;
+
+        self.locked_amount = locked_amount;
+        self.vesting_start = current_time;
+        self.vesting_ends_at = current_time
+            .checked_add(vesting_period)
+            .ok_or(StakeError::ProgramAddError)?;
+
+        Ok(())
+    }
+
+    /// Takes the vested reward out of the escrow
+    pub fn claim(&mut self, current_time: i64) -> Result<u64> {
+        self.release(current_time)?;
+
+        let vested_amount = self.vested_amount;
+        self.vested_amount = 0;
+
+        Ok(vested_amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escrow() -> VestingEscrow {
+        VestingEscrow {
+            stake_details: Pubkey::new_unique(),
+            staker: Pubkey::new_unique(),
+            locked_amount: 0,
+            vested_amount: 0,
+            vesting_start: 0,
+            vesting_ends_at: 0,
+            bump: 255
+        }
+    }
+
+    #[test]
+    fn deposit_vests_linearly() {
+        let mut vesting_escrow = escrow();
+        vesting_escrow.deposit(1_000, 100, 100).unwrap();
+
+        assert_eq!(vesting_escrow.claim(100).unwrap(), 0);
+        assert_eq!(vesting_escrow.claim(150).unwrap(), 500);
+        assert_eq!(vesting_escrow.claim(175).unwrap(), 250);
+        assert_eq!(vesting_escrow.claim(300).unwrap(), 250);
+        assert_eq!(vesting_escrow.claim(400).unwrap(), 0);
+    }
+
+    #[test]
+    fn deposit_keeps_the_earlier_schedule() {
+        let mut vesting_escrow = escrow();
+        vesting_escrow.deposit(1_000, 0, 100).unwrap();
+
+        // 500 left to vest in 50 seconds and 500 more over 100 seconds end after 75 seconds
+        vesting_escrow.deposit(500, 50, 100).unwrap();
+        assert_eq!((vesting_escrow.locked_amount, vesting_escrow.vested_amount), (1_000, 500));
+        assert_eq!((vesting_escrow.vesting_start, vesting_escrow.vesting_ends_at), (50, 125));
+
+        assert_eq!(vesting_escrow.claim(50).unwrap(), 500);
+        assert_eq!(vesting_escrow.claim(100).unwrap(), 666);
+        assert_eq!(vesting_escrow.claim(125).unwrap(), 334);
+    }
+
+    #[test]
+    fn deposit_after_the_end_vests_over_the_whole_duration() {
+        let mut vesting_escrow = escrow();
+        vesting_escrow.deposit(1_000, 0, 100).unwrap();
+        vesting_escrow.deposit(300, 200, 100).unwrap();
+
+        assert_eq!(vesting_escrow.vesting_ends_at, 300);
+        assert_eq!(vesting_escrow.claim(200).unwrap(), 1_000);
+        assert_eq!(vesting_escrow.claim(250).unwrap(), 150);
+        assert_eq!(vesting_escrow.claim(300).unwrap(), 150);
+    }
+
+    #[test]
+    fn claim_takes_only_the_vested_reward() {
+        let mut vesting_escrow = escrow();
+        vesting_escrow.deposit(0, 0, 100).unwrap();
+        assert_eq!(vesting_escrow.claim(50).unwrap(), 0);
+
+        vesting_escrow.deposit(100, 50, 100).unwrap();
+        assert_eq!(vesting_escrow.claim(100).unwrap(), 50);
+        assert_eq!(vesting_escrow.locked_amount, 50);
+        assert_eq!(vesting_escrow.claim(100).unwrap(), 0);
+    }
+}
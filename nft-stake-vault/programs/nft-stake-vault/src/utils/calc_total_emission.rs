@@ -1,21 +1,30 @@
 use anchor_lang::prelude::*;
-use crate::StakeError;
+use crate::{state::EmissionCurve, StakeError};
+use super::{apply_multiplier, curve_reward_per_nft};
 
+/// The emission between the two times if every NFT is staked with the given multiplier,
+/// by the reward curve laid over them
 pub fn calc_total_emission(
     reward: u64,
+    emission_curve: &EmissionCurve,
     max_stakers_count: u64,
     staking_starts_at: i64,
-    staking_ends_at: i64
+    staking_ends_at: i64,
+    max_multiplier: u64
 ) -> Result<u64> {
     let total_staking_period = staking_ends_at.checked_sub(staking_starts_at).ok_or(StakeError::ProgramSubError)?;
 
-    let rewardable_time_u64 = match u64::try_from(total_staking_period) {
-        Ok(time) => time,
-        _ => return err!(StakeError::FailedTimeConversion)
+    let reward_per_nft = curve_reward_per_nft(emission_curve, reward, total_staking_period, total_staking_period)?;
+
+    let total_emission = reward_per_nft.checked_mul(max_stakers_count as u128).ok_or(StakeError::ProgramMulError)?;
+
+    let total_emission = match u64::try_from(total_emission) {
+        Ok(emission) => emission,
+        _ => return err!(StakeError::ProgramMulError)
     };
 
-    let total_rewardable_time = rewardable_time_u64.checked_mul(max_stakers_count).ok_or(StakeError::ProgramMulError)?;
-    let total_emission = total_rewardable_time.checked_mul(reward).ok_or(StakeError::ProgramMulError)?;
+    // Every NFT could be staked from the collection with the highest multiplier
+    let total_emission = apply_multiplier(total_emission, max_multiplier)?;
 
     Ok(total_emission)
-}
+}
\ No newline at end of file
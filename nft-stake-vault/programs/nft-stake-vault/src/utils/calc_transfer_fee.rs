@@ -0,0 +1,49 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+    state::Mint
+};
+
+use crate::StakeError;
+
+/// Returns the transfer fee config of the mint, if it has one
+fn transfer_fee_config(mint: &AccountInfo) -> Result<Option<TransferFeeConfig>> {
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+
+    Ok(mint_state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// The fee withheld from a transfer of the given amount
+pub fn calc_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let fee = match transfer_fee_config(mint)? {
+        Some(fee_config) => {
+            let epoch = Clock::get()?.epoch;
+
+            fee_config
+                .calculate_epoch_fee(epoch, amount)
+                .ok_or(StakeError::TransferFeeError)?
+        },
+        None => 0
+    };
+
+    Ok(fee)
+}
+
+/// The amount that must be sent for the given amount to arrive after the transfer fee
+pub fn calc_pre_fee_amount(mint: &AccountInfo, post_fee_amount: u64) -> Result<u64> {
+    let fee = match transfer_fee_config(mint)? {
+        Some(fee_config) => {
+            let epoch = Clock::get()?.epoch;
+
+            fee_config
+                .calculate_inverse_epoch_fee(epoch, post_fee_amount)
+                .ok_or(StakeError::TransferFeeError)?
+        },
+        None => 0
+    };
+
+    post_fee_amount
+        .checked_add(fee)
+        .ok_or(StakeError::ProgramAddError.into())
+}
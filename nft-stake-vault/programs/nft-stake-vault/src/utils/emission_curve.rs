@@ -0,0 +1,266 @@
+use anchor_lang::prelude::*;
+use crate::{state::EmissionCurve, StakeError, MULTIPLIER_BASE};
+
+/// The precision of the fixed-point maths of an exponential curve
+pub const DECAY_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// ln 2, scaled by the decay precision
+const LN_2: u128 = 693_147_180_559_945_309;
+
+/// The reward accrued by an NFT with the base multiplier on the curve, from its start until the elapsed time.
+/// A linear curve ramps over the curve duration. Rounded down, it never decreases as the time goes on
+pub fn curve_reward_per_nft(curve: &EmissionCurve, reward: u64, elapsed: i64, curve_duration: i64) -> Result<u128> {
+    let (elapsed, curve_duration) = match (u128::try_from(elapsed), u128::try_from(curve_duration)) {
+        (Ok(elapsed), Ok(curve_duration)) => (elapsed, curve_duration),
+        _ => return err!(StakeError::FailedTimeConversion)
+    };
+
+    let reward = reward as u128;
+
+    match *curve {
+        EmissionCurve::Constant => Ok(reward.checked_mul(elapsed).ok_or(StakeError::ProgramMulError)?),
+        EmissionCurve::Linear { end_reward } => linear_reward_per_nft(reward, end_reward as u128, curve_duration, elapsed),
+        EmissionCurve::Exponential { decay, period } => exponential_reward_per_nft(reward, decay as u128, period as u128, elapsed)
+    }
+}
+
+/// The area under the ramp until the elapsed time, then the end reward for the time after it
+fn linear_reward_per_nft(reward: u128, end_reward: u128, duration: u128, elapsed: u128) -> Result<u128> {
+    let ramp_time = elapsed.min(duration);
+
+    // t * (r0 * (2T - t) + r1 * t) / 2T
+    let ramp_reward = reward
+        .checked_mul(2 * duration - ramp_time)
+        .ok_or(StakeError::ProgramMulError)?
+        .checked_add(end_reward.checked_mul(ramp_time).ok_or(StakeError::ProgramMulError)?)
+        .ok_or(StakeError::ProgramAddError)?
+        .checked_mul(ramp_time)
+        .ok_or(StakeError::ProgramMulError)?
+        .checked_div(2 * duration)
+        .ok_or(StakeError::ProgramDivError)?;
+
+    let flat_reward = end_reward
+        .checked_mul(elapsed - ramp_time)
+        .ok_or(StakeError::ProgramMulError)?;
+
+    Ok(ramp_reward.checked_add(flat_reward).ok_or(StakeError::ProgramAddError)?)
+}
+
+/// The integral of the rate decaying continuously, r0 * q^(t / P). The elapsed periods add up to
+/// r0 * P * (1 - q^n) / ln(1 / q), and the current one to q^n times the integral of the first period until its time
+fn exponential_reward_per_nft(reward: u128, decay: u128, period: u128, elapsed: u128) -> Result<u128> {
+    let factor = DECAY_PRECISION * (MULTIPLIER_BASE as u128 - decay) / MULTIPLIER_BASE as u128;
+    let decay_rate = decay_rate(decay);
+
+    let periods = elapsed / period;
+    let period_time = elapsed % period;
+
+    let reward_period = reward.checked_mul(period).ok_or(StakeError::ProgramMulError)?;
+
+    let remaining = decay_power(factor, periods)?;
+    let periods_reward = decayed_reward(reward_period, DECAY_PRECISION - remaining, decay_rate)?;
+    let next_periods_reward = decayed_reward(reward_period, DECAY_PRECISION - decay_power(factor, periods + 1)?, decay_rate)?;
+
+    // q^(t / P) = e^(-t * ln(1 / q) / P) of the time into the current period
+    let exponent = period_time
+        .checked_mul(decay_rate)
+        .ok_or(StakeError::ProgramMulError)?
+        .checked_div(period)
+        .ok_or(StakeError::ProgramDivError)?;
+
+    let period_share = remaining
+        .checked_mul(DECAY_PRECISION - exp_neg(exponent))
+        .ok_or(StakeError::ProgramMulError)?
+        / DECAY_PRECISION;
+
+    // Capped at the whole period, so the rounding can't take it past the start of the next one
+    let period_reward = decayed_reward(reward_period, period_share, decay_rate)?
+        .min(next_periods_reward.checked_sub(periods_reward).ok_or(StakeError::ProgramSubError)?);
+
+    Ok(periods_reward.checked_add(period_reward).ok_or(StakeError::ProgramAddError)?)
+}
+
+/// The reward of the share of the rate that decayed, r0 * P * share / ln(1 / q)
+fn decayed_reward(reward_period: u128, decayed_share: u128, decay_rate: u128) -> Result<u128> {
+    let decayed_periods = decayed_share
+        .checked_mul(DECAY_PRECISION)
+        .ok_or(StakeError::ProgramMulError)?
+        .checked_div(decay_rate)
+        .ok_or(StakeError::ProgramDivError)?;
+
+    Ok(reward_period.checked_mul(decayed_periods).ok_or(StakeError::ProgramMulError)? / DECAY_PRECISION)
+}
+
+/// ln(1 / q) of the decay, scaled by the precision. The ratio is halved into [1, 2) first,
+/// where the series of ln x = 2 * atanh((x - 1) / (x + 1)) gains a digit with each term
+fn decay_rate(decay: u128) -> u128 {
+    let base = MULTIPLIER_BASE as u128;
+    let mut remaining = base - decay;
+    let mut halvings = 0;
+
+    while remaining * 2 <= base {
+        remaining *= 2;
+        halvings += 1;
+    }
+
+    let ratio = base * DECAY_PRECISION / remaining;
+    let atanh_arg = (ratio - DECAY_PRECISION) * DECAY_PRECISION / (ratio + DECAY_PRECISION);
+    let arg_squared = atanh_arg * atanh_arg / DECAY_PRECISION;
+
+    let mut power = atanh_arg;
+    let mut series = 0;
+    let mut divisor = 1;
+
+    while power > 0 {
+        series += power / divisor;
+        power = power * arg_squared / DECAY_PRECISION;
+        divisor += 2;
+    }
+
+    halvings * LN_2 + 2 * series
+}
+
+/// e^(-x) scaled by the precision, for x up to ln(1 / q) of the steepest decay, about 9.2.
+/// It's the 16th power of the inverse of the series of e^(x / 16), every step rounding down the same way,
+/// so it never grows with x
+fn exp_neg(exponent: u128) -> u128 {
+    let exponent = exponent / 16;
+
+    let mut term = DECAY_PRECISION;
+    let mut series = DECAY_PRECISION;
+    let mut divisor = 1;
+
+    while term > 0 {
+        term = term * exponent / DECAY_PRECISION / divisor;
+        series += term;
+        divisor += 1;
+    }
+
+    let mut result = DECAY_PRECISION * DECAY_PRECISION / series;
+
+    for _ in 0..4 {
+        result = result * result / DECAY_PRECISION;
+    }
+
+    result
+}
+
+/// The decay factor raised to the number of periods by squaring, rounded down.
+/// Multiplying by the squares in ascending order keeps it from growing with the periods,
+/// so the sum of the periods never decreases
+fn decay_power(factor: u128, mut periods: u128) -> Result<u128> {
+    let mut remaining = DECAY_PRECISION;
+    let mut square = factor;
+
+    while periods > 0 && remaining > 0 {
+        if periods & 1 == 1 {
+            remaining = remaining.checked_mul(square).ok_or(StakeError::ProgramMulError)? / DECAY_PRECISION;
+        }
+
+        square = square.checked_mul(square).ok_or(StakeError::ProgramMulError)? / DECAY_PRECISION;
+        periods >>= 1;
+    }
+
+    Ok(remaining)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The reward of a linear curve summed second by second, at the rate in the middle of each second
+    fn linear_step_sum(reward: u64, end_reward: u64, duration: i64, elapsed: i64) -> u128 {
+        let (reward, end_reward, duration) = (reward as i128, end_reward as i128, duration as i128);
+
+        // Each second scaled by twice the duration, so the middle of the second is a whole number
+        let ramp_reward: i128 = (0..(elapsed as i128).min(duration))
+            .map(|second| 2 * duration * reward + (end_reward - reward) * (2 * second + 1))
+            .sum();
+
+        let flat_time = (elapsed as i128 - duration).max(0);
+
+        (ramp_reward / (2 * duration) + end_reward * flat_time) as u128
+    }
+
+    /// The integral of an exponential curve, r0 * P * (1 - q^(t / P)) / ln(1 / q)
+    fn exponential_integral(reward: u64, decay: u64, period: i64, elapsed: i64) -> f64 {
+        let factor = 1.0 - decay as f64 / MULTIPLIER_BASE as f64;
+
+        reward as f64 * period as f64 * (1.0 - factor.powf(elapsed as f64 / period as f64)) / -factor.ln()
+    }
+
+    #[test]
+    fn linear_curve_matches_the_step_sum() {
+        let curves = [(100, 10, 1_000), (10, 100, 1_000), (7, 3, 9), (0, 50, 333)];
+
+        for (reward, end_reward, duration) in curves {
+            let curve = EmissionCurve::Linear { end_reward };
+
+            for elapsed in [0, 1, 2, duration / 2, duration - 1, duration, duration + 1, 3 * duration] {
+                assert_eq!(
+                    curve_reward_per_nft(&curve, reward, elapsed, duration).unwrap(),
+                    linear_step_sum(reward, end_reward, duration, elapsed),
+                    "linear curve {} to {} over {}, at {}", reward, end_reward, duration, elapsed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exponential_curve_matches_the_integral() {
+        let curves = [(1_000_000, 500, 100), (1_000_000, 9_000, 60), (5_000, 1, 3_600), (1_000, 2_500, 1), (1_000_000_000, 9_999, 86_400)];
+
+        for (reward, decay, period) in curves {
+            let curve = EmissionCurve::Exponential { decay, period };
+
+            for elapsed in [0, 1, period / 3, period - 1, period, period + period / 2, 10 * period, 37 * period + 1, 200 * period] {
+                let closed_form = curve_reward_per_nft(&curve, reward, elapsed, 0).unwrap() as f64;
+                let integral = exponential_integral(reward, decay, period, elapsed);
+
+                // The fixed-point maths keeps about 15 digits, more than the float it's checked against
+                let tolerance = 2.0 + integral * 1e-12;
+
+                assert!(
+                    (closed_form - integral).abs() <= tolerance,
+                    "exponential curve {} decaying {} every {}, at {}: {} against {}", reward, decay, period, elapsed, closed_form, integral
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exponential_curve_decays_continuously() {
+        let curve = EmissionCurve::Exponential { decay: 1_000, period: 10 };
+        let reward_at = |elapsed: i64| curve_reward_per_nft(&curve, 1_000_000, elapsed, 0).unwrap();
+
+        // The first second accrues a bit less than the initial rate, as it decays during the second
+        let first_second = reward_at(1);
+        assert!(first_second > 994_000 && first_second < 995_000);
+
+        // Every second accrues less than the one before, within a period and across its end
+        for second in 0..30 {
+            assert!(reward_at(second + 2) - reward_at(second + 1) < reward_at(second + 1) - reward_at(second));
+        }
+
+        // A whole period accrues 10% less than the one before
+        let first_period = reward_at(10) - reward_at(0);
+        assert!((reward_at(20) - reward_at(10)).abs_diff(first_period * 9 / 10) <= 2);
+    }
+
+    #[test]
+    fn exponential_curve_never_decreases() {
+        let curves = [(1_000, 1_000, 10), (1_000_000, 9_999, 7), (3, 1, 5)];
+
+        for (reward, decay, period) in curves {
+            let curve = EmissionCurve::Exponential { decay, period };
+            let mut last_reward = 0;
+
+            // Even once the rate rounds down to nothing
+            for elapsed in 0..20_000 {
+                let accrued = curve_reward_per_nft(&curve, reward, elapsed, 0).unwrap();
+                assert!(accrued >= last_reward, "exponential curve {} decaying {} every {}, at {}", reward, decay, period, elapsed);
+                last_reward = accrued;
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use anchor_lang::{prelude::*, solana_program::keccak::hashv};
+
+/// Whether the proof leads from the leaf to the root, hashing each pair of nodes in sorted order
+pub fn verify_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let computed_root = proof.iter().fold(leaf, |hash, node| {
+        if hash <= *node {
+            hashv(&[&hash, node]).0
+        } else {
+            hashv(&[node, &hash]).0
+        }
+    });
+
+    computed_root == root
+}
+
+/// The leaf of a mint in an allowlist tree
+pub fn mint_leaf(mint: &Pubkey) -> [u8; 32] {
+    hashv(&[mint.as_ref()]).0
+}
+
+/// The leaf of a mint and its rarity multiplier in a rarity tree
+pub fn rarity_leaf(mint: &Pubkey, multiplier: u64) -> [u8; 32] {
+    hashv(&[mint.as_ref(), &multiplier.to_le_bytes()]).0
+}
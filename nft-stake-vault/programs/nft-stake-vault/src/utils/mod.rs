@@ -1,7 +1,27 @@
 pub use calc_reward::*;
 pub use calc_total_emission::*;
-pub use calc_actual_balance::*;
+pub use emission_curve::*;
+pub use transfer_pnft::*;
+pub use lock_pnft::*;
+pub use token_group::*;
+pub use core_asset::*;
+pub use calc_transfer_fee::*;
+pub use collection_source::*;
+pub use merkle_proof::*;
+pub use create_pda::*;
+pub use settle_forfeit::*;
+pub use reward_streams::*;
 
 pub mod calc_reward;
 pub mod calc_total_emission;
-pub mod calc_actual_balance;
+pub mod emission_curve;
+pub mod transfer_pnft;
+pub mod lock_pnft;
+pub mod token_group;
+pub mod core_asset;
+pub mod calc_transfer_fee;
+pub mod collection_source;
+pub mod merkle_proof;
+pub mod create_pda;
+pub mod settle_forfeit;
+pub mod reward_streams;
@@ -0,0 +1,175 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_interface::{transfer_checked, Mint, TokenAccount, TransferChecked},
+    associated_token::{create_idempotent, get_associated_token_address_with_program_id, Create}
+};
+
+use crate::{state::{Details, NftRecord, RewardStream}, StakeError};
+
+/// The accounts of each reward stream at the start of the remaining accounts:
+/// reward stream, stream mint, stream vault, reward receive account and the token program of the stream mint
+pub const ACCOUNTS_PER_STREAM: usize = 5;
+
+pub struct StreamPayout<'info> {
+    pub staker: AccountInfo<'info>,
+    pub token_authority: AccountInfo<'info>,
+    pub associated_token_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>
+}
+
+/// The number of remaining accounts taken by the reward streams of the staking
+pub fn stream_accounts_len(stake_details: &Details) -> usize {
+    stake_details.reward_streams.len() * ACCOUNTS_PER_STREAM
+}
+
+/// Reads a reward stream of the staking and checks it is the one of the stream mint
+fn load_reward_stream(stake_details_key: &Pubkey, stream_mint: &Pubkey, reward_stream_info: &AccountInfo) -> Result<RewardStream> {
+    require_keys_eq!(*reward_stream_info.owner, crate::ID, ErrorCode::AccountOwnedByWrongProgram);
+
+    let reward_stream = RewardStream::try_deserialize(&mut &reward_stream_info.try_borrow_data()?[..])?;
+
+    let reward_stream_key = Pubkey::create_program_address(
+        &[b"reward-stream", stake_details_key.as_ref(), stream_mint.as_ref(), &[reward_stream.bump]],
+        &crate::ID
+    ).map_err(|_| ErrorCode::ConstraintSeeds)?;
+
+    require_keys_eq!(reward_stream_info.key(), reward_stream_key, ErrorCode::ConstraintSeeds);
+
+    Ok(reward_stream)
+}
+
+/// Reads every reward stream of the staking, in the order they were added
+pub fn load_reward_streams(stake_details: &Account<Details>, remaining_accounts: &[AccountInfo]) -> Result<Vec<RewardStream>> {
+    let stake_details_key = stake_details.key();
+
+    require_gte!(remaining_accounts.len(), stream_accounts_len(stake_details), StakeError::InvalidStreamAccounts);
+
+    stake_details.reward_streams
+        .iter()
+        .zip(remaining_accounts.chunks(ACCOUNTS_PER_STREAM))
+        .map(|(stream_mint, stream_accounts)| load_reward_stream(&stake_details_key, stream_mint, &stream_accounts[0]))
+        .collect()
+}
+
+/// Reads every reward stream of the staking from the reward stream accounts alone at the start of the remaining accounts
+pub fn load_stream_accounts(stake_details: &Account<Details>, remaining_accounts: &[AccountInfo]) -> Result<Vec<RewardStream>> {
+    let stake_details_key = stake_details.key();
+
+    require_gte!(remaining_accounts.len(), stake_details.reward_streams.len(), StakeError::InvalidStreamAccounts);
+
+    stake_details.reward_streams
+        .iter()
+        .zip(remaining_accounts)
+        .map(|(stream_mint, reward_stream_info)| load_reward_stream(&stake_details_key, stream_mint, reward_stream_info))
+        .collect()
+}
+
+/// The current reward per NFT of every stream, read from the reward stream accounts alone
+/// at the start of the remaining accounts, the checkpoints of an NFT staked now
+pub fn load_stream_checkpoints(stake_details: &Account<Details>, remaining_accounts: &[AccountInfo], current_time: i64) -> Result<Vec<u128>> {
+    let reward_streams = load_stream_accounts(stake_details, remaining_accounts)?;
+
+    stream_checkpoints(&reward_streams, current_time, stake_details.staking_ends_at)
+}
+
+/// The current reward per NFT of every stream, the checkpoints of an NFT whose stream rewards were just paid
+pub fn stream_checkpoints(reward_streams: &[RewardStream], current_time: i64, staking_ends_at: i64) -> Result<Vec<u128>> {
+    reward_streams
+        .iter()
+        .map(|reward_stream| reward_stream.reward_per_nft_at(current_time, staking_ends_at))
+        .collect()
+}
+
+/// The reward of an NFT in every stream since its checkpoints, along with the one accrued before its last override
+pub fn stream_rewards_of(
+    reward_streams: &[RewardStream],
+    nft_record: &NftRecord,
+    current_time: i64,
+    staking_ends_at: i64,
+    multiplier: u64
+) -> Result<Vec<u64>> {
+    reward_streams
+        .iter()
+        .enumerate()
+        .map(|(stream_index, reward_stream)| {
+            reward_stream
+                .reward_of(nft_record.stream_checkpoint(stream_index), current_time, staking_ends_at, multiplier)?
+                .checked_add(nft_record.accrued_stream_reward(stream_index))
+                .ok_or(error!(StakeError::ProgramAddError))
+        })
+        .collect()
+}
+
+/// Transfers the reward of every stream to the staker, a stream vault that can't cover the reward fails the payout
+pub fn pay_reward_streams<'info>(
+    accounts: &StreamPayout<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    reward_streams: &[RewardStream],
+    rewards: &[u64],
+    signer_seeds: &[&[&[u8]]]
+) -> Result<()> {
+    for ((reward_stream, &reward), stream_accounts) in reward_streams
+        .iter()
+        .zip(rewards)
+        .zip(remaining_accounts.chunks(ACCOUNTS_PER_STREAM))
+    {
+        let stream_mint_info = &stream_accounts[1];
+        let stream_vault_info = &stream_accounts[2];
+        let reward_receive_info = &stream_accounts[3];
+        let stream_token_program = &stream_accounts[4];
+
+        require_keys_eq!(stream_mint_info.key(), reward_stream.reward_mint, ErrorCode::ConstraintAddress);
+        let stream_mint = InterfaceAccount::<Mint>::try_from(stream_mint_info)?;
+
+        // Each stream mint is paid with its own token program
+        let token_program_key = *stream_mint_info.owner;
+        require_keys_eq!(stream_token_program.key(), token_program_key, ErrorCode::InvalidProgramId);
+
+        require_keys_eq!(
+            stream_vault_info.key(),
+            get_associated_token_address_with_program_id(&accounts.token_authority.key(), &stream_mint.key(), &token_program_key),
+            ErrorCode::ConstraintAssociated
+        );
+        require_keys_eq!(
+            reward_receive_info.key(),
+            get_associated_token_address_with_program_id(&accounts.staker.key(), &stream_mint.key(), &token_program_key),
+            ErrorCode::ConstraintAssociated
+        );
+
+        let stream_vault = InterfaceAccount::<TokenAccount>::try_from(stream_vault_info)?;
+
+        require_gte!(stream_vault.amount, reward, StakeError::InsufficientBalInVault);
+
+        if reward == 0 {
+            continue;
+        }
+
+        let create_accounts = Create {
+            payer: accounts.staker.clone(),
+            associated_token: reward_receive_info.clone(),
+            authority: accounts.staker.clone(),
+            mint: stream_mint_info.clone(),
+            system_program: accounts.system_program.clone(),
+            token_program: stream_token_program.clone()
+        };
+
+        create_idempotent(CpiContext::new(accounts.associated_token_program.clone(), create_accounts))?;
+
+        let transfer_accounts = TransferChecked {
+            from: stream_vault_info.clone(),
+            mint: stream_mint_info.clone(),
+            to: reward_receive_info.clone(),
+            authority: accounts.token_authority.clone()
+        };
+
+        transfer_checked(
+            CpiContext::new_with_signer(stream_token_program.clone(), transfer_accounts, signer_seeds),
+            reward,
+            stream_mint.decimals
+        )?;
+
+        msg!("Stream reward: {} of {}", reward, reward_stream.reward_mint);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{burn, transfer_checked, Burn, TransferChecked};
+
+use crate::{state::PenaltyDestination, StakeError};
+
+pub struct ForfeitAccounts<'info> {
+    pub stake_token_vault: AccountInfo<'info>,
+    pub reward_mint: AccountInfo<'info>,
+    pub token_authority: AccountInfo<'info>,
+    pub penalty_treasury: Option<AccountInfo<'info>>,
+    pub token_program: AccountInfo<'info>
+}
+
+/// Burns the forfeited reward or sends it to the treasury,
+/// the vault destination needs no transfer since the tokens stay in the stake vault
+pub fn settle_forfeit<'info>(
+    accounts: ForfeitAccounts<'info>,
+    destination: PenaltyDestination,
+    amount: u64,
+    decimals: u8,
+    signer_seeds: &[&[&[u8]]]
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    match destination {
+        PenaltyDestination::Vault => Ok(()),
+        PenaltyDestination::Burn => {
+            let cpi_accounts = Burn {
+                mint: accounts.reward_mint,
+                from: accounts.stake_token_vault,
+                authority: accounts.token_authority
+            };
+
+            burn(CpiContext::new_with_signer(accounts.token_program, cpi_accounts, signer_seeds), amount)
+        },
+        PenaltyDestination::Treasury => {
+            let cpi_accounts = TransferChecked {
+                from: accounts.stake_token_vault,
+                mint: accounts.reward_mint,
+                to: accounts.penalty_treasury.ok_or(StakeError::TreasuryAccountMissing)?,
+                authority: accounts.token_authority
+            };
+
+            transfer_checked(CpiContext::new_with_signer(accounts.token_program, cpi_accounts, signer_seeds), amount, decimals)
+        }
+    }
+}
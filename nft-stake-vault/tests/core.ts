@@ -0,0 +1,217 @@
+import * as anchor from "@project-serum/anchor";
+import { utils, BN } from "@project-serum/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { Program } from "@project-serum/anchor";
+import * as token from "@solana/spl-token"
+import { createUmi } from "@metaplex-foundation/umi-bundle-defaults";
+import { generateSigner, keypairIdentity } from "@metaplex-foundation/umi";
+import { mplCore, create, createCollection, fetchAsset, fetchCollection, MPL_CORE_PROGRAM_ID } from "@metaplex-foundation/mpl-core";
+import { fromWeb3JsKeypair, fromWeb3JsPublicKey, toWeb3JsPublicKey } from "@metaplex-foundation/umi-web3js-adapters";
+import { NftStakeVault } from "../target/types/nft_stake_vault";
+import { expect } from "chai";
+
+// mpl-core is cloned into the local validator, see Anchor.toml
+const coreProgram = toWeb3JsPublicKey(MPL_CORE_PROGRAM_ID);
+
+// Configure the client to use the local cluster.
+anchor.setProvider(anchor.AnchorProvider.env());
+
+const program = anchor.workspace.NftStakeVault as Program<NftStakeVault>;
+const programId = program.idl.metadata.address;
+const connection = program.provider.connection;
+const payer = (program.provider as anchor.AnchorProvider).wallet as anchor.Wallet;
+
+const umi = createUmi(connection.rpcEndpoint)
+  .use(mplCore())
+  .use(keypairIdentity(fromWeb3JsKeypair(payer.payer)));
+
+// The Core collection of the staking and another one
+const collectionSigner = generateSigner(umi);
+const otherCollectionSigner = generateSigner(umi);
+const collectionAddress = toWeb3JsPublicKey(collectionSigner.publicKey);
+const otherCollection = toWeb3JsPublicKey(otherCollectionSigner.publicKey);
+
+const [stakeDetails] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("stake"),
+    collectionAddress.toBytes(),
+    payer.publicKey.toBytes()
+], programId);
+
+const [tokenAuthority] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("token-authority"),
+    stakeDetails.toBytes()
+], programId);
+
+const [nftAuthority] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("nft-authority"),
+    stakeDetails.toBytes()
+], programId);
+
+const [stakerAccount] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("staker"),
+    stakeDetails.toBytes(),
+    payer.publicKey.toBytes()
+], programId);
+
+const [vestingEscrow] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("vesting"),
+    stakeDetails.toBytes(),
+    payer.publicKey.toBytes()
+], programId);
+
+function findNftRecord(asset: PublicKey) {
+  return PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("nft-record"),
+    stakeDetails.toBytes(),
+    asset.toBytes()
+  ], programId)[0];
+}
+
+function findRewardOverride(asset: PublicKey) {
+  return PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("reward-override"),
+    stakeDetails.toBytes(),
+    asset.toBytes()
+  ], programId)[0];
+}
+
+async function createAsset(collection: PublicKey, name: string) {
+  const asset = generateSigner(umi);
+
+  await create(umi, {
+    asset,
+    collection: await fetchCollection(umi, fromWeb3JsPublicKey(collection)),
+    name,
+    uri: "https://example.com/asset.json"
+  }).sendAndConfirm(umi);
+
+  return toWeb3JsPublicKey(asset.publicKey);
+}
+
+describe("nft-stake-vault Core assets", () => {
+  let tokenMint: PublicKey;
+  let stakeTokenVault: PublicKey;
+  let rewardReceiveAccount: PublicKey;
+  let asset: PublicKey;
+
+  before(async() => {
+    await createCollection(umi, { collection: collectionSigner, name: "Staked", uri: "https://example.com/staked.json" }).sendAndConfirm(umi);
+    await createCollection(umi, { collection: otherCollectionSigner, name: "Other", uri: "https://example.com/other.json" }).sendAndConfirm(umi);
+
+    tokenMint = await token.createMint(connection, payer.payer, payer.publicKey, null, 0);
+    const tokenAccount = await token.createAssociatedTokenAccount(connection, payer.payer, tokenMint, payer.publicKey);
+    await token.mintTo(connection, payer.payer, tokenMint, tokenAccount, payer.payer, 1_000_000);
+
+    stakeTokenVault = token.getAssociatedTokenAddressSync(tokenMint, tokenAuthority, true);
+    rewardReceiveAccount = tokenAccount;
+
+    const startTime = new BN(Math.floor(Date.now() / 1000));
+
+    await program.methods.initStaking({
+      reward: new BN(1),
+      minimumPeriod: new BN(0),
+      stakingStartsAt: startTime,
+      stakingEndsAt: startTime.add(new BN(3600)),
+      maxStakersCount: new BN(10),
+      nonCustodial: false,
+      emissionCurve: { constant: {} }
+    })
+    .accounts({
+      stakeDetails,
+      tokenMint,
+      tokenAuthority,
+      collectionAddress,
+      nftAuthority,
+      stakeTokenVault,
+      tokenAccount
+    })
+    .rpc();
+
+    const stakeAccount = await program.account.details.fetch(stakeDetails);
+    expect(stakeAccount.collectionSource).to.deep.equal({ core: {} });
+  });
+
+  it("stakes a Core asset", async() => {
+    asset = await createAsset(collectionAddress, "Core #1");
+    const nftRecord = findNftRecord(asset);
+
+    await program.methods.stakeCore([], null, null)
+    .accounts({
+      stakeDetails,
+      nftRecord,
+      stakerAccount,
+      rewardOverride: findRewardOverride(asset),
+      asset,
+      collection: collectionAddress,
+      nftAuthority,
+      coreProgram
+    })
+    .rpc();
+
+    // The asset stays with the staker, frozen by the nft authority
+    const assetAccount = await fetchAsset(umi, fromWeb3JsPublicKey(asset));
+    expect(assetAccount.owner.toString()).to.equal(payer.publicKey.toBase58());
+    expect(assetAccount.freezeDelegate.frozen).to.be.true;
+    expect(assetAccount.freezeDelegate.authority.address.toString()).to.equal(nftAuthority.toBase58());
+
+    const nftRecordAccount = await program.account.nftRecord.fetch(nftRecord);
+    expect(nftRecordAccount.nftMint.toBase58()).to.equal(asset.toBase58());
+  });
+
+  it("unstakes the Core asset", async() => {
+    const nftRecord = findNftRecord(asset);
+
+    await program.methods.unstakeCore()
+    .accounts({
+      stakeDetails,
+      nftRecord,
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride: findRewardOverride(asset),
+      rewardMint: tokenMint,
+      stakeTokenVault,
+      rewardReceiveAccount,
+      penaltyTreasury: null,
+      asset,
+      collection: collectionAddress,
+      tokenAuthority,
+      nftAuthority,
+      coreProgram
+    })
+    .rpc();
+
+    // The asset is thawed and the freeze delegate removed
+    const assetAccount = await fetchAsset(umi, fromWeb3JsPublicKey(asset));
+    expect(assetAccount.owner.toString()).to.equal(payer.publicKey.toBase58());
+    expect(assetAccount.freezeDelegate).to.be.undefined;
+
+    expect(await connection.getAccountInfo(nftRecord)).to.be.null;
+
+    // The staking doesn't vest its rewards, so no vesting escrow is created
+    expect(await connection.getAccountInfo(vestingEscrow)).to.be.null;
+  });
+
+  it("stakes a Core asset from a different collection and fails", async() => {
+    const otherAsset = await createAsset(otherCollection, "Other #1");
+    let error;
+
+    try {
+      await program.methods.stakeCore([], null, null)
+      .accounts({
+        stakeDetails,
+        nftRecord: findNftRecord(otherAsset),
+        stakerAccount,
+        rewardOverride: findRewardOverride(otherAsset),
+        asset: otherAsset,
+        collection: otherCollection,
+        nftAuthority,
+        coreProgram
+      })
+      .rpc();
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error.error.errorCode.code).to.equal("InvalidCollection");
+  });
+});
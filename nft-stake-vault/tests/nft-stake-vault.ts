@@ -4,6 +4,7 @@ import {PublicKey} from "@solana/web3.js";
 import { Program } from "@project-serum/anchor";
 import * as token from "@solana/spl-token"
 import { NftStakeVault } from "../target/types/nft_stake_vault";
+import { expect } from "chai";
 
 //constants
 const collectionAddress = new PublicKey("AyRhD1Yh8MAdZAhQL8eK1FZcogg1GW4Y87HqsWJytTzo"); // Mint Address of the Collection NFT for which the staking to be activated
@@ -57,10 +58,73 @@ const [nftRecord2] = PublicKey.findProgramAddressSync([
     nftMint2.toBytes()
 ], programId);
 
+const [rewardOverride] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("reward-override"),
+    stakeDetails.toBytes(),
+    nftMint.toBytes()
+], programId);
+
+const [rewardOverride2] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("reward-override"),
+    stakeDetails.toBytes(),
+    nftMint2.toBytes()
+], programId);
+
+const [stakerAccount] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("staker"),
+    stakeDetails.toBytes(),
+    program.provider.publicKey.toBytes()
+], programId);
+
+const [vestingEscrow] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("vesting"),
+    stakeDetails.toBytes(),
+    program.provider.publicKey.toBytes()
+], programId);
+
 const nftCustody = token.getAssociatedTokenAddressSync(nftMint, nftAuthority, true);
 const stakeTokenVault = token.getAssociatedTokenAddressSync(tokenMint, tokenAuthority, true);
 
+// The vault holds the balance in record, which covers the reward owed to every staker
+async function assertSolvent() {
+  const details = await program.account.details.fetch(stakeDetails);
+  const vault = await token.getAccount(program.provider.connection, stakeTokenVault);
+
+  // The reward per NFT follows the scheduled changes that already started
+  const now = new BN(Math.floor(Date.now() / 1000));
+  const cutoff = BN.min(now, details.stakingEndsAt);
+
+  let rewardPerNft = details.rewardPerNft;
+  let reward = details.currentReward;
+  let lastChange = details.lastRewardChange;
+
+  for (const change of details.rewardSchedule) {
+    if (change.startsAt.gte(now)) break;
+
+    rewardPerNft = rewardPerNft.add(reward.mul(BN.max(BN.min(change.startsAt, cutoff).sub(lastChange), new BN(0))));
+    reward = change.reward;
+    lastChange = BN.max(change.startsAt, lastChange);
+  }
+
+  rewardPerNft = rewardPerNft.add(reward.mul(BN.max(cutoff.sub(lastChange), new BN(0))));
+
+  const liability = rewardPerNft
+    .mul(details.stakedMultiplier)
+    .sub(details.stakedCheckpoints)
+    .add(new BN(9999))
+    .div(new BN(10000))
+    .add(details.unpaidAccruedReward);
+
+  const heldBalance = new BN(vault.amount.toString()).sub(details.vestingBalance);
+
+  expect(heldBalance.gte(details.currentBalance)).to.be.true;
+  expect(details.currentBalance.gte(liability)).to.be.true;
+}
+
 describe("nft-stake-vault", () => {
+  // The first stake time of the wallet, kept by its staker account across unstakes
+  let firstStakedAt: number;
+
   it("initializes staking", async() => {
     const minimumPeriod = new BN(0);
     const reward = new BN(1);
@@ -68,13 +132,15 @@ describe("nft-stake-vault", () => {
     const endTime = startTime.add(new BN(3600));
     const maxStakerCount = new BN(10);
 
-    const tx = await program.methods.initStaking(
+    const tx = await program.methods.initStaking({
       reward,
       minimumPeriod,
-      startTime,
-      endTime,
-      maxStakerCount
-    )
+      stakingStartsAt: startTime,
+      stakingEndsAt: endTime,
+      maxStakersCount: maxStakerCount,
+      nonCustodial: false,
+      emissionCurve: { constant: {} }
+    })
     .accounts({
         stakeDetails,
         tokenMint,
@@ -90,13 +156,17 @@ describe("nft-stake-vault", () => {
 
     let stakeAccount = await program.account.details.fetch(stakeDetails);
     console.log(stakeAccount);
+
+    await assertSolvent();
   });
 
   it("stakes NFT", async() => {
-    const tx = await program.methods.stake()
+    const tx = await program.methods.stake([], null, null)
     .accounts({
       stakeDetails,
       nftRecord,
+      stakerAccount,
+      rewardOverride,
       nftMint,
       nftToken,
       nftMetadata,
@@ -113,14 +183,26 @@ describe("nft-stake-vault", () => {
 
     console.log("Stake Details: ", stakeAccount);
     console.log("NFT Record: ", nftRecordAccount);
+
+    let stakerAccountData = await program.account.stakerAccount.fetch(stakerAccount);
+    console.log("Staker Account: ", stakerAccountData);
+
+    expect(stakerAccountData.stakedCount.toNumber()).to.equal(1);
+    expect(stakerAccountData.totalClaimed.toNumber()).to.equal(0);
+    expect(stakerAccountData.firstStakedAt.toNumber()).to.equal(nftRecordAccount.stakedAt.toNumber());
+    firstStakedAt = stakerAccountData.firstStakedAt.toNumber();
+
+    await assertSolvent();
   });
 
   it("stakes NFT from different collection and fails", async() => {
     try {
-      const tx = await program.methods.stake()
+      const tx = await program.methods.stake([], null, null)
       .accounts({
         stakeDetails,
         nftRecord: nftRecord2,
+        stakerAccount,
+        rewardOverride: rewardOverride2,
         nftMint: nftMint2,
         nftToken: nftToken2,
         nftMetadata: nftMetadata2,
@@ -142,6 +224,9 @@ describe("nft-stake-vault", () => {
     .accounts({
       stakeDetails,
       nftRecord,
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride,
       rewardMint: tokenMint,
       rewardReceiveAccount: tokenAccount,
       tokenAuthority,
@@ -153,6 +238,8 @@ describe("nft-stake-vault", () => {
 
     nftRecordAccount = await program.account.nftRecord.fetch(nftRecord);
     console.log("NFT Staked at: ", nftRecordAccount.stakedAt.toNumber());
+
+    await assertSolvent();
   });
 
   it("claims rewards and unstakes", async() => { 
@@ -160,12 +247,18 @@ describe("nft-stake-vault", () => {
     .accounts({
       stakeDetails,
       nftRecord,
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride,
       rewardMint: tokenMint,
       rewardReceiveAccount: tokenAccount,
       tokenAuthority,
       nftAuthority,
       nftCustody,
       nftMint,
+      nftMetadata,
+      nftEdition,
+      rewardTokenProgram: token.TOKEN_PROGRAM_ID,
       nftReceiveAccount: nftToken,
       stakeTokenVault         
     })
@@ -175,6 +268,41 @@ describe("nft-stake-vault", () => {
 
     let stakeAccount = await program.account.details.fetch(stakeDetails);
     console.log("Stake Details: ", stakeAccount);
+
+    // The staking doesn't vest its rewards, so no vesting escrow is created
+    expect(await program.provider.connection.getAccountInfo(vestingEscrow)).to.be.null;
+
+    // The wallet keeps the time of its first stake with nothing staked
+    const stakerAccountData = await program.account.stakerAccount.fetch(stakerAccount);
+    expect(stakerAccountData.stakedCount.toNumber()).to.equal(0);
+    expect(stakerAccountData.firstStakedAt.toNumber()).to.equal(firstStakedAt);
+
+    await assertSolvent();
+  });
+
+  it("stakes NFT again", async() => {
+    const tx = await program.methods.stake([], null, null)
+    .accounts({
+      stakeDetails,
+      nftRecord,
+      stakerAccount,
+      rewardOverride,
+      nftMint,
+      nftToken,
+      nftMetadata,
+      nftAuthority,
+      nftEdition,
+      nftCustody,
+    })
+    .rpc()
+
+    console.log("TX: ", tx);
+
+    const stakerAccountData = await program.account.stakerAccount.fetch(stakerAccount);
+    expect(stakerAccountData.stakedCount.toNumber()).to.equal(1);
+    expect(stakerAccountData.firstStakedAt.toNumber()).to.equal(firstStakedAt);
+
+    await assertSolvent();
   });
 
   it("extends staking", async() => {
@@ -190,6 +318,8 @@ describe("nft-stake-vault", () => {
 
     let stakeAccount = await program.account.details.fetch(stakeDetails);
     console.log("Stake Details: ", stakeAccount);
+
+    await assertSolvent();
   });
 
   it("increase reward", async() => {
@@ -205,6 +335,43 @@ describe("nft-stake-vault", () => {
 
     let stakeAccount = await program.account.details.fetch(stakeDetails);
     console.log("Stake Details: ", stakeAccount);
+
+    await assertSolvent();
+  });
+
+  it("migrates staking created with the accumulator and fails", async() => {
+    let error;
+
+    try {
+      await program.methods.migrateStaking()
+      .accounts({
+        stakeDetails,
+        stakeTokenVault
+      })
+      .rpc()
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error.error.errorCode.code).to.equal("AlreadyMigrated");
+  });
+
+  it("migrates NFT record created with the accumulator and fails", async() => {
+    let error;
+
+    try {
+      await program.methods.migrateNftRecord(nftMint)
+      .accounts({
+        stakeDetails,
+        nftRecord,
+        rewardOverride
+      })
+      .rpc()
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error.error.errorCode.code).to.equal("AlreadyMigrated");
   });
 
   it("adds funds to the vault", async() => {
@@ -223,6 +390,29 @@ describe("nft-stake-vault", () => {
 
     let stakeAccount = await program.account.details.fetch(stakeDetails);
     console.log("Stake Details: ", stakeAccount);
+
+    await assertSolvent();
+  });
+
+  it("schedules a double-rewards window", async() => {
+    const boostStart = new BN(Date.now() / 1000 + 60);
+    const boostEnd = boostStart.add(new BN(60));
+
+    const tx = await program.methods.setRewardSchedule([
+      { startsAt: boostStart, reward: new BN(4) },
+      { startsAt: boostEnd, reward: new BN(2) }
+    ])
+    .accounts({
+      stakeDetails,
+    })
+    .rpc();
+
+    console.log("TX: ", tx);
+
+    let stakeAccount = await program.account.details.fetch(stakeDetails);
+    console.log("Reward Schedule: ", stakeAccount.rewardSchedule);
+
+    await assertSolvent();
   });
 
   it("closes staking", async() => {
@@ -240,5 +430,220 @@ describe("nft-stake-vault", () => {
 
     let stakeAccount = await program.account.details.fetch(stakeDetails);
     console.log("Stake Details: ", stakeAccount);
+
+    await assertSolvent();
+  });
+
+  it("claims rewards and unstakes after closing", async() => { 
+    const tx = await program.methods.unstake()
+    .accounts({
+      stakeDetails,
+      nftRecord,
+      stakerAccount,
+      vestingEscrow: null,
+      rewardOverride,
+      rewardMint: tokenMint,
+      rewardReceiveAccount: tokenAccount,
+      tokenAuthority,
+      nftAuthority,
+      nftCustody,
+      nftMint,
+      nftMetadata,
+      nftEdition,
+      rewardTokenProgram: token.TOKEN_PROGRAM_ID,
+      nftReceiveAccount: nftToken,
+      stakeTokenVault         
+    })
+    .rpc()
+
+    console.log("TX: ", tx);
+
+    let stakeAccount = await program.account.details.fetch(stakeDetails);
+    console.log("Stake Details: ", stakeAccount);
+
+    await assertSolvent();
+  });
+});
+
+// A staking created before the accumulator, loaded into the validator from tests/fixtures/legacy:
+// one NFT staked 100 seconds into a staking of 1000 seconds with a reward of 1, and 10000 tokens in the vault
+const legacyCreator = anchor.web3.Keypair.fromSecretKey(Uint8Array.from([
+  123, 92, 4, 190, 186, 44, 115, 134, 175, 209, 238, 219, 189, 83, 175, 227,
+  59, 0, 49, 32, 32, 72, 7, 48, 38, 43, 150, 168, 253, 141, 114, 70,
+  154, 144, 115, 130, 172, 252, 175, 197, 33, 124, 76, 146, 214, 148, 17, 125,
+  174, 194, 186, 60, 249, 88, 219, 181, 21, 11, 91, 170, 122, 163, 243, 39
+]));
+const legacyCollection = new PublicKey("DgJAxAhZoeV7T3sFBS8hQ9A7RyddGfyYKM2iL1ZfRxYD");
+const legacyRewardMint = new PublicKey("5tbAPknpY9Hqx29aqi6pizn5MxBRdUbdgYbMuhvi92wq");
+const legacyNftMint = new PublicKey("8fWZHBTk27nEkWNow5CqG7MF7QdUnj5LRbTRrc7Wu5xJ");
+const legacyStakedAt = 1_700_000_100;
+
+const [legacyStakeDetails] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("stake"),
+    legacyCollection.toBytes(),
+    legacyCreator.publicKey.toBytes()
+], programId);
+
+const [legacyTokenAuthority] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("token-authority"),
+    legacyStakeDetails.toBytes()
+], programId);
+
+const [legacyNftAuthority] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("nft-authority"),
+    legacyStakeDetails.toBytes()
+], programId);
+
+const [legacyNftRecord, legacyNftRecordBump] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("nft-record"),
+    legacyStakeDetails.toBytes(),
+    legacyNftMint.toBytes()
+], programId);
+
+const [legacyRewardOverride] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("reward-override"),
+    legacyStakeDetails.toBytes(),
+    legacyNftMint.toBytes()
+], programId);
+
+const [legacyStakerAccount] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("staker"),
+    legacyStakeDetails.toBytes(),
+    legacyCreator.publicKey.toBytes()
+], programId);
+
+const [legacyVestingEscrow] = PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode("vesting"),
+    legacyStakeDetails.toBytes(),
+    legacyCreator.publicKey.toBytes()
+], programId);
+
+const legacyStakeTokenVault = token.getAssociatedTokenAddressSync(legacyRewardMint, legacyTokenAuthority, true);
+const legacyNftCustody = token.getAssociatedTokenAddressSync(legacyNftMint, legacyNftAuthority, true);
+const legacyRewardAccount = token.getAssociatedTokenAddressSync(legacyRewardMint, legacyCreator.publicKey);
+const legacyNftAccount = token.getAssociatedTokenAddressSync(legacyNftMint, legacyCreator.publicKey);
+
+describe("nft-stake-vault migration", () => {
+  before(async() => {
+    const connection = program.provider.connection;
+    const signature = await connection.requestAirdrop(legacyCreator.publicKey, 1_000_000_000);
+
+    await connection.confirmTransaction(signature);
+  });
+
+  it("migrates staking created before the accumulator", async() => {
+    const tx = await program.methods.migrateStaking()
+    .accounts({
+      stakeDetails: legacyStakeDetails,
+      stakeTokenVault: legacyStakeTokenVault,
+      creator: legacyCreator.publicKey
+    })
+    .signers([legacyCreator])
+    .rpc()
+
+    console.log("TX: ", tx);
+
+    const details = await program.account.details.fetch(legacyStakeDetails);
+
+    expect(details.usesAccumulator).to.be.true;
+    expect(details.currentReward.toNumber()).to.equal(1);
+    expect(details.currentStakersCount.toNumber()).to.equal(1);
+    expect(details.unmigratedRecords.toNumber()).to.equal(1);
+    expect(details.stakedMultiplier.toNumber()).to.equal(0);
+    expect(details.currentBalance.toNumber()).to.equal(10000);
+  });
+
+  it("migrates the staking again and fails", async() => {
+    let error;
+
+    try {
+      await program.methods.migrateStaking()
+      .accounts({
+        stakeDetails: legacyStakeDetails,
+        stakeTokenVault: legacyStakeTokenVault,
+        creator: legacyCreator.publicKey
+      })
+      .signers([legacyCreator])
+      .rpc()
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error.error.errorCode.code).to.equal("AlreadyMigrated");
+  });
+
+  it("migrates NFT record created before the accumulator", async() => {
+    const tx = await program.methods.migrateNftRecord(legacyNftMint)
+    .accounts({
+      stakeDetails: legacyStakeDetails,
+      nftRecord: legacyNftRecord,
+      rewardOverride: legacyRewardOverride,
+      payer: legacyCreator.publicKey
+    })
+    .signers([legacyCreator])
+    .rpc()
+
+    console.log("TX: ", tx);
+
+    const record = await program.account.nftRecord.fetch(legacyNftRecord);
+
+    expect(record.staker.equals(legacyCreator.publicKey)).to.be.true;
+    expect(record.nftMint.equals(legacyNftMint)).to.be.true;
+    expect(record.stakedAt.toNumber()).to.equal(legacyStakedAt);
+    expect(record.multiplier.toNumber()).to.equal(10000);
+    expect(record.accruedReward.toNumber()).to.equal(0);
+    expect(record.unbondingEndsAt).to.be.null;
+    expect(record.bump).to.equal(legacyNftRecordBump);
+    expect(record.rewardCheckpoint.toNumber()).to.equal(100);
+
+    const details = await program.account.details.fetch(legacyStakeDetails);
+
+    expect(details.unmigratedRecords.toNumber()).to.equal(0);
+    expect(details.stakedMultiplier.toNumber()).to.equal(10000);
+  });
+
+  it("unstakes the migrated NFT", async() => {
+    const tx = await program.methods.unstake()
+    .accounts({
+      stakeDetails: legacyStakeDetails,
+      nftRecord: legacyNftRecord,
+      stakerAccount: legacyStakerAccount,
+      vestingEscrow: null,
+      rewardOverride: legacyRewardOverride,
+      rewardMint: legacyRewardMint,
+      rewardReceiveAccount: legacyRewardAccount,
+      tokenAuthority: legacyTokenAuthority,
+      nftAuthority: legacyNftAuthority,
+      nftCustody: legacyNftCustody,
+      nftMint: legacyNftMint,
+      nftMetadata: null,
+      nftEdition: null,
+      rewardTokenProgram: token.TOKEN_PROGRAM_ID,
+      nftReceiveAccount: legacyNftAccount,
+      stakeTokenVault: legacyStakeTokenVault,
+      staker: legacyCreator.publicKey
+    })
+    .signers([legacyCreator])
+    .rpc()
+
+    console.log("TX: ", tx);
+
+    // The NFT earned 900 seconds of reward until the staking ended
+    const connection = program.provider.connection;
+    const rewardAccount = await token.getAccount(connection, legacyRewardAccount);
+    const nftAccount = await token.getAccount(connection, legacyNftAccount);
+
+    expect(Number(rewardAccount.amount)).to.equal(900);
+    expect(Number(nftAccount.amount)).to.equal(1);
+    expect(await connection.getAccountInfo(legacyNftRecord)).to.be.null;
+    expect(await connection.getAccountInfo(legacyNftCustody)).to.be.null;
+    expect(await connection.getAccountInfo(legacyVestingEscrow)).to.be.null;
+
+    const details = await program.account.details.fetch(legacyStakeDetails);
+
+    expect(details.currentStakersCount.toNumber()).to.equal(0);
+    expect(details.stakedMultiplier.toNumber()).to.equal(0);
+    expect(details.stakedCheckpoints.toNumber()).to.equal(0);
+    expect(details.currentBalance.toNumber()).to.equal(9100);
   });
 });
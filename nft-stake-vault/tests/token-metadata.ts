@@ -0,0 +1,501 @@
+import * as anchor from "@project-serum/anchor";
+import { utils, BN } from "@project-serum/anchor";
+import { AccountMeta, ComputeBudgetProgram, PublicKey } from "@solana/web3.js";
+import { Program } from "@project-serum/anchor";
+import * as token from "@solana/spl-token"
+import { createUmi } from "@metaplex-foundation/umi-bundle-defaults";
+import { generateSigner, keypairIdentity, percentAmount, some, KeypairSigner } from "@metaplex-foundation/umi";
+import {
+  mplTokenMetadata,
+  createNft,
+  verifyCollectionV1,
+  findMetadataPda,
+  findMasterEditionPda,
+  MPL_TOKEN_METADATA_PROGRAM_ID
+} from "@metaplex-foundation/mpl-token-metadata";
+import { fromWeb3JsKeypair, toWeb3JsPublicKey } from "@metaplex-foundation/umi-web3js-adapters";
+import { NftStakeVault } from "../target/types/nft_stake_vault";
+import { expect } from "chai";
+
+// Token Metadata is cloned into the local validator, see Anchor.toml
+const metadataProgram = toWeb3JsPublicKey(MPL_TOKEN_METADATA_PROGRAM_ID);
+
+// Configure the client to use the local cluster.
+anchor.setProvider(anchor.AnchorProvider.env());
+
+const program = anchor.workspace.NftStakeVault as Program<NftStakeVault>;
+const programId = program.idl.metadata.address;
+const connection = program.provider.connection;
+const payer = (program.provider as anchor.AnchorProvider).wallet as anchor.Wallet;
+
+const umi = createUmi(connection.rpcEndpoint)
+  .use(mplTokenMetadata())
+  .use(keypairIdentity(fromWeb3JsKeypair(payer.payer)));
+
+function findPda(seed: string, ...keys: PublicKey[]) {
+  return PublicKey.findProgramAddressSync([
+    utils.bytes.utf8.encode(seed),
+    ...keys.map(key => key.toBytes())
+  ], programId)[0];
+}
+
+interface Staking {
+  collectionMint: KeypairSigner;
+  stakeDetails: PublicKey;
+  tokenAuthority: PublicKey;
+  nftAuthority: PublicKey;
+  stakerAccount: PublicKey;
+  tokenMint: PublicKey;
+  tokenAccount: PublicKey;
+  stakeTokenVault: PublicKey;
+}
+
+interface Nft {
+  nftMint: PublicKey;
+  nftToken: PublicKey;
+  nftMetadata: PublicKey;
+  nftEdition: PublicKey;
+  nftCustody: PublicKey;
+  nftRecord: PublicKey;
+  rewardOverride: PublicKey;
+}
+
+// A staking of a new verified collection, funded from a new reward mint and live from now
+async function createStaking(reward: number, minimumPeriod: number, maxStakersCount: number): Promise<Staking> {
+  const collectionMint = generateSigner(umi);
+
+  await createNft(umi, {
+    mint: collectionMint,
+    name: "Collection",
+    uri: "https://example.com/collection.json",
+    sellerFeeBasisPoints: percentAmount(0),
+    isCollection: true
+  }).sendAndConfirm(umi);
+
+  const collectionAddress = toWeb3JsPublicKey(collectionMint.publicKey);
+  const stakeDetails = findPda("stake", collectionAddress, payer.publicKey);
+  const tokenAuthority = findPda("token-authority", stakeDetails);
+  const nftAuthority = findPda("nft-authority", stakeDetails);
+  const stakerAccount = findPda("staker", stakeDetails, payer.publicKey);
+
+  const tokenMint = await token.createMint(connection, payer.payer, payer.publicKey, null, 0);
+  const tokenAccount = await token.createAssociatedTokenAccount(connection, payer.payer, tokenMint, payer.publicKey);
+  await token.mintTo(connection, payer.payer, tokenMint, tokenAccount, payer.payer, 100_000_000);
+
+  const stakeTokenVault = token.getAssociatedTokenAddressSync(tokenMint, tokenAuthority, true);
+
+  // The time of the validator, which can lag behind the wall clock
+  const startTime = new BN(await connection.getBlockTime(await connection.getSlot()));
+
+  await program.methods.initStaking({
+    reward: new BN(reward),
+    minimumPeriod: new BN(minimumPeriod),
+    stakingStartsAt: startTime,
+    stakingEndsAt: startTime.add(new BN(3600)),
+    maxStakersCount: new BN(maxStakersCount),
+    nonCustodial: false,
+    emissionCurve: { constant: {} }
+  })
+  .accounts({
+    stakeDetails,
+    tokenMint,
+    tokenAuthority,
+    collectionAddress,
+    nftAuthority,
+    stakeTokenVault,
+    tokenAccount
+  })
+  .rpc();
+
+  return { collectionMint, stakeDetails, tokenAuthority, nftAuthority, stakerAccount, tokenMint, tokenAccount, stakeTokenVault };
+}
+
+// Mints an NFT to the wallet, verified in the collection of the staking
+async function mintNft(staking: Staking, name: string): Promise<Nft> {
+  const mint = generateSigner(umi);
+
+  await createNft(umi, {
+    mint,
+    name,
+    uri: "https://example.com/nft.json",
+    sellerFeeBasisPoints: percentAmount(5),
+    collection: some({ key: staking.collectionMint.publicKey, verified: false })
+  }).sendAndConfirm(umi);
+
+  await verifyCollectionV1(umi, {
+    metadata: findMetadataPda(umi, { mint: mint.publicKey }),
+    collectionMint: staking.collectionMint.publicKey,
+    authority: umi.identity
+  }).sendAndConfirm(umi);
+
+  const nftMint = toWeb3JsPublicKey(mint.publicKey);
+
+  return {
+    nftMint,
+    nftToken: token.getAssociatedTokenAddressSync(nftMint, payer.publicKey),
+    nftMetadata: toWeb3JsPublicKey(findMetadataPda(umi, { mint: mint.publicKey })[0]),
+    nftEdition: toWeb3JsPublicKey(findMasterEditionPda(umi, { mint: mint.publicKey })[0]),
+    nftCustody: token.getAssociatedTokenAddressSync(nftMint, staking.nftAuthority, true),
+    nftRecord: findPda("nft-record", staking.stakeDetails, nftMint),
+    rewardOverride: findPda("reward-override", staking.stakeDetails, nftMint)
+  };
+}
+
+async function mintNfts(staking: Staking, count: number) {
+  const nfts: Nft[] = [];
+
+  for (let i = 0; i < count; i++) {
+    nfts.push(await mintNft(staking, `NFT #${i + 1}`));
+  }
+
+  return nfts;
+}
+
+function stakeMany(staking: Staking, nfts: Nft[]) {
+  return program.methods.stakeMany(nfts.map(() => ({ allowlistProof: [], rarity: null })), null)
+  .accounts({
+    stakeDetails: staking.stakeDetails,
+    stakerAccount: staking.stakerAccount,
+    nftAuthority: staking.nftAuthority,
+    metadataProgram
+  })
+  .remainingAccounts(([] as AccountMeta[]).concat(...nfts.map(nft => [
+    { pubkey: nft.nftRecord, isSigner: false, isWritable: true },
+    { pubkey: nft.rewardOverride, isSigner: false, isWritable: false },
+    { pubkey: nft.nftMint, isSigner: false, isWritable: false },
+    { pubkey: nft.nftToken, isSigner: false, isWritable: true },
+    { pubkey: nft.nftMetadata, isSigner: false, isWritable: false },
+    { pubkey: nft.nftEdition, isSigner: false, isWritable: false },
+    { pubkey: nft.nftCustody, isSigner: false, isWritable: true }
+  ])))
+  .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })]);
+}
+
+function stake(staking: Staking, nft: Nft) {
+  return program.methods.stake([], null, null)
+  .accounts({
+    stakeDetails: staking.stakeDetails,
+    nftRecord: nft.nftRecord,
+    stakerAccount: staking.stakerAccount,
+    rewardOverride: nft.rewardOverride,
+    nftMint: nft.nftMint,
+    nftToken: nft.nftToken,
+    nftMetadata: nft.nftMetadata,
+    nftEdition: nft.nftEdition,
+    nftAuthority: staking.nftAuthority,
+    nftCustody: nft.nftCustody,
+    nftTokenRecord: null,
+    custodyTokenRecord: null,
+    authRules: null,
+    authRulesProgram: null,
+    sysvarInstructions: null,
+    metadataProgram
+  });
+}
+
+function unstake(staking: Staking, nft: Nft, penaltyTreasury: PublicKey | null = null) {
+  return program.methods.unstake()
+  .accounts({
+    stakeDetails: staking.stakeDetails,
+    nftRecord: nft.nftRecord,
+    stakerAccount: staking.stakerAccount,
+    vestingEscrow: null,
+    rewardOverride: nft.rewardOverride,
+    rewardMint: staking.tokenMint,
+    stakeTokenVault: staking.stakeTokenVault,
+    rewardReceiveAccount: staking.tokenAccount,
+    penaltyTreasury,
+    nftMint: nft.nftMint,
+    nftReceiveAccount: nft.nftToken,
+    nftCustody: nft.nftCustody,
+    nftMetadata: nft.nftMetadata,
+    nftEdition: nft.nftEdition,
+    custodyTokenRecord: null,
+    receiveTokenRecord: null,
+    authRules: null,
+    authRulesProgram: null,
+    sysvarInstructions: null,
+    tokenAuthority: staking.tokenAuthority,
+    nftAuthority: staking.nftAuthority,
+    rewardTokenProgram: token.TOKEN_PROGRAM_ID,
+    metadataProgram
+  });
+}
+
+function withdrawRewardsBatch(staking: Staking, nfts: Nft[]) {
+  return program.methods.withdrawRewardsBatch()
+  .accounts({
+    stakeDetails: staking.stakeDetails,
+    stakerAccount: staking.stakerAccount,
+    vestingEscrow: null,
+    rewardMint: staking.tokenMint,
+    stakeTokenVault: staking.stakeTokenVault,
+    rewardReceiveAccount: staking.tokenAccount,
+    tokenAuthority: staking.tokenAuthority
+  })
+  .remainingAccounts(([] as AccountMeta[]).concat(...nfts.map(nft => [
+    { pubkey: nft.nftRecord, isSigner: false, isWritable: true },
+    { pubkey: nft.rewardOverride, isSigner: false, isWritable: false }
+  ])));
+}
+
+async function balanceOf(tokenAccount: PublicKey) {
+  return Number((await token.getAccount(connection, tokenAccount)).amount);
+}
+
+function sleep(ms: number) {
+  return new Promise(resolve => setTimeout(resolve, ms));
+}
+
+describe("nft-stake-vault batch stake", () => {
+  let staking: Staking;
+  let nfts: Nft[];
+
+  before(async() => {
+    staking = await createStaking(1, 0, 10);
+  });
+
+  it("stakes a batch of NFTs in one transaction", async() => {
+    nfts = await mintNfts(staking, 3);
+
+    await stakeMany(staking, nfts).rpc();
+
+    for (const nft of nfts) {
+      const nftRecordAccount = await program.account.nftRecord.fetch(nft.nftRecord);
+      expect(nftRecordAccount.nftMint.toBase58()).to.equal(nft.nftMint.toBase58());
+      expect(nftRecordAccount.staker.toBase58()).to.equal(payer.publicKey.toBase58());
+      expect(nftRecordAccount.multiplier.toNumber()).to.equal(10000);
+
+      // Each NFT moved into its custody account
+      expect(await balanceOf(nft.nftCustody)).to.equal(1);
+      expect(await balanceOf(nft.nftToken)).to.equal(0);
+    }
+
+    const stakeAccount = await program.account.details.fetch(staking.stakeDetails);
+    expect(stakeAccount.currentStakersCount.toNumber()).to.equal(3);
+    expect(stakeAccount.stakedMultiplier.toNumber()).to.equal(3 * 10000);
+
+    // The staker account counts the whole batch, first staked with it
+    const stakerAccount = await program.account.stakerAccount.fetch(staking.stakerAccount);
+    const stakedAt = (await program.account.nftRecord.fetch(nfts[0].nftRecord)).stakedAt.toNumber();
+    expect(stakerAccount.stakedCount.toNumber()).to.equal(3);
+    expect(stakerAccount.firstStakedAt.toNumber()).to.equal(stakedAt);
+  });
+
+  it("stakes an empty batch and fails", async() => {
+    let error;
+
+    try {
+      await stakeMany(staking, []).rpc();
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error.error.errorCode.code).to.equal("InvalidBatchAccounts");
+  });
+
+  it("withdraws the rewards of a batch of NFTs", async() => {
+    await sleep(2000);
+
+    const stakedAtBefore = await Promise.all(nfts.map(async nft => (await program.account.nftRecord.fetch(nft.nftRecord)).stakedAt.toNumber()));
+    const balanceBefore = await balanceOf(staking.tokenAccount);
+
+    await withdrawRewardsBatch(staking, nfts).rpc();
+
+    const stakedAtAfter = await Promise.all(nfts.map(async nft => (await program.account.nftRecord.fetch(nft.nftRecord)).stakedAt.toNumber()));
+    const balanceAfter = await balanceOf(staking.tokenAccount);
+
+    // Each NFT earns 1 token a second since its last withdraw
+    const expectedReward = stakedAtAfter.reduce((sum, stakedAt, i) => sum + stakedAt - stakedAtBefore[i], 0);
+    expect(expectedReward).to.be.greaterThan(0);
+    expect(balanceAfter - balanceBefore).to.equal(expectedReward);
+  });
+
+  it("withdraws the reward of a record passed twice only once", async() => {
+    await sleep(2000);
+
+    const [nft, ...otherNfts] = nfts;
+    const stakedAtBefore = (await program.account.nftRecord.fetch(nft.nftRecord)).stakedAt.toNumber();
+    const otherStakedAt = await Promise.all(otherNfts.map(async other => (await program.account.nftRecord.fetch(other.nftRecord)).stakedAt.toNumber()));
+    const balanceBefore = await balanceOf(staking.tokenAccount);
+
+    await withdrawRewardsBatch(staking, [nft, nft]).rpc();
+
+    const stakedAtAfter = (await program.account.nftRecord.fetch(nft.nftRecord)).stakedAt.toNumber();
+    const balanceAfter = await balanceOf(staking.tokenAccount);
+
+    // The second pass finds the record already paid up to now
+    expect(stakedAtAfter - stakedAtBefore).to.be.greaterThan(0);
+    expect(balanceAfter - balanceBefore).to.equal(stakedAtAfter - stakedAtBefore);
+
+    // The records left out of the batch keep accruing
+    for (const [i, other] of otherNfts.entries()) {
+      expect((await program.account.nftRecord.fetch(other.nftRecord)).stakedAt.toNumber()).to.equal(otherStakedAt[i]);
+    }
+  });
+});
+
+describe("nft-stake-vault wallet limit", () => {
+  let staking: Staking;
+
+  before(async() => {
+    staking = await createStaking(1, 0, 10);
+
+    await program.methods.setMaxPerWallet(new BN(2))
+    .accounts({
+      stakeDetails: staking.stakeDetails
+    })
+    .rpc();
+  });
+
+  it("stakes up to the wallet limit", async() => {
+    const [first, second] = await mintNfts(staking, 2);
+
+    await stake(staking, first).rpc();
+    await stakeMany(staking, [second]).rpc();
+
+    const stakerAccount = await program.account.stakerAccount.fetch(staking.stakerAccount);
+    expect(stakerAccount.stakedCount.toNumber()).to.equal(2);
+  });
+
+  it("stakes past the wallet limit and fails", async() => {
+    const nft = await mintNft(staking, "NFT #3");
+    let error;
+
+    try {
+      await stake(staking, nft).rpc();
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error.error.errorCode.code).to.equal("WalletLimitReached");
+    expect(await connection.getAccountInfo(nft.nftRecord)).to.be.null;
+  });
+
+  it("stakes a batch past the wallet limit and fails", async() => {
+    await program.methods.setMaxPerWallet(new BN(3))
+    .accounts({
+      stakeDetails: staking.stakeDetails
+    })
+    .rpc();
+
+    // One more NFT fits in the limit, the batch of two doesn't
+    const nfts = await mintNfts(staking, 2);
+    let error;
+
+    try {
+      await stakeMany(staking, nfts).rpc();
+    } catch(e) {
+      error = e;
+    }
+
+    expect(error.error.errorCode.code).to.equal("WalletLimitReached");
+
+    for (const nft of nfts) {
+      expect(await connection.getAccountInfo(nft.nftRecord)).to.be.null;
+    }
+  });
+});
+
+describe("nft-stake-vault unstake penalty", () => {
+  // 100 tokens a second, with the whole reward at stake over a minimum period of 10 seconds
+  const reward = 100;
+  const minimumPeriod = 10;
+  const unstakePenalty = 10000;
+
+  let staking: Staking;
+
+  // The forfeit of a reward earned over its share of the minimum period, rounded down like the program
+  function expectedForfeit(stakedReward: number) {
+    const stakedFor = stakedReward / reward;
+
+    return Math.floor(stakedReward * (unstakePenalty / 10000) * (minimumPeriod - stakedFor) / minimumPeriod);
+  }
+
+  function setPenalty(penaltyDestination: object, penaltyTreasury: PublicKey | null = null) {
+    return program.methods.setUnstakePenalty(new BN(unstakePenalty), penaltyDestination as any)
+    .accounts({
+      stakeDetails: staking.stakeDetails,
+      rewardMint: staking.tokenMint,
+      penaltyTreasury
+    })
+    .rpc();
+  }
+
+  // Stakes a new NFT and unstakes it after about the given time,
+  // returning the reward received and what left the stake vault
+  async function stakeAndUnstake(ms: number, penaltyTreasury: PublicKey | null = null) {
+    const nft = await mintNft(staking, "NFT");
+    await stake(staking, nft).rpc();
+    await sleep(ms);
+
+    const receivedBefore = await balanceOf(staking.tokenAccount);
+    const vaultBefore = await balanceOf(staking.stakeTokenVault);
+
+    await unstake(staking, nft, penaltyTreasury).rpc();
+
+    return {
+      received: await balanceOf(staking.tokenAccount) - receivedBefore,
+      debited: vaultBefore - await balanceOf(staking.stakeTokenVault)
+    };
+  }
+
+  before(async() => {
+    staking = await createStaking(reward, minimumPeriod, 10);
+  });
+
+  it("burns most of the reward unstaked at the start of the minimum period", async() => {
+    await setPenalty({ burn: {} });
+
+    const supplyBefore = Number((await token.getMint(connection, staking.tokenMint)).supply);
+    const { received, debited } = await stakeAndUnstake(1000);
+    const supplyAfter = Number((await token.getMint(connection, staking.tokenMint)).supply);
+
+    const forfeit = debited - received;
+    expect(forfeit).to.be.greaterThan(received);
+    expect(forfeit).to.equal(expectedForfeit(debited));
+
+    // The forfeit leaves the supply
+    expect(supplyBefore - supplyAfter).to.equal(forfeit);
+  });
+
+  it("sends part of the reward unstaked in the middle of the minimum period to the treasury", async() => {
+    const treasuryOwner = anchor.web3.Keypair.generate();
+    const penaltyTreasury = await token.createAssociatedTokenAccount(connection, payer.payer, staking.tokenMint, treasuryOwner.publicKey);
+
+    await setPenalty({ treasury: {} }, penaltyTreasury);
+
+    const { received, debited } = await stakeAndUnstake(5000, penaltyTreasury);
+
+    const forfeit = debited - received;
+    expect(forfeit).to.be.greaterThan(0);
+    expect(received).to.be.greaterThan(0);
+    expect(forfeit).to.equal(expectedForfeit(debited));
+    expect(await balanceOf(penaltyTreasury)).to.equal(forfeit);
+  });
+
+  it("keeps the forfeited reward in the stake vault for the other stakers", async() => {
+    await setPenalty({ vault: {} });
+
+    const currentBalanceBefore = (await program.account.details.fetch(staking.stakeDetails)).currentBalance.toNumber();
+    const { received, debited } = await stakeAndUnstake(1000);
+    const currentBalanceAfter = (await program.account.details.fetch(staking.stakeDetails)).currentBalance.toNumber();
+
+    // Only the received reward leaves the vault and its balance in record
+    expect(received).to.be.greaterThan(0);
+    expect(debited).to.equal(received);
+    expect(currentBalanceBefore - currentBalanceAfter).to.equal(received);
+  });
+
+  it("forfeits nothing once the minimum period ends", async() => {
+    await setPenalty({ burn: {} });
+
+    const supplyBefore = Number((await token.getMint(connection, staking.tokenMint)).supply);
+    const { received, debited } = await stakeAndUnstake((minimumPeriod + 1) * 1000);
+    const supplyAfter = Number((await token.getMint(connection, staking.tokenMint)).supply);
+
+    expect(received).to.be.at.least(reward * minimumPeriod);
+    expect(debited).to.equal(received);
+    expect(supplyAfter).to.equal(supplyBefore);
+  });
+});